@@ -1,6 +1,37 @@
 extern crate bindgen;
 
+use std::env;
+
+/// Versions of Lua that can be selected with a feature, the API they
+/// implement and the library to link against for them.
+const LUA_VERSIONS: &[(&str, &str, Option<&str>)] = &[
+    ("LUA51", "51", Some("lua5.1")),
+    ("LUA52", "52", Some("lua5.2")),
+    // lua-sys links against Lua 5.3 on its own
+    ("LUA53", "53", None),
+    ("LUAJIT", "51", Some("luajit-5.1"))
+];
+
+/// Tells rustc which Lua API to compile `compat` and `lua_sys` for.
+fn select_lua_version() {
+    let enabled: Vec<_> = LUA_VERSIONS.iter()
+        .filter(|&&(feature, _, _)|
+                env::var(format!("CARGO_FEATURE_{}", feature)).is_ok())
+        .collect();
+    if enabled.len() != 1 {
+        panic!("Exactly one of the lua51, lua52, lua53 and luajit features \
+                must be enabled, use --no-default-features to disable lua53");
+    }
+    let &(_, api, lib) = enabled[0];
+    println!("cargo:rustc-check-cfg=cfg(lua_api, values(\"51\", \"52\", \"53\"))");
+    println!("cargo:rustc-cfg=lua_api=\"{}\"", api);
+    if let Some(lib) = lib {
+        println!("cargo:rustc-link-lib={}", lib);
+    }
+}
+
 fn main() {
+    select_lua_version();
     let generated = bindgen::builder()
         .header("src/cairo_xcb.h")
         .ctypes_prefix("libc")
@@ -33,6 +33,64 @@ end)
 print("Calling button constructor...")
 print(dump(button({a = 5})))
 
+print("Button class info:")
+local info = awesome.class_info("button")
+assert(info.name == "button")
+print(dump(info))
+
+print("Weak signal handlers...")
+local called = 0
+local handler = function() called = called + 1 end
+button.weak_connect_signal("weak::test", handler)
+button.connect_signal{name = "weak::test", func = function() called = called + 1 end,
+                      weak = true}
+collectgarbage("collect")
+button.emit_signal("weak::test")
+assert(called == 1)
+handler = nil
+collectgarbage("collect")
+button.emit_signal("weak::test")
+assert(called == 1)
+
+print("Class signals...")
+-- Bad arguments don't leave the class locked
+assert(not pcall(button.connect_signal, "bad::test", 1))
+assert(not pcall(button.disconnect_signal, "bad::test", 1))
+assert(not pcall(button.set_index_miss_handler, 1))
+-- Handlers can use the class while it emits
+local inner = function() called = called + 1 end
+button.connect_signal("nested::test", function()
+  button.connect_signal("nested::test", inner)
+end)
+button.emit_signal("nested::test")
+assert(called == 1)
+button.emit_signal("nested::test")
+assert(called == 2)
+button.disconnect_signal("nested::test", inner)
+-- Names don't have to be valid UTF-8
+button.emit_signal("bad::\255")
+
+print("Index miss handlers...")
+local b = button({})
+local missed = {}
+button.set_index_miss_handler(function(obj, key)
+  assert(obj == b)
+  return "missed " .. key
+end)
+button.set_newindex_miss_handler(function(obj, key, value)
+  missed[key] = value
+end)
+assert(b.shape == "missed shape")
+b.shape = "rounded"
+assert(missed.shape == "rounded")
+-- Known properties don't reach the handlers
+b.button = 3
+assert(b.button == 3 and missed.button == nil)
+-- Every class can be extended
+for _, class in ipairs({screen, tag, drawin}) do
+  class.set_index_miss_handler(function() end)
+  class.set_newindex_miss_handler(function() end)
+end
 
 --assert(button ~= nil)
 --assert(getmetatable(button).__index ~= nil)
@@ -3,10 +3,8 @@
 #![allow(unused_variables)]
 
 #[macro_use] extern crate awesome_wayland;
-#[macro_use] extern crate lazy_static;
-extern crate lua_sys;
 extern crate libc;
-use lua_sys::*;
+use awesome_wayland::lua_sys::*;
 use libc::c_int;
 
 use awesome_wayland::{Lua, LuaErr, Awesome};
@@ -59,9 +57,9 @@ impl callbacks::Button for DummyStruct {
         //button___call,
         //button_connect_signal,
         button_disconnect_signal,
-        button_emit_signal,
-        button_set_index_miss_handler,
-        button_set_newindex_miss_handler,
+        //button_emit_signal,
+        //button_set_index_miss_handler,
+        //button_set_newindex_miss_handler,
         button_add_signal,
         button_instances,
         button,
@@ -101,47 +99,6 @@ impl callbacks::Client for DummyStruct {
         client_titlebar_left,
         client_get_icon
     ]);
-    // Properties
-    default_impl!([
-        client_name,
-        client_transient_for,
-        client_skip_taskbar,
-        client_content,
-        client_type_,
-        client_class,
-        client_instance,
-        client_role,
-        client_pid,
-        client_leader_window,
-        client_machine,
-        client_icon_name,
-        client_screen,
-        client_hidden,
-        client_minimized,
-        client_fullscreen,
-        client_modal,
-        client_group_window,
-        client_maximized,
-        client_maximized_horizontal,
-        client_maximized_vertical,
-        client_icon,
-        client_icon_sizes,
-        client_ontop,
-        client_above,
-        client_below,
-        client_sticky,
-        client_size_hints_honor,
-        client_urgent,
-        client_size_hints,
-        client_focusable,
-        client_shape_bounding,
-        client_shape_clip,
-        client_shape_input,
-        client_startup_id,
-        client_client_shape_bounding,
-        client_client_shape_clip,
-        client_first_tag
-    ]);
 }
 impl callbacks::Drawin for DummyStruct {
     default_impl!([
@@ -150,8 +107,8 @@ impl callbacks::Drawin for DummyStruct {
         drawin_disconnect_signal,
         drawin_emit_signal,
         drawin_instances,
-        drawin_set_index_miss_handler,
-        drawin_set_newindex_miss_handler,
+        //drawin_set_index_miss_handler,
+        //drawin_set_newindex_miss_handler,
         drawin___call,
         drawin_geometry,
         drawin_drawable,
@@ -218,8 +175,8 @@ impl callbacks::Screen for DummyStruct {
         screen_disconnect_signal,
         screen_emit_signal,
         screen_instances,
-        screen_set_index_miss_handler,
-        screen_set_newindex_miss_handler,
+        //screen_set_index_miss_handler,
+        //screen_set_newindex_miss_handler,
         screen_count,
         screen___index,
         screen___newindex,
@@ -241,8 +198,8 @@ impl callbacks::Tag for DummyStruct {
         tag_disconnect_signal,
         tag_emit_signal,
         tag_instances,
-        tag_set_index_miss_handler,
-        tag_set_newindex_miss_handler,
+        //tag_set_index_miss_handler,
+        //tag_set_newindex_miss_handler,
         tag___call,
         tag_clients_meta,
         tag_name,
@@ -257,12 +214,11 @@ impl Default for DummyStruct {
     }
 }
 
-register_for_lua!(DummyStruct, AWESOME);
-
 fn main() {
-    register_all!(DummyStruct, AWESOME);
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
 
-    match LUA.load_and_run(PathBuf::from("examples/class-test.lua")) {
+    match awesome.lua.load_and_run(PathBuf::from("examples/class-test.lua")) {
         Ok(_) => {},
         Err(LuaErr::Load(_)) => {
             println!("Could not find lua file! Please run this from the root \
@@ -0,0 +1,178 @@
+-- `c` is a client the compositor manages, on the only screen
+
+local emitted = {}
+client.connect_signal("property::name", function(c)
+  table.insert(emitted, c.name)
+end)
+
+print("Starting client property test")
+assert(c.valid)
+c.name = "term"
+assert(c.name == "term")
+assert(#emitted == 1 and emitted[1] == "term")
+-- Setting the same value again changes nothing
+c.name = "term"
+assert(#emitted == 1)
+
+-- Geometry
+local geometry_signals = {}
+for _, name in ipairs{"geometry", "position", "size", "x", "width"} do
+  client.connect_signal("property::" .. name, function(cl)
+    geometry_signals[cl] = (geometry_signals[cl] or "") .. name .. " "
+  end)
+end
+c.x = 10
+c.width = 640
+assert(c.x == 10 and c.y == 0 and c.width == 640)
+-- Moving and resizing go through the same path, which signals both
+assert(geometry_signals[c] == "geometry position x geometry size width ")
+geometry_signals[c] = nil
+c.x = 10
+assert(geometry_signals[c] == nil)
+
+-- Opacity is kept between 0 and 1
+c.opacity = 2
+assert(c.opacity == 1)
+
+-- Clients that accept the focus are focusable, unless Lua says otherwise
+assert(c.focusable)
+c.focusable = false
+assert(not c.focusable)
+
+-- Clients are docks if Lua says so
+assert(not c.dockable and not d.dockable)
+c.dockable = true
+assert(c.dockable)
+c.dockable = false
+c.border_color = "#ff0000"
+assert(c.border_color == "#ff0000")
+
+-- Properties only the compositor knows are nil unless it implements them
+assert(c.icon == nil and c.content == nil and c.first_tag == nil)
+c.shape_bounding = 1
+assert(c.shape_bounding == nil)
+local ok, err = pcall(function() c.first_tag = 1 end)
+assert(not ok and err:find("read%-only"))
+
+-- Screens are set by object or by number
+assert(c.screen == nil)
+c.screen = 1
+assert(c.screen ~= nil and c.screen == c.screen)
+c.screen = c.screen
+
+c.fullscreen = true
+c.ontop = true
+assert(c.fullscreen and c.ontop and not c.sticky)
+
+-- Properties set by the compositor can only be read
+assert(c.pid == nil and c.machine == nil)
+assert(c.window == 1)
+ok, err = pcall(function() c.class = "XTerm" end)
+assert(not ok and err:find("read%-only"))
+ok, err = pcall(function() c.fullscreen = "yes" end)
+assert(not ok and err:find("bad value for property 'fullscreen'"))
+ok, err = pcall(function() c.screen = 2 end)
+assert(not ok and err:find("number 2 is out of range"))
+-- A bad screen is an error that doesn't keep the configuration locked
+ok, err = pcall(client.get, 2)
+assert(not ok and err:find("invalid screen number"))
+ok, err = pcall(client.focus_history, {})
+assert(not ok and err:find("screen"))
+assert(#client.get(1) == 1)
+print("Finished client property test")
+
+print("Starting size hints test")
+-- `t` is a terminal with a border of 1, a base size of 4x4, a minimum size
+-- of 20x36 and that grows by 8x16
+assert(t.size_hints.base_width == 4 and t.size_hints.min_height == 36)
+assert(t.size_hints.width_inc == 8 and t.size_hints.height_inc == 16)
+assert(t.size_hints.win_gravity == "center")
+assert(t.size_hints.max_width == nil)
+local w, h = t:apply_size_hints(100, 100)
+assert(w == 94 and h == 86)
+w, h = t:apply_size_hints(10, 10)
+assert(w == 22 and h == 38)
+-- Sizes that don't fit aren't wrapped around
+ok, err = pcall(t.apply_size_hints, t, -1, 100)
+assert(not ok and err:find("number %-1 is out of range"))
+ok, err = pcall(t.apply_size_hints, t, 100, 65536)
+assert(not ok and err:find("number 65536 is out of range"))
+-- The hints are honored when resizing
+t.width = 100
+assert(t.width == 94 and t.height == 38)
+assert(geometry_signals[t] == "geometry size width ")
+-- Fullscreen clients keep their size
+assert(c:apply_size_hints(100, 100) == c.width)
+print("Finished size hints test")
+
+print("Starting stacking test")
+-- `d` and `e` are dialogs of `t`, `d` is modal
+local names = {[c] = "c", [t] = "t", [d] = "d", [e] = "e"}
+local function named(clients)
+  local found = {}
+  for _, listed in ipairs(clients) do
+    table.insert(found, names[listed])
+  end
+  return table.concat(found, " ")
+end
+local function stacked(s)
+  return named(client.get(s, true))
+end
+local raised, lowered = {}, {}
+client.connect_signal("raised", function(c) table.insert(raised, names[c]) end)
+client.connect_signal("lowered", function(c) table.insert(lowered, names[c]) end)
+
+-- From the top, dialogs above their parent and modal ones above the others
+assert(stacked() == "c d e t")
+assert(stacked(1) == "c")
+-- An unfocused fullscreen client is a normal one
+c.ontop = false
+assert(stacked() == "d e t c")
+c:raise()
+assert(stacked() == "c d e t")
+-- Dialogs are raised with their parent
+d:raise()
+assert(stacked() == "d e t c")
+c.above = true
+assert(stacked() == "c d e t")
+c.above = false
+assert(stacked() == "d e t c")
+t:lower()
+assert(stacked() == "c d e t")
+assert(table.concat(raised, " ") == "c d" and table.concat(lowered, " ") == "t")
+assert(t.type == "normal" and d.type == "dialog")
+print("Finished stacking test")
+
+print("Starting focus test")
+local focused, unfocused = {}, {}
+client.connect_signal("focus", function(c) table.insert(focused, names[c]) end)
+client.connect_signal("unfocus", function(c) table.insert(unfocused, names[c]) end)
+assert(client.focus == nil)
+-- `c` was made unfocusable
+client.focus = c
+assert(client.focus == nil and #focused == 0)
+client.focus = t
+assert(client.focus == t)
+-- Focusing a client clears its urgency
+d.urgent = true
+client.focus = d
+assert(client.focus == d and not d.urgent)
+-- A focused fullscreen client is above the others
+c.focusable = true
+c:lower()
+assert(stacked() == "d e t c")
+client.focus = c
+assert(stacked() == "c d e t")
+assert(named(client.focus_history()) == "c d t")
+assert(named(client.focus_history(1)) == "c")
+client.focus = nil
+assert(client.focus == nil)
+assert(stacked() == "d e t c")
+-- Taking the focus away keeps the history
+assert(named(client.focus_history()) == "c d t")
+client.focus = nil
+assert(table.concat(focused, " ") == "t d c")
+assert(table.concat(unfocused, " ") == "t d c")
+print("Finished focus test")
+
+print("Finished test")
@@ -0,0 +1,148 @@
+//! Tests the properties of clients: reading and setting them from Lua,
+//! the `property::` signals and the notifications of the compositor.
+#![allow(unused_variables)]
+
+#[macro_use] extern crate awesome_wayland;
+extern crate libc;
+
+mod common;
+
+use std::ffi::CString;
+use std::ptr;
+use awesome_wayland::{Lua, luaA, LuaErr, Awesome};
+use awesome_wayland::callbacks::client::{client_new, ClientState, SizeHints};
+use awesome_wayland::callbacks::screen::{screen_new, ScreenState};
+use awesome_wayland::focus;
+use awesome_wayland::object::WindowType;
+use awesome_wayland::stack::StackedWindow;
+use common::DummyStruct;
+use awesome_wayland::lua_sys::*;
+
+/// Creates a client with a window, as the compositor would when it
+/// manages one, and sets it as the global `name`.
+fn new_client(lua: &Lua, name: &str, window: i32) -> *mut ClientState {
+    unsafe {
+        let client = client_new(lua.0) as *mut ClientState;
+        (*client).window.window = window;
+        luaA::object_ref(lua.0, -1);
+        luaA::object_push(lua.0, client as _);
+        let name = CString::new(name).unwrap();
+        lua_setglobal(lua.0, name.as_ptr());
+        client
+    }
+}
+
+/// The hints on their own, for what the terminal in the Lua test lacks.
+fn test_size_hints() {
+    let square = SizeHints {
+        aspect: Some(((1, 1), (1, 1))),
+        ..SizeHints::default()
+    };
+    assert_eq!(square.apply(200, 100), (100, 100));
+    assert_eq!(square.apply(100, 200), (100, 100));
+    let narrow = SizeHints {
+        max_size: Some((50, 0)),
+        ..SizeHints::default()
+    };
+    assert_eq!(narrow.apply(100, 100), (50, 100));
+    // An increment on one axis is applied without one on the other
+    let rows = SizeHints {
+        resize_inc: Some((0, 16)),
+        ..SizeHints::default()
+    };
+    assert_eq!(rows.apply(100, 100), (100, 96));
+    let columns = SizeHints {
+        resize_inc: Some((8, 0)),
+        ..SizeHints::default()
+    };
+    assert_eq!(columns.apply(100, 100), (96, 100));
+    assert_eq!(SizeHints::default().apply(100, 100), (100, 100));
+}
+
+fn main() {
+    test_size_hints();
+
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    let lua = &awesome.lua;
+    // Geometries that don't fit are refused instead of wrapping around
+    let area: luaA::area_t = lua.eval("return {x = -5, width = 10}").unwrap();
+    assert_eq!((area.x, area.y, area.width), (-5, 0, 10));
+    assert!(match lua.eval::<luaA::area_t>("return {width = -1}") {
+        Err(LuaErr::OutOfRange(_)) => true,
+        _ => false
+    });
+    unsafe {
+        let screen = screen_new(lua.0) as *mut ScreenState;
+        luaA::object_ref(lua.0, -1);
+        awesome.conf().screens.push(screen);
+    }
+    let client = new_client(lua, "c", 1);
+    awesome.conf().clients.push(client);
+    // A terminal, sized in characters of 8x16 pixels
+    let terminal = new_client(lua, "t", 2);
+    unsafe {
+        (*terminal).window.border_width = 1;
+        (*terminal).size_hints_honor = true;
+        (*terminal).size_hints = SizeHints {
+            base_size: Some((4, 4)),
+            min_size: Some((20, 36)),
+            resize_inc: Some((8, 16)),
+            win_gravity: Some(5),
+            ..SizeHints::default()
+        };
+    }
+    awesome.conf().clients.push(terminal);
+    // Its dialogs, the first one is modal
+    let dialog = new_client(lua, "d", 3);
+    let other_dialog = new_client(lua, "e", 4);
+    for &window in &[dialog, other_dialog] {
+        unsafe {
+            (*window).transient_for = terminal;
+            (*window).window.window_type = WindowType::Dialog;
+        }
+        awesome.conf().clients.push(window);
+    }
+    unsafe { (*dialog).modal = true; }
+    awesome.conf().stack = vec![client, terminal, dialog, other_dialog];
+
+    common::run_lua_file(lua, "examples/client-test.lua");
+
+    // Only the changes were passed on, in order
+    assert_eq!(*awesome.callbacks.changed.borrow(),
+               vec!["name", "x", "width", "opacity", "focusable", "dockable",
+                    "dockable", "border_color", "screen", "fullscreen", "ontop",
+                    "width", "ontop", "above", "above", "urgent", "urgent",
+                    "focusable"]);
+    // Restacked when a layer or the focus changed, and when raising or
+    // lowering
+    let restacked = awesome.callbacks.restacked.borrow();
+    assert_eq!(restacked.len(), 13);
+    assert_eq!(*restacked.last().unwrap(),
+               vec![StackedWindow::Client(client),
+                    StackedWindow::Client(terminal),
+                    StackedWindow::Client(other_dialog),
+                    StackedWindow::Client(dialog)]);
+    let none = ptr::null_mut();
+    assert_eq!(*awesome.callbacks.focused.borrow(),
+               vec![(none, terminal), (terminal, dialog), (dialog, client),
+                    (client, none)]);
+    assert!(awesome.conf().focus.client.is_null());
+    unsafe {
+        assert_eq!((*client).name, "term");
+        assert_eq!((*client).geometry.x, 10);
+        assert_eq!((*client).window.opacity, 1.0);
+        assert!((*client).fullscreen && (*client).ontop);
+        assert_eq!((*terminal).geometry.width, 94);
+    }
+
+    // Unmanaged clients are forgotten by the focus and the stack
+    lua.eval::<()>("client.focus = d").unwrap();
+    unsafe { lua.invalidate(dialog) }.unwrap();
+    assert!(awesome.conf().focus.client.is_null());
+    assert_eq!(unsafe { focus::focus_history(&awesome.conf(), None) },
+               vec![client, terminal]);
+    let history: usize = lua.eval("return #client.focus_history()").unwrap();
+    assert_eq!(history, 2);
+    assert!(!awesome.conf().stack.contains(&dialog));
+}
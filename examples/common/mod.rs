@@ -0,0 +1,183 @@
+//! The callbacks shared by the examples.
+//!
+//! Only the callbacks the library has no default for are stubbed, so the
+//! examples test the library's own implementation of everything else.
+#![allow(dead_code, unused_variables)]
+
+use std::cell::RefCell;
+use std::path::PathBuf;
+use awesome_wayland::{Lua, LuaErr};
+use awesome_wayland::callbacks;
+use awesome_wayland::callbacks::client::ClientState;
+use awesome_wayland::stack::StackedWindow;
+use libc::c_int;
+
+/// Records the properties, the stacking orders and the focus changes the
+/// compositor was told about.
+#[derive(Default)]
+pub struct DummyStruct {
+    pub changed: RefCell<Vec<String>>,
+    pub restacked: RefCell<Vec<Vec<StackedWindow>>>,
+    pub focused: RefCell<Vec<(*mut ClientState, *mut ClientState)>>
+}
+
+/// Defines the the default impl of a callback to do nothing.
+/// Save on a LOT of typing
+macro_rules! default_impl {
+    ($([ $( $inner:ident ),+ ])+) => {
+        $($(fn $inner(&self, lua: &Lua) -> c_int {0})*),*
+    };
+}
+
+impl callbacks::Awesome for DummyStruct {
+    default_impl!([
+        awesome_quit,
+        awesome_spawn,
+        awesome_systray,
+        awesome_load_image,
+        awesome_set_preferred_icon_size,
+        awesome_register_xproperty,
+        awesome_set_xproperty,
+        awesome_get_xproperty,
+        awesome_xkb_set_layout_group,
+        awesome_xkb_get_layout_group,
+        awesome_xkb_get_group_names,
+        awesome_xrdb_get_value,
+        awesome_kill,
+        awesome_sync
+    ]);
+}
+
+impl callbacks::Button for DummyStruct {
+}
+
+impl callbacks::Client for DummyStruct {
+    default_impl!([
+        client_keys,
+        client_isvisible,
+        client_geometry,
+        client_tags,
+        client_kill,
+        client_swap,
+        client_unmanange,
+        client_titlebar_top,
+        client_titlebar_right,
+        client_titlebar_bottom,
+        client_titlebar_left,
+        client_get_icon
+    ]);
+
+    fn client_property_changed(&self, lua: &Lua, client: &mut ClientState,
+                               property: &str) {
+        self.changed.borrow_mut().push(property.into());
+    }
+
+    fn client_restack(&self, lua: &Lua, windows: &[StackedWindow]) {
+        self.restacked.borrow_mut().push(windows.to_vec());
+    }
+
+    fn client_focus_changed(&self, lua: &Lua, old: *mut ClientState,
+                            new: *mut ClientState) {
+        self.focused.borrow_mut().push((old, new));
+    }
+}
+
+impl callbacks::Drawable for DummyStruct {
+    default_impl!([
+        drawable_refresh,
+        drawable_geometry
+    ]);
+}
+
+impl callbacks::Drawin for DummyStruct {
+    default_impl!([
+        drawin___call
+    ]);
+}
+
+impl callbacks::Keygrabber for DummyStruct {
+    default_impl!([
+        keygrabber_run,
+        keygrabber_stop,
+        keygrabber_isrunning,
+        keygrabber___index,
+        keygrabber___newindex
+    ]);
+}
+
+impl callbacks::Mousegrabber for DummyStruct {
+    default_impl!([
+        mousegrabber_run,
+        mousegrabber_stop,
+        mousegrabber_isrunning,
+        mousegrabber___index,
+        mousegrabber___newindex
+    ]);
+}
+
+impl callbacks::Mouse for DummyStruct {
+    default_impl!([
+        mouse___index,
+        mouse___newindex,
+        mouse_coords,
+        mouse_object_under_pointer,
+        mouse_set_index_miss_handler,
+        mouse_set_newindex_miss_handler
+    ]);
+}
+
+impl callbacks::Root for DummyStruct {
+    default_impl!([
+        root_buttons,
+        root_keys,
+        root_cursor,
+        root_fake_input,
+        root_drawins,
+        root_wallpaper,
+        root_size,
+        root_size_mm,
+        root_tags,
+        root___index,
+        root___newindex
+    ]);
+}
+
+impl callbacks::Screen for DummyStruct {
+    default_impl!([
+        screen_count,
+        screen___index,
+        screen___newindex,
+        screen___call,
+        screen_fake_add,
+        screen_fake_remove,
+        screen_fake_resize,
+        screen_swap,
+        screen_geometry,
+        screen_index,
+        screen_outputs,
+        screen_workarea
+    ]);
+}
+
+impl callbacks::Tag for DummyStruct {
+    default_impl!([
+        tag___call,
+        tag_clients_meta,
+        tag_name,
+        tag_selected,
+        tag_activated
+    ]);
+}
+
+/// Runs the Lua half of an example, exiting if it can't be found.
+pub fn run_lua_file(lua: &Lua, path: &str) {
+    match lua.load_and_run(PathBuf::from(path)) {
+        Ok(_) => {},
+        Err(LuaErr::Load(_)) => {
+            println!("Could not find lua file! Please run this from the root \
+                      of the project directory");
+            ::std::process::exit(1);
+        },
+        err => err.unwrap()
+    }
+}
@@ -0,0 +1,68 @@
+//! Tests looking up the configuration in the XDG directories.
+
+#[macro_use] extern crate awesome_wayland;
+extern crate libc;
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use awesome_wayland::Awesome;
+use awesome_wayland::awesome::config_search_paths;
+use common::DummyStruct;
+
+/// Writes `rc` as the `awesome/rc.lua` of the configuration directory.
+fn write_rc(dir: &Path, rc: &str) -> PathBuf {
+    fs::create_dir_all(dir.join("awesome")).unwrap();
+    let path = dir.join("awesome/rc.lua");
+    fs::write(&path, rc).unwrap();
+    path
+}
+
+fn main() {
+    let root = env::temp_dir()
+        .join(format!("awesome-config-search-test-{}", std::process::id()));
+    let home = root.join("home");
+    let empty = root.join("empty");
+    let system = root.join("system");
+    fs::create_dir_all(&empty).unwrap();
+    let broken = write_rc(&home, "error('broken configuration')");
+    let good = write_rc(&system, "loaded = awesome.conffile");
+    env::set_var("XDG_CONFIG_HOME", &home);
+    env::set_var("XDG_CONFIG_DIRS",
+                 env::join_paths(&[empty.clone(), PathBuf::from("relative"),
+                                   system.clone()]).unwrap());
+
+    // The explicit path comes first, relative directories are ignored
+    assert_eq!(config_search_paths(None),
+               vec![broken.clone(), empty.join("awesome/rc.lua"), good.clone()]);
+    let explicit = root.join("explicit.lua");
+    assert_eq!(config_search_paths(Some(explicit.clone()))[0], explicit);
+
+    // The broken configuration is skipped, and its error kept
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    assert_eq!(awesome.load_configuration(None).unwrap(), good);
+    {
+        let conf = awesome.conf();
+        assert_eq!(conf.startup_errors.len(), 1);
+        let error = &conf.startup_errors[0];
+        assert!(error.starts_with(&format!("{}: ", broken.display())));
+        assert!(error.contains("broken configuration"));
+        assert_eq!(conf.conffile, Some(good.clone()));
+    }
+    let (loaded, errors): (String, String) = awesome.lua
+        .eval("return loaded, awesome.startup_errors").unwrap();
+    assert_eq!(loaded, good.to_string_lossy());
+    assert!(errors.contains("broken configuration"));
+
+    // A missing explicit path is an error, instead of being skipped
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    assert_eq!(awesome.load_configuration(Some(explicit.clone())).unwrap(), good);
+    assert!(awesome.conf().startup_errors[0]
+            .starts_with(&format!("{}: ", explicit.display())));
+
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,30 @@
+print("Starting registry diagnostics test")
+-- Returns the references held by the signal of the owner
+local function held_by(owner, signal)
+  local found = {}
+  for _, reference in ipairs(awesome.diagnostics().references) do
+    for _, holder in ipairs(reference.signals) do
+      if holder.owner == owner and holder.signal == signal then
+        table.insert(found, reference)
+      end
+    end
+  end
+  return found
+end
+
+local function handler() end
+button.connect_signal("test::registry", handler)
+local held = held_by("button", "test::registry")
+assert(#held == 1 and held[1].class == "function" and held[1].refcount == 1)
+button.disconnect_signal("test::registry", handler)
+assert(#held_by("button", "test::registry") == 0)
+
+for _, class in ipairs(awesome.diagnostics().instances) do
+  if class.class == "button" then
+    assert(class.instances > 0)
+  end
+end
+assert(#awesome.diagnostics().errors == 0)
+print("Finished registry diagnostics test")
+
+print("Finished test")
@@ -0,0 +1,29 @@
+//! Tests the diagnostics of the object registry, from Rust and Lua.
+
+#[macro_use] extern crate awesome_wayland;
+extern crate libc;
+
+mod common;
+
+use awesome_wayland::{Lua, luaA, Awesome};
+use awesome_wayland::diagnostics::{self, RegistryError};
+use common::DummyStruct;
+
+/// Misuses of the object registry are reported, instead of printed.
+fn test_registry_errors(lua: &Lua) {
+    assert!(diagnostics::take_errors(lua).is_empty());
+    unsafe {
+        luaA::object_unref(lua.0, 0x10 as *mut _);
+    }
+    assert_eq!(diagnostics::errors(lua),
+               vec![RegistryError::ReferenceNotFound(0x10)]);
+    assert_eq!(diagnostics::take_errors(lua).len(), 1);
+    assert!(diagnostics::errors(lua).is_empty());
+}
+
+fn main() {
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    common::run_lua_file(&awesome.lua, "examples/diagnostics-test.lua");
+    test_registry_errors(&awesome.lua);
+}
@@ -0,0 +1,62 @@
+//! Tests emitting signals from Rust, with typed arguments.
+
+#[macro_use] extern crate awesome_wayland;
+extern crate libc;
+
+mod common;
+
+use awesome_wayland::{Lua, luaA, LuaErr, Awesome};
+use awesome_wayland::object::class::Object;
+use awesome_wayland::state;
+use common::DummyStruct;
+use awesome_wayland::lua_sys::*;
+
+/// Emits signals from Rust, with typed arguments.
+fn test_emit_from_rust(lua: &Lua) {
+    lua.eval::<()>(r#"
+        emitted = {}
+        local function record(name)
+            return function(...) emitted[name] = {...} end
+        end
+        rust_button = button({})
+        rust_button:connect_signal("test::rust", record("object"))
+        button.connect_signal("test::rust", record("class"))
+        awesome.connect_signal("test::rust", record("global"))
+    "#).unwrap();
+    let object = unsafe {
+        lua_getglobal(lua.0, c_str!("rust_button"));
+        &*(lua_touserdata(lua.0, -1) as *const Object)
+    };
+    /* Only objects in the registry can be pushed back from Rust */
+    assert_eq!(lua.emit_signal(object, "test::rust", ()),
+               Err(LuaErr::ObjectNotFound));
+    unsafe {
+        luaA::object_ref(lua.0, -1);
+    }
+
+    lua.emit_signal(object, "test::rust", (1, "two".to_string())).unwrap();
+    let (is_object, a, b): (bool, i32, String) =
+        lua.eval("return emitted.object[1] == rust_button, \
+                  emitted.object[2], emitted.object[3]").unwrap();
+    assert!(is_object && a == 1 && b == "two");
+    /* The class is also told about the object */
+    let is_object: bool = lua.eval("return emitted.class[1] == rust_button").unwrap();
+    assert!(is_object);
+
+    let button_class = unsafe { &state::get(lua.0).button_class };
+    lua.emit_class_signal(button_class, "test::rust", 3.5).unwrap();
+    let value: f64 = lua.eval("return emitted.class[1]").unwrap();
+    assert_eq!(value, 3.5);
+
+    lua.emit_global_signal("test::rust", (true, Some(7), None::<i32>)).unwrap();
+    let (a, b, c): (bool, i32, Option<i32>) =
+        lua.eval("return emitted.global[1], emitted.global[2], \
+                  emitted.global[3]").unwrap();
+    assert!(a && b == 7 && c.is_none());
+}
+
+fn main() {
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    test_emit_from_rust(&awesome.lua);
+}
@@ -0,0 +1,59 @@
+//! Tests registering Rust closures as Lua functions, and the errors they
+//! raise.
+
+#[macro_use] extern crate awesome_wayland;
+extern crate libc;
+
+mod common;
+
+use std::cell::Cell;
+use std::rc::Rc;
+use awesome_wayland::{Lua, Awesome, FromLua, ToLua, LuaErr, RustFn};
+use common::DummyStruct;
+use libc::c_int;
+
+fn main() {
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    let lua = &awesome.lua;
+
+    let calls = Rc::new(Cell::new(0));
+    let counter = calls.clone();
+    let functions: Vec<(String, RustFn)> = vec![
+        ("add".into(), Box::new(move |lua: &Lua| -> Result<c_int, LuaErr> {
+            counter.set(counter.get() + 1);
+            unsafe {
+                let a = i32::from_lua(lua.0, 1)?;
+                let b = i32::from_lua(lua.0, 2)?;
+                Ok((a + b).push_to_lua(lua.0))
+            }
+        })),
+        ("fail".into(), Box::new(|_: &Lua| -> Result<c_int, LuaErr> {
+            Err(LuaErr::ArgumentInvalid)
+        })),
+        ("panic".into(), Box::new(|_: &Lua| -> Result<c_int, LuaErr> {
+            panic!("from Rust")
+        }))
+    ];
+    lua.register_functions("rust", functions).unwrap();
+
+    let sum: i32 = lua.eval("return rust.add(1, 2)").unwrap();
+    assert_eq!(sum, 3);
+    assert_eq!(calls.get(), 1);
+
+    // Errors are raised in Lua, with their message
+    let (ok, message): (bool, String) =
+        lua.eval("return pcall(rust.add, 1, 'two')").unwrap();
+    assert!(!ok && message.contains("expected"));
+    let (ok, message): (bool, String) =
+        lua.eval("return pcall(rust.fail)").unwrap();
+    assert!(!ok && message == "invalid argument");
+
+    // Panics don't unwind in to Lua
+    let (ok, message): (bool, String) =
+        lua.eval("return pcall(rust.panic)").unwrap();
+    assert!(!ok && message == "from Rust");
+    // The state is still usable
+    let sum: i32 = lua.eval("return rust.add(2, 2)").unwrap();
+    assert_eq!(sum, 4);
+}
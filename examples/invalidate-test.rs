@@ -0,0 +1,115 @@
+//! Tests invalidating objects, and the checkers of their classes.
+
+#[macro_use] extern crate awesome_wayland;
+extern crate libc;
+
+mod common;
+
+use awesome_wayland::{Lua, luaA, LuaErr, Awesome};
+use awesome_wayland::object::class::Object;
+use awesome_wayland::callbacks::client::{client_new, ClientState};
+use awesome_wayland::callbacks::screen::{screen_new, ScreenState};
+use common::DummyStruct;
+use awesome_wayland::lua_sys::*;
+
+/// Invalidated objects emit `removed` and can no longer be used.
+fn test_invalidate(lua: &Lua) {
+    lua.eval::<()>(r#"
+        doomed = button({})
+        doomed:connect_signal("removed", function(b)
+            valid_when_removed = b.valid
+        end)
+    "#).unwrap();
+    let object = unsafe {
+        lua_getglobal(lua.0, c_str!("doomed"));
+        let object = lua_touserdata(lua.0, -1) as *mut Object;
+        luaA::object_ref(lua.0, -1);
+        object
+    };
+    unsafe { lua.invalidate(object) }.unwrap();
+    let (removed, valid): (bool, bool) =
+        lua.eval("return valid_when_removed, doomed.valid").unwrap();
+    assert!(removed && !valid);
+    let (ok, message): (bool, String) =
+        lua.eval("return pcall(function() return doomed.modifiers end)").unwrap();
+    assert!(!ok && message.contains("invalid button"));
+    /* Only once */
+    unsafe {
+        assert_eq!(lua.invalidate(object), Err(LuaErr::ObjectNotFound));
+        luaA::object_unref(lua.0, object as _);
+    }
+}
+
+/// Objects that their class checker considers invalid can't be used.
+///
+/// The dummy `__index` of screens does nothing, so the objects are indexed
+/// with `luaA::class_index` directly.
+fn test_checker(lua: &Lua) {
+    let screen = unsafe {
+        lua_pushcfunction(lua.0, Some(luaA::class_index));
+        lua_setglobal(lua.0, c_str!("index"));
+        let screen = screen_new(lua.0) as *mut ScreenState;
+        lua_setglobal(lua.0, c_str!("gone"));
+        &mut *screen
+    };
+    let valid: bool = lua.eval(r#"return index(gone, "valid")"#).unwrap();
+    assert!(valid);
+
+    screen.set_valid(false);
+    let (valid, ok, message): (bool, bool, String) = lua.eval(r#"
+        return index(gone, "valid"), pcall(index, gone, "data")
+    "#).unwrap();
+    assert!(!valid && !ok);
+    assert!(message.contains("property 'data' of an invalid screen object"));
+
+    screen.set_valid(true);
+    let (valid, data): (bool, String) =
+        lua.eval(r#"return index(gone, "valid"), type(index(gone, "data"))"#)
+           .unwrap();
+    assert!(valid && data == "table");
+}
+
+/// Invalidated objects are forgotten by the configuration.
+fn test_invalidate_conf(awesome: &Awesome<DummyStruct>) {
+    let lua = &awesome.lua;
+    let (client, screen) = unsafe {
+        let screen = screen_new(lua.0) as *mut ScreenState;
+        luaA::object_ref(lua.0, -1);
+        let client = client_new(lua.0) as *mut ClientState;
+        (*client).window.window = 1;
+        (*client).screen = screen as _;
+        luaA::object_ref(lua.0, -1);
+        (client, screen)
+    };
+    {
+        let mut conf = awesome.conf();
+        conf.screens.push(screen);
+        conf.primary_screen = screen;
+        conf.clients.push(client);
+        conf.stack.push(client);
+        conf.focus.client = client;
+    }
+    unsafe { lua.invalidate(client) }.unwrap();
+    {
+        let conf = awesome.conf();
+        assert!(conf.clients.is_empty() && conf.stack.is_empty());
+        assert!(conf.focus.client.is_null());
+    }
+    unsafe { lua.invalidate(screen) }.unwrap();
+    {
+        let conf = awesome.conf();
+        assert!(conf.screens.is_empty() && conf.primary_screen.is_null());
+    }
+    unsafe {
+        luaA::object_unref(lua.0, client as _);
+        luaA::object_unref(lua.0, screen as _);
+    }
+}
+
+fn main() {
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    test_invalidate(&awesome.lua);
+    test_checker(&awesome.lua);
+    test_invalidate_conf(&awesome);
+}
@@ -0,0 +1,138 @@
+//! Tests defining a Lua class from a Rust struct with `LuaClass`.
+
+#[macro_use] extern crate awesome_wayland;
+extern crate libc;
+
+mod common;
+
+use awesome_wayland::{Lua, LuaErr, Awesome, LuaClass, ClassProperty, RustFn};
+use awesome_wayland::lua_sys::*;
+use awesome_wayland::object::lua_class;
+use common::DummyStruct;
+
+#[derive(Default)]
+struct LayerSurface {
+    layer: u32,
+    namespace: String
+}
+
+impl LuaClass for LayerSurface {
+    fn name() -> &'static str {
+        "layer_surface"
+    }
+
+    fn properties() -> Vec<ClassProperty<Self>> {
+        vec![
+            ClassProperty::read_write("layer",
+                                      |surface: &Self| surface.layer,
+                                      |surface: &mut Self, layer| surface.layer = layer),
+            ClassProperty::read_only("namespace",
+                                     |surface: &Self| surface.namespace.clone())
+        ]
+    }
+
+    fn object_methods() -> Vec<(String, RustFn)> {
+        let raise: RustFn = Box::new(|lua: &Lua| {
+            let surface = unsafe { lua_class::to_object::<LayerSurface>(lua, 1) }
+                .ok_or(LuaErr::ArgumentInvalid)?;
+            surface.layer += 1;
+            Ok(0)
+        });
+        vec![("raise".into(), raise)]
+    }
+}
+
+/// Reads the layer of the surface in the global `name` from Rust.
+fn layer(lua: &Lua, name: &str) -> Option<u32> {
+    let name = ::std::ffi::CString::new(name).unwrap();
+    unsafe {
+        lua_getglobal(lua.0, name.as_ptr());
+        let layer = lua_class::to_object::<LayerSurface>(lua, -1)
+            .map(|surface| surface.layer);
+        lua_pop(lua.0, 1);
+        layer
+    }
+}
+
+fn main() {
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    let lua = &awesome.lua;
+    lua_class::register::<LayerSurface>(lua).unwrap();
+    assert_eq!(lua_class::register::<LayerSurface>(lua),
+               Err(LuaErr::AlreadyDefined("layer_surface".into())));
+
+    lua.eval::<()>("created = 0 \
+                    layer_surface.connect_signal('new', function() \
+                        created = created + 1 \
+                    end)").unwrap();
+
+    // Objects pushed from Rust are the same as those created from Lua
+    lua_class::push_object(lua, LayerSurface {
+        layer: 2,
+        namespace: "panel".into()
+    });
+    unsafe {
+        lua_setglobal(lua.0, c_str!("panel"));
+    }
+    let created: i32 = lua.eval("return created").unwrap();
+    assert_eq!(created, 1);
+    let instances: i32 = lua.eval("return layer_surface.instances()").unwrap();
+    assert_eq!(instances, 1);
+
+    // Properties are read and set from Lua, and their changes are signaled
+    let read: (u32, String) = lua.eval("return panel.layer, panel.namespace")
+        .unwrap();
+    assert_eq!(read, (2, "panel".into()));
+    lua.eval::<()>("changes = {} \
+                    panel:connect_signal('property::layer', function(s) \
+                        table.insert(changes, s.layer) \
+                    end) \
+                    panel.layer = 3 \
+                    panel.layer = 3").unwrap();
+    let changes: i32 = lua.eval("return #changes == 1 and changes[1]").unwrap();
+    assert_eq!(changes, 3);
+    let read_only: bool = lua.eval("return not pcall(function() \
+                                        panel.namespace = 'bar' \
+                                    end)").unwrap();
+    assert!(read_only);
+
+    // The value set from Lua is the one in the struct, and the other way
+    // around
+    assert_eq!(layer(lua, "panel"), Some(3));
+    lua.eval::<()>("panel:raise()").unwrap();
+    assert_eq!(layer(lua, "panel"), Some(4));
+    unsafe {
+        lua_getglobal(lua.0, c_str!("panel"));
+        lua_class::to_object::<LayerSurface>(lua, -1).unwrap().layer = 7;
+        lua_pop(lua.0, 1);
+    }
+    let layer_from_lua: u32 = lua.eval("return panel.layer").unwrap();
+    assert_eq!(layer_from_lua, 7);
+
+    // Anything else is not an object of the class
+    lua.eval::<()>("not_surface = button({})").unwrap();
+    assert_eq!(layer(lua, "not_surface"), None);
+    assert_eq!(layer(lua, "created"), None);
+
+    // Signals on the class and on the object
+    lua.eval::<()>("emitted = {} \
+                    layer_surface.connect_signal('test::class', function(...) \
+                        table.insert(emitted, select('#', ...)) \
+                    end) \
+                    panel:connect_signal('test::object', function(s, arg) \
+                        table.insert(emitted, arg) \
+                    end) \
+                    layer_surface.emit_signal('test::class', 1, 2) \
+                    panel:emit_signal('test::object', 'arg')").unwrap();
+    let emitted: bool = lua.eval("return #emitted == 2 and emitted[1] == 2 \
+                                  and emitted[2] == 'arg'").unwrap();
+    assert!(emitted);
+
+    // Objects created from Lua take their properties from the constructor
+    let layer_default: u32 = lua.eval("local s = layer_surface({layer = 5}) \
+                                       return s.layer").unwrap();
+    assert_eq!(layer_default, 5);
+    let created: i32 = lua.eval("return created").unwrap();
+    assert_eq!(created, 2);
+}
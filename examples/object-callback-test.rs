@@ -2,8 +2,6 @@
 #![allow(unused_variables)]
 
 #[macro_use] extern crate awesome_wayland;
-#[macro_use] extern crate lazy_static;
-extern crate lua_sys;
 extern crate libc;
 
 use std::path::PathBuf;
@@ -12,8 +10,8 @@ use awesome_wayland::{Lua, luaA, LuaErr, Awesome};
 use awesome_wayland::callbacks;
 use awesome_wayland::callbacks::*;
 use libc::c_int;
-use lua_sys::lua_State;
-use lua_sys::*;
+use awesome_wayland::lua_sys::lua_State;
+use awesome_wayland::lua_sys::*;
 
 // Contains no state, just here so we can register the libs.
 pub struct DummyStruct;
@@ -79,47 +77,6 @@ impl callbacks::Client for DummyStruct {
         client_titlebar_left,
         client_get_icon
     ]);
-    // Properties
-    default_impl!([
-        client_name,
-        client_transient_for,
-        client_skip_taskbar,
-        client_content,
-        client_type_,
-        client_class,
-        client_instance,
-        client_role,
-        client_pid,
-        client_leader_window,
-        client_machine,
-        client_icon_name,
-        client_screen,
-        client_hidden,
-        client_minimized,
-        client_fullscreen,
-        client_modal,
-        client_group_window,
-        client_maximized,
-        client_maximized_horizontal,
-        client_maximized_vertical,
-        client_icon,
-        client_icon_sizes,
-        client_ontop,
-        client_above,
-        client_below,
-        client_sticky,
-        client_size_hints_honor,
-        client_urgent,
-        client_size_hints,
-        client_focusable,
-        client_shape_bounding,
-        client_shape_clip,
-        client_shape_input,
-        client_startup_id,
-        client_client_shape_bounding,
-        client_client_shape_clip,
-        client_first_tag
-    ]);
 }
 impl callbacks::Drawin for DummyStruct {
     default_impl!([
@@ -235,11 +192,10 @@ impl Default for DummyStruct {
     }
 }
 
-register_for_lua!(DummyStruct, AWESOME);
-
 fn main() {
-    register_all!(DummyStruct, AWESOME);
-    match LUA.load_and_run(PathBuf::from("examples/object-callback-test.lua")) {
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    match awesome.lua.load_and_run(PathBuf::from("examples/object-callback-test.lua")) {
         Ok(_) => {},
         Err(LuaErr::Load(_)) => {
             println!("Could not find lua file! Please run this from the root \
@@ -0,0 +1,57 @@
+//! Tests profiling the callbacks and signals called from Lua.
+
+#[macro_use] extern crate awesome_wayland;
+extern crate libc;
+
+mod common;
+
+use awesome_wayland::Awesome;
+use awesome_wayland::profiler::CallKind;
+use common::DummyStruct;
+
+/// Calls a registered callback and emits a signal from Lua, `times` each.
+fn call_from_lua(awesome: &Awesome<DummyStruct>, times: u32) {
+    awesome.lua.eval::<()>(&format!("for _ = 1, {} do \
+                                         root.size() \
+                                         awesome.emit_signal('test::profiled') \
+                                     end", times)).unwrap();
+}
+
+fn main() {
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    awesome.lua.eval::<()>("awesome.connect_signal('test::profiled', \
+                                                   function() end)").unwrap();
+
+    // Nothing is recorded until the profiler is enabled
+    call_from_lua(&awesome, 2);
+    assert!(awesome.profiler().methods.is_empty());
+    assert!(awesome.profiler().signals.is_empty());
+
+    awesome.profiler().enabled = true;
+    call_from_lua(&awesome, 3);
+    {
+        let profiler = awesome.profiler();
+        let root_size = profiler.methods["root_size"];
+        assert_eq!(root_size.count, 3);
+        assert!(root_size.max <= root_size.total);
+        assert_eq!(profiler.signals["test::profiled"].count, 3);
+        assert!(profiler.sorted(CallKind::Method).iter()
+                .any(|&(name, stats)| name == "root_size" && stats.count == 3));
+
+        let mut report = Vec::new();
+        profiler.dump(&mut report).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        let line = report.lines()
+            .find(|line| line.starts_with("root_size "))
+            .expect("root_size is not in the report");
+        assert_eq!(line.split_whitespace().nth(1), Some("3"));
+    }
+
+    // Disabling it again stops the recording, resetting forgets it
+    awesome.profiler().enabled = false;
+    call_from_lua(&awesome, 1);
+    assert_eq!(awesome.profiler().methods["root_size"].count, 3);
+    awesome.profiler().reset();
+    assert!(awesome.profiler().methods.is_empty());
+}
@@ -6,11 +6,9 @@
 #![allow(unused_variables)]
 
 #[macro_use] extern crate awesome_wayland;
-#[macro_use] extern crate lazy_static;
-extern crate lua_sys;
 extern crate wlc;
 extern crate libc;
-use lua_sys::*;
+use awesome_wayland::lua_sys::*;
 
 use libc::c_int;
 use awesome_wayland::{Lua, luaA, LuaErr, Awesome};
@@ -66,7 +64,7 @@ impl callbacks::Button for DummyStruct {
 
 impl callbacks::Client for DummyStruct {
     fn client_get(&self, lua: &Lua) -> c_int {
-        use lua_sys::*;
+        use awesome_wayland::lua_sys::*;
         unsafe {
             lua_newtable(lua.0)
         }
@@ -99,47 +97,6 @@ impl callbacks::Client for DummyStruct {
         client_titlebar_left,
         client_get_icon
     ]);
-    // Properties
-    default_impl!([
-        client_name,
-        client_transient_for,
-        client_skip_taskbar,
-        client_content,
-        client_type_,
-        client_class,
-        client_instance,
-        client_role,
-        client_pid,
-        client_leader_window,
-        client_machine,
-        client_icon_name,
-        client_screen,
-        client_hidden,
-        client_minimized,
-        client_fullscreen,
-        client_modal,
-        client_group_window,
-        client_maximized,
-        client_maximized_horizontal,
-        client_maximized_vertical,
-        client_icon,
-        client_icon_sizes,
-        client_ontop,
-        client_above,
-        client_below,
-        client_sticky,
-        client_size_hints_honor,
-        client_urgent,
-        client_size_hints,
-        client_focusable,
-        client_shape_bounding,
-        client_shape_clip,
-        client_shape_input,
-        client_startup_id,
-        client_client_shape_bounding,
-        client_client_shape_clip,
-        client_first_tag
-    ]);
 }
 impl callbacks::Drawin for DummyStruct {
     default_impl!([
@@ -262,22 +219,21 @@ impl Default for DummyStruct {
     }
 }
 
-register_for_lua!(DummyStruct, AWESOME);
-
 struct Compositor;
 
 impl wlc::Callback for Compositor {}
 
 fn main() {
-    register_all!(DummyStruct, AWESOME);
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
 
     let compositor = Compositor;
 
     // Adds default awesome libs to path
-    LUA.add_default_awesome_libs();
+    awesome.lua.add_default_awesome_libs();
 
     // Run the user init script
-    LUA.load_and_run("examples/rc.lua".into()).unwrap();
+    awesome.lua.load_and_run("examples/rc.lua".into()).unwrap();
 
     wlc::init(compositor).unwrap();
 }
@@ -1,6 +1,6 @@
 -- Helper debug functions
 function isdefined(name)
-  print(string.format("%s is defined: %s", name, tostring(loadstring(string.format("return %s", name)) ~= nil)))
+  print(string.format("%s is defined: %s", name, tostring((loadstring or load)(string.format("return %s", name)) ~= nil)))
 end
 
 function dump(o)
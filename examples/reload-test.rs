@@ -0,0 +1,114 @@
+//! Tests reloading the configuration in a new Lua state, with the clients
+//! and screens of the old one.
+
+#[macro_use] extern crate awesome_wayland;
+extern crate libc;
+
+mod common;
+
+use std::fs;
+use awesome_wayland::{Lua, luaA, Awesome};
+use awesome_wayland::callbacks::client::{client_new, ClientState};
+use awesome_wayland::callbacks::screen::{screen_new, ScreenState};
+use common::DummyStruct;
+
+/// The configuration before the reload, which connects to the clients.
+const OLD_RC: &str = r#"
+old_rc = true
+client.connect_signal("test::reload", function() old_handler_ran = true end)
+for _, c in ipairs(client.get()) do
+  c:connect_signal("test::reload", function() old_handler_ran = true end)
+end
+"#;
+
+/// The configuration after the reload, which finds the objects of the old
+/// one through the reload signals.
+const NEW_RC: &str = r#"
+assert(old_rc == nil)
+added, managed = {}, {}
+screen.connect_signal("added", function(s) table.insert(added, s) end)
+client.connect_signal("manage", function(c)
+  -- The handlers of the old configuration are gone
+  assert(#awesome.signals(c) == 0)
+  managed[c.window] = c
+end)
+handler_runs = 0
+client.connect_signal("test::reload", function() handler_runs = handler_runs + 1 end)
+"#;
+
+fn new_client(lua: &Lua, window: i32, screen: *mut ScreenState) -> *mut ClientState {
+    unsafe {
+        let client = client_new(lua.0) as *mut ClientState;
+        (*client).window.window = window;
+        (*client).screen = screen as _;
+        luaA::object_ref(lua.0, -1);
+        client
+    }
+}
+
+fn main() {
+    let mut awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    let screen = unsafe {
+        let screen = screen_new(awesome.lua.0) as *mut ScreenState;
+        luaA::object_ref(awesome.lua.0, -1);
+        screen
+    };
+    awesome.conf().screens.push(screen);
+    let terminal = new_client(&awesome.lua, 1, screen);
+    let dialog = new_client(&awesome.lua, 2, screen);
+    unsafe {
+        (*dialog).transient_for = terminal;
+    }
+    awesome.conf().clients.extend(&[terminal, dialog]);
+    awesome.conf().stack = vec![dialog, terminal];
+
+    let dir = std::env::temp_dir()
+        .join(format!("awesome-reload-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let rc = dir.join("rc.lua");
+    fs::write(&rc, OLD_RC).unwrap();
+    assert_eq!(awesome.load_configuration(Some(rc.clone())).unwrap(), rc);
+    let connected: bool = awesome.lua.eval("return old_rc and \
+                                            #awesome.signals(client.get()[1]) == 1")
+        .unwrap();
+    assert!(connected);
+
+    // The same configuration file is loaded again
+    fs::write(&rc, NEW_RC).unwrap();
+    assert_eq!(awesome.reload(|lua| register_all!(DummyStruct, lua)).unwrap(), rc);
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(!awesome.need_reload());
+    assert!(awesome.conf().startup_errors.is_empty());
+
+    // The objects moved to the new state, and point at each other there
+    let (new_screen, new_terminal, new_dialog) = {
+        let conf = awesome.conf();
+        assert_eq!(conf.screens.len(), 1);
+        assert_eq!(conf.clients.len(), 2);
+        (conf.screens[0], conf.clients[0], conf.clients[1])
+    };
+    unsafe {
+        assert_eq!((*new_terminal).window.window, 1);
+        assert_eq!((*new_dialog).transient_for, new_terminal);
+        assert_eq!((*new_dialog).screen as *mut ScreenState, new_screen);
+        assert_eq!((*new_terminal).screen as *mut ScreenState, new_screen);
+    }
+    assert_eq!(awesome.conf().stack, vec![new_dialog, new_terminal]);
+
+    // The new configuration was told about them, and can use them
+    let signaled: bool = awesome.lua.eval("return #added == 1 \
+                                           and managed[1] and managed[2] \
+                                           and managed[2].transient_for == managed[1] \
+                                           and managed[1].screen == added[1]")
+        .unwrap();
+    assert!(signaled);
+
+    // Only the handlers of the new configuration run
+    unsafe {
+        awesome.lua.emit_signal(&*new_terminal, "test::reload", ()).unwrap();
+    }
+    let ran: (i32, bool) = awesome.lua.eval("return handler_runs, old_handler_ran == nil")
+        .unwrap();
+    assert_eq!(ran, (1, true));
+}
@@ -0,0 +1,67 @@
+//! Tests what code running in a sandbox can reach.
+
+#[macro_use] extern crate awesome_wayland;
+extern crate libc;
+
+mod common;
+
+use awesome_wayland::{Awesome, Sandbox};
+use common::DummyStruct;
+
+fn main() {
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    let lua = &awesome.lua;
+    let sandbox = Sandbox::default();
+    let run = |code: &str| -> bool {
+        lua.eval_sandboxed(code, "sandbox-test", &sandbox).unwrap()
+    };
+
+    assert!(run("return io == nil and require == nil and load == nil"));
+
+    // Only the harmless parts of awesome and root are exposed
+    assert!(run("return awesome.spawn == nil and awesome.exec == nil \
+                 and awesome.quit == nil and awesome.restart == nil \
+                 and awesome.kill == nil and awesome.sync == nil \
+                 and awesome.set_xproperty == nil"));
+    assert!(run("return type(awesome.connect_signal) == 'function'"));
+    assert!(run("return root.fake_input == nil and root.keys == nil \
+                 and root.cursor == nil"));
+    assert!(run("return type(root.size) == 'function'"));
+    assert!(run("return getmetatable(awesome) == false"));
+    assert!(run("return not pcall(setmetatable, awesome, {})"));
+    let spawn: bool = lua.eval("return type(awesome.spawn) == 'function'")
+        .unwrap();
+    assert!(spawn);
+
+    // The globals can't be changed from the sandbox
+    for global in &["awesome", "client", "screen", "tag", "root", "button",
+                    "mouse", "keygrabber", "mousegrabber"] {
+        let code = format!("local ok, err = pcall(function() \
+                                {0}.sandboxed = 1 \
+                            end) \
+                            return not ok and err:find('{0} is read%-only') \
+                                and {0}.sandboxed == nil", global);
+        assert!(run(&code), "{} can be written to", global);
+        let reached: bool = lua.eval(&format!("return {}.sandboxed ~= nil",
+                                              global)).unwrap();
+        assert!(!reached, "write to {} reached the host", global);
+    }
+    assert!(run("return not pcall(function() awesome.spawn = 1 end)"));
+    assert!(run("return not pcall(function() root.fake_input = print end)"));
+    assert!(run("return not pcall(function() client.focus = nil end)"));
+    let root_fake_input: bool = lua.eval("return type(root.fake_input) == 'function'")
+        .unwrap();
+    assert!(root_fake_input);
+    // The classes can still be called through their proxy
+    assert!(run("return button({}) ~= nil"));
+
+    // The metatables of the host can't be reached
+    assert!(run("return not pcall(getmetatable, '')"));
+    assert!(run("return not pcall(getmetatable, button({}))"));
+    assert!(run("local mt = {} \
+                 return getmetatable(setmetatable({}, mt)) == mt"));
+    assert!(run("local t = setmetatable({}, {__metatable = 'locked'}) \
+                 return getmetatable(t) == 'locked'"));
+    assert!(run("return getmetatable({}) == nil"));
+}
@@ -0,0 +1,145 @@
+-- Returns how many functions are connected to the signal named `name`, in
+-- the list returned by awesome.signals()
+local function count(signals, name)
+  for _, signal in ipairs(signals) do
+    if signal.name == name then
+      return signal.handlers
+    end
+  end
+  return 0
+end
+
+local calls = {}
+local function recorder(id)
+  return function(...)
+    table.insert(calls, {id = id, args = {...}})
+  end
+end
+
+print("Starting object signal test")
+local b = button({})
+local first, second = recorder("first"), recorder("second")
+b:connect_signal("property::foo", first)
+b:connect_signal("property::foo", second)
+b:connect_signal("property::bar", first)
+assert(count(awesome.signals(b), "property::foo") == 2)
+assert(count(awesome.signals(b), "property::bar") == 1)
+
+-- The first function connected is called, first, with the object
+b:emit_signal("property::foo", 42)
+assert(#calls == 2)
+assert(calls[1].id == "first" and calls[2].id == "second")
+assert(calls[1].args[1] == b and calls[1].args[2] == 42)
+
+-- Disconnecting only removes that function, from that signal
+calls = {}
+b:disconnect_signal("property::foo", first)
+b:emit_signal("property::foo")
+assert(#calls == 1 and calls[1].id == "second")
+calls = {}
+b:emit_signal("property::bar")
+assert(#calls == 1 and calls[1].id == "first")
+
+-- Signals without functions are no longer listed
+b:disconnect_signal("property::foo", second)
+b:disconnect_signal("property::bar", first)
+assert(#awesome.signals(b) == 0)
+calls = {}
+b:emit_signal("property::foo")
+assert(#calls == 0)
+print("Finished object signal test")
+
+print("Starting class signal test")
+calls = {}
+button.connect_signal("test::class", first)
+assert(count(awesome.class_info("button").connected, "test::class") == 1)
+button.emit_signal("test::class", "a", "b")
+assert(#calls == 1 and calls[1].args[1] == "a" and calls[1].args[2] == "b")
+button.disconnect_signal("test::class", first)
+assert(count(awesome.class_info("button").connected, "test::class") == 0)
+
+-- Signals that are declared are listed, even without functions
+button.add_signal("test::declared")
+local declared = awesome.class_info("button").signals
+local found = false
+for _, name in ipairs(declared) do
+  found = found or name == "test::declared"
+end
+assert(found and count(awesome.class_info("button").connected, "test::declared") == 0)
+
+-- Emitting on an object also emits on its class
+calls = {}
+button.connect_signal("test::object", first)
+b:emit_signal("test::object")
+assert(#calls == 1 and calls[1].args[1] == b)
+button.disconnect_signal("test::object", first)
+
+-- The class can be used from the handlers of the signals of its constructor
+local function connect_nested()
+  button.connect_signal("test::nested", second)
+end
+button.connect_signal("new", connect_nested)
+button({})
+button.disconnect_signal("new", connect_nested)
+calls = {}
+button.emit_signal("test::nested")
+assert(#calls == 1 and calls[1].id == "second")
+button.disconnect_signal("test::nested", second)
+
+-- A bad value given to the constructor leaves the class usable
+local ok, err = pcall(button, {button = "left"})
+assert(not ok and err:find("bad value for property 'button'"))
+button.connect_signal("test::class", first)
+button.disconnect_signal("test::class", first)
+print("Finished class signal test")
+
+print("Starting global signal test")
+calls = {}
+awesome.connect_signal("test::global", first)
+-- Handlers can use the global signals themselves
+awesome.connect_signal("test::global", function()
+  awesome.connect_signal("test::nested", second)
+  awesome.emit_signal("test::nested")
+end)
+awesome.emit_signal("test::global", 1)
+assert(#calls == 2)
+assert(calls[1].id == "first" and calls[1].args[1] == 1)
+assert(calls[2].id == "second")
+assert(count(awesome.signals(), "test::global") == 2)
+awesome.disconnect_signal("test::global", first)
+assert(count(awesome.signals(), "test::global") == 1)
+print("Finished global signal test")
+
+print("Starting weak signal test")
+calls = {}
+local weak = recorder("weak")
+b:weak_connect_signal("test::weak", weak)
+b:connect_signal{name = "test::weak", func = recorder("collected"), weak = true}
+awesome.connect_signal{name = "test::weak", func = weak, weak = true}
+collectgarbage("collect")
+b:emit_signal("test::weak")
+awesome.emit_signal("test::weak")
+assert(#calls == 2 and calls[1].id == "weak" and calls[2].id == "weak")
+
+-- Collected functions are dropped silently
+calls = {}
+weak = nil
+collectgarbage("collect")
+b:emit_signal("test::weak")
+awesome.emit_signal("test::weak")
+assert(#calls == 0)
+assert(count(awesome.signals(b), "test::weak") == 0)
+assert(count(awesome.signals(), "test::weak") == 0)
+
+-- A function connected after one was collected doesn't take its place
+calls = {}
+awesome.weak_connect_signal("test::collected", recorder("collected"))
+collectgarbage("collect")
+local live = recorder("live")
+awesome.weak_connect_signal("test::live", live)
+awesome.emit_signal("test::collected")
+awesome.emit_signal("test::live")
+assert(#calls == 1 and calls[1].id == "live")
+print("Finished weak signal test")
+
+print("Finished test")
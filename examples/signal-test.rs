@@ -0,0 +1,50 @@
+//! Tests connecting, disconnecting and emitting signals, on objects,
+//! classes and the `awesome` global.
+
+#[macro_use] extern crate awesome_wayland;
+extern crate libc;
+
+mod common;
+
+use awesome_wayland::Awesome;
+use awesome_wayland::object::{Signals, SignalFunc};
+use common::DummyStruct;
+
+/// The store on its own, with dummy pointers as functions.
+fn test_signals_store() {
+    let (a, b, c) = (1 as *mut _, 2 as *mut _, 3 as *mut _);
+    let mut signals = Signals::new();
+    assert!(signals.is_empty());
+
+    signals.connect("property::name", SignalFunc::strong(a));
+    signals.connect("property::name", SignalFunc::strong(b));
+    signals.connect("manage", SignalFunc::strong(c));
+    assert_eq!(signals.names(), vec!["manage", "property::name"]);
+    assert_eq!(signals.count("property::name"), 2);
+    assert_eq!(signals.count("unmanage"), 0);
+    let order: Vec<_> = signals.handlers("property::name").iter()
+        .map(|func| func.ptr)
+        .collect();
+    assert_eq!(order, vec![a, b]);
+
+    /* Only the given function is removed, from the given signal */
+    assert!(signals.disconnect("manage", a).is_none());
+    assert_eq!(signals.disconnect("property::name", a).map(|func| func.ptr),
+               Some(a));
+    assert_eq!(signals.counts(), vec![("manage".to_string(), 1),
+                                      ("property::name".to_string(), 1)]);
+    assert!(signals.disconnect("property::name", a).is_none());
+
+    /* A signal without functions is no longer listed */
+    signals.disconnect("property::name", b);
+    signals.disconnect("manage", c);
+    assert!(signals.is_empty());
+}
+
+fn main() {
+    test_signals_store();
+
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    common::run_lua_file(&awesome.lua, "examples/signal-test.lua");
+}
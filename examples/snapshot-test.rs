@@ -0,0 +1,109 @@
+//! Takes a snapshot of the managed state, writes it as JSON and rebuilds it
+//! in a headless instance.
+#![allow(unused_variables)]
+
+#[macro_use] extern crate awesome_wayland;
+extern crate libc;
+
+mod common;
+
+use std::default::Default;
+use awesome_wayland::Awesome;
+use awesome_wayland::snapshot::*;
+use common::DummyStruct;
+
+/// Two screens, with a terminal and a modal dialog on top of it.
+fn layout() -> Snapshot {
+    Snapshot {
+        screens: vec![ScreenSnapshot { valid: true }; 2],
+        primary_screen: Some(1),
+        clients: vec![
+            ClientSnapshot {
+                window: 1,
+                name: "term".into(),
+                class: "XTerm".into(),
+                instance: "xterm".into(),
+                geometry: Geometry { x: 0, y: 20, width: 640, height: 480 },
+                opacity: 1.0,
+                screen: Some(0),
+                focusable: true,
+                size_hints_honor: true,
+                ..Default::default()
+            },
+            ClientSnapshot {
+                window: 2,
+                name: "Save as".into(),
+                geometry: Geometry { x: 100, y: 100, width: 300, height: 200 },
+                opacity: 0.5,
+                screen: Some(0),
+                transient_for: Some(0),
+                modal: true,
+                ontop: true,
+                ..Default::default()
+            }
+        ],
+        stack: vec![0, 1],
+        focus: Some(1),
+        focus_history: vec![1, 0],
+        tags: vec![TagSnapshot {}; 3],
+        drawins: vec![DrawinSnapshot {
+            window: 3,
+            geometry: Geometry { x: 0, y: 0, width: 1920, height: 20 },
+            opacity: 1.0,
+            ontop: true,
+            visible: true,
+            cursor: "left_ptr".into()
+        }],
+        keys: vec![KeySnapshot { modifiers: 64, keysym: 0xff0d, keycode: 36 }],
+        buttons: vec![ButtonSnapshot { modifiers: 0, button: 1 }]
+    }
+}
+
+/// Snapshots that don't describe a state are refused.
+fn test_invalid() {
+    assert!(match Snapshot::from_json("{") {
+        Err(SnapshotErr::Json(_)) => true,
+        _ => false
+    });
+    let dangling = Snapshot { focus: Some(2), ..layout() };
+    assert_eq!(dangling.validate(),
+               Err(SnapshotErr::InvalidIndex { list: "client", index: 2 }));
+
+    /* Clients can't be transient for themselves, even through others */
+    let mut own_parent = layout();
+    own_parent.clients[1].transient_for = Some(1);
+    assert_eq!(own_parent.validate(),
+               Err(SnapshotErr::TransientCycle { client: 1 }));
+    let mut cycle = layout();
+    cycle.clients[0].transient_for = Some(1);
+    assert_eq!(cycle.validate(), Err(SnapshotErr::TransientCycle { client: 0 }));
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    assert_eq!(awesome.restore(&cycle),
+               Err(SnapshotErr::TransientCycle { client: 0 }));
+}
+
+fn main() {
+    test_invalid();
+
+    let snapshot = layout();
+    let json = snapshot.to_json();
+    assert_eq!(Snapshot::from_json(&json).unwrap(), snapshot);
+
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    awesome.restore(&snapshot).unwrap();
+
+    // The rebuilt state gives back the same snapshot, and the same JSON
+    assert_eq!(awesome.snapshot(), snapshot);
+    let from_lua: String = awesome.lua.eval("return awesome.snapshot()").unwrap();
+    assert_eq!(from_lua, json);
+
+    // The objects were created in the Lua state
+    let clients: i32 = awesome.lua.eval(r#"
+        for _, class in ipairs(awesome.diagnostics().instances) do
+            if class.class == "client" then return class.referenced end
+        end
+    "#).unwrap();
+    assert_eq!(clients, 2);
+}
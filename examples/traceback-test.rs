@@ -0,0 +1,61 @@
+//! Tests the tracebacks of Lua errors, and the `debug::error` signal.
+
+#[macro_use] extern crate awesome_wayland;
+extern crate libc;
+
+mod common;
+
+use awesome_wayland::{Awesome, LuaErr, StackFrame};
+use common::DummyStruct;
+
+/// Errors two calls deep in the chunk.
+const FAILING: &str = "local function inner()
+  error('deep failure')
+end
+local function outer()
+  inner()
+  return true
+end
+outer()";
+
+fn main() {
+    let awesome: Awesome<DummyStruct> = Awesome::new();
+    register_all!(DummyStruct, &awesome.lua);
+    let lua = &awesome.lua;
+
+    let (message, frames) = match lua.eval_named::<()>(FAILING, "=traceback-test") {
+        Err(LuaErr::Eval(message, frames)) => (message, frames),
+        result => panic!("Expected an evaluation error, got {:?}", result)
+    };
+    assert_eq!(message, "traceback-test:2: deep failure");
+    // From the innermost frame: `error`, the two functions and the chunk
+    assert_eq!(frames.len(), 4, "unexpected frames {:?}", frames);
+    assert_eq!(frames[0].what, "C");
+    assert_eq!(frames[0].name, Some("error".to_string()));
+    let lua_frames: Vec<_> = frames[1..].iter()
+        .map(|frame| (frame.source.as_str(), frame.line, frame.name.clone()))
+        .collect();
+    assert_eq!(lua_frames,
+               vec![("traceback-test", Some(2), Some("inner".to_string())),
+                    ("traceback-test", Some(5), Some("outer".to_string())),
+                    ("traceback-test", Some(8), None)]);
+    assert_eq!(frames[3], StackFrame {
+        source: "traceback-test".into(),
+        line: Some(8),
+        name: None,
+        what: "main".into()
+    });
+
+    // Errors in signal handlers are reported with debug::error instead
+    lua.eval_named::<()>("errors = {} \
+                          awesome.connect_signal('debug::error', function(message) \
+                              table.insert(errors, message) \
+                          end) \
+                          awesome.connect_signal('test::fail', function() \
+                              error('handler failure') \
+                          end) \
+                          awesome.emit_signal('test::fail')",
+                         "=handler-test").unwrap();
+    let errors: Vec<String> = lua.eval("return errors").unwrap();
+    assert_eq!(errors, vec!["handler-test:1: handler failure".to_string()]);
+}
@@ -2,25 +2,60 @@
 //!
 //! It wraps Lua, and ensures that the correct callbacks are defined for
 //! each of the methods used by the Awesome Lua libraries.
+use std::env;
+use std::fmt;
+use std::collections::HashMap;
 use std::path::PathBuf;
-use super::lua::{Lua, LuaErr};
+use std::ptr;
+use lua_sys::*;
+use super::lua::{Lua, LuaErr, luaA};
+use std::sync::{Mutex, MutexGuard};
+use super::globalconf::GlobalConf;
+use super::state;
+use super::sandbox::Sandbox;
+use super::profiler::Profiler;
+use super::snapshot::{Snapshot, SnapshotErr};
 use super::callbacks::{self, Button, Client, Drawin, Keygrabber,
                        Mousegrabber, Mouse, Root, Screen, Tag};
+use super::callbacks::client::ClientState;
+use super::callbacks::screen::ScreenState;
+use super::callbacks::tag::TagState;
 
 /// Represents the bindings to the awesome libraries.
 /// Contains the raw Lua context, as well as the struct that has all of the
 /// necessary callbacks defined that are called from Lua.
-#[derive(Debug)]
+///
+/// Each instance has its own Lua state and configuration, so several of
+/// them can be used at once.
 pub struct Awesome<T>
-    where T: callbacks::Awesome + Button + Client + Drawin + Keygrabber +
-    Mousegrabber + Mouse + Root + Screen + Tag {
+    where T: 'static + callbacks::Awesome + Button + Client + Drawin +
+    Keygrabber + Mousegrabber + Mouse + Root + Screen + Tag {
     /// The user-provided data that is operated on by the callbacks.
-    pub callbacks: T
+    pub callbacks: Box<T>,
+    /// The Lua state the callbacks are registered in.
+    ///
+    /// Pass this to the register_*! macros to register the callbacks.
+    pub lua: Lua,
+    /// The global configuration, shared with the Lua state
+    conf: Box<Mutex<GlobalConf>>
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum AwesomeErr {
-    Lua(LuaErr)
+    Lua(LuaErr),
+    /// None of the configuration files could be loaded.
+    /// The reason each one failed is in `GlobalConf.startup_errors`.
+    NoConfiguration
+}
+
+impl fmt::Display for AwesomeErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AwesomeErr::Lua(ref err) => write!(f, "{}", err),
+            AwesomeErr::NoConfiguration =>
+                write!(f, "no configuration file could be loaded")
+        }
+    }
 }
 
 impl From<LuaErr> for AwesomeErr {
@@ -30,26 +65,315 @@ impl From<LuaErr> for AwesomeErr {
 }
 
 impl<T> Awesome<T>
-    where T: Default + callbacks::Awesome + Button + Client + Drawin + Keygrabber +
-    Mousegrabber + Mouse + Root + Screen + Tag {
+    where T: 'static + Default + callbacks::Awesome + Button + Client +
+    Drawin + Keygrabber + Mousegrabber + Mouse + Root + Screen + Tag {
 
     /// Constructs a new `Awesome` instance, and calls the default constructor
     /// for the `T` value.
     pub fn new() -> Self {
-        let callbacks = T::default();
-        Awesome{
-            callbacks
-        }
+        Awesome::with_callbacks(T::default())
     }
 }
 
 impl<T> Awesome<T>
-    where T: callbacks::Awesome + Button + Client + Drawin + Keygrabber +
-    Mousegrabber + Mouse + Root + Screen + Tag {
+    where T: 'static + callbacks::Awesome + Button + Client + Drawin +
+    Keygrabber + Mousegrabber + Mouse + Root + Screen + Tag {
+
+    /// Constructs a new `Awesome` instance that uses the given callbacks.
+    pub fn with_callbacks(callbacks: T) -> Self {
+        let callbacks = Box::new(callbacks);
+        let conf = Box::new(Mutex::new(GlobalConf::default()));
+        let lua = new_lua(&conf, &*callbacks);
+        Awesome {
+            callbacks,
+            lua,
+            conf
+        }
+    }
+
+    /// The global configuration of this instance.
+    pub fn conf(&self) -> MutexGuard<GlobalConf> {
+        self.conf.lock().unwrap()
+    }
+
+    /// The profiler of the callbacks and signals, see
+    /// [profiler](../profiler/index.html). Set `enabled` to start profiling.
+    pub fn profiler(&self) -> MutexGuard<Profiler> {
+        unsafe {
+            state::get(self.lua.0).profiler.lock().unwrap()
+        }
+    }
+
+    /// Takes a [snapshot](../snapshot/index.html) of the state managed in
+    /// the configuration, e.g to attach to a bug report.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(&self.conf())
+    }
+
+    /// Rebuilds the state of the snapshot in this instance, see
+    /// [Snapshot::restore](../snapshot/struct.Snapshot.html#method.restore).
+    ///
+    /// The classes must have been set up first, e.g with `register_all!`.
+    pub fn restore(&self, snapshot: &Snapshot) -> Result<(), SnapshotErr> {
+        snapshot.restore(&self.lua, &self.conf)
+    }
 
-    /// Load the rc.lua configuration file from the specified path.
-    pub fn load_configuration(&mut self, path: PathBuf, lua: &Lua)
-                              -> Result<(), AwesomeErr> {
-        Ok(lua.load_and_run(path)?)
+    /// Load the rc.lua configuration file, trying each of the
+    /// [config_search_paths](fn.config_search_paths.html) in turn until
+    /// one of them loads without an error.
+    ///
+    /// The reason each configuration file failed is appended to
+    /// `GlobalConf.startup_errors`, and the one that loaded is reported
+    /// through `awesome.conffile` and returned.
+    pub fn load_configuration(&self, path: Option<PathBuf>)
+                              -> Result<PathBuf, AwesomeErr> {
+        let explicit = path.is_some();
+        let lua = &self.lua;
+        load_first(&self.conf, config_search_paths(path), explicit,
+                   |path| lua.load_and_run(path))
+    }
+
+    /// Load the rc.lua configuration file like
+    /// [load_configuration](#method.load_configuration), but inside of the
+    /// sandbox so that it can't access the file system or spawn processes.
+    pub fn load_configuration_sandboxed(&self, path: Option<PathBuf>,
+                                        sandbox: &Sandbox)
+                                        -> Result<PathBuf, AwesomeErr> {
+        let explicit = path.is_some();
+        let lua = &self.lua;
+        load_first(&self.conf, config_search_paths(path), explicit,
+                   |path| lua.eval_file_sandboxed(path, sandbox))
+    }
+
+    /// Has the configuration asked to be reloaded with `awesome.restart()`?
+    pub fn need_reload(&self) -> bool {
+        self.conf().need_reload
+    }
+
+    /// Tears down the Lua state and loads the configuration again in a new one.
+    ///
+    /// All of the signals and classes are dropped with the old state, and
+    /// `setup` is called with the new state to set up the classes again
+    /// (e.g with `register_all!`) along with anything else the
+    /// configuration needs, like
+    /// [add_default_awesome_libs](../struct.Lua.html#method.add_default_awesome_libs).
+    /// The clients, tags and screens are kept and moved to the new state,
+    /// and once the configuration has loaded the screens receive `added` and
+    /// the clients `manage` as if they were new.
+    /// Everything else the old configuration created (drawins, buttons,
+    /// keys, ...) is lost.
+    ///
+    /// This must not be called from within a Lua callback, as it closes the
+    /// state that is running it.
+    pub fn reload<F>(&mut self, setup: F) -> Result<PathBuf, AwesomeErr>
+        where F: FnOnce(&Lua)
+    {
+        unsafe {
+            let objects = detach_objects(&self.lua, &mut self.conf());
+            let profiler = self.profiler().clone();
+            lua_close(self.lua.0);
+            self.lua = new_lua(&self.conf, &*self.callbacks);
+            *self.profiler() = profiler;
+            setup(&self.lua);
+            attach_objects(&self.lua, &mut self.conf(), objects);
+        }
+        let conffile = {
+            let mut global_conf = self.conf();
+            global_conf.need_reload = false;
+            global_conf.startup_errors.clear();
+            global_conf.conffile.take()
+        };
+        let result = self.load_configuration(conffile);
+        unsafe {
+            emit_reload_signals(&self.lua, &self.conf);
+        }
+        result
+    }
+}
+
+impl<T> fmt::Debug for Awesome<T>
+    where T: 'static + fmt::Debug + callbacks::Awesome + Button + Client +
+    Drawin + Keygrabber + Mousegrabber + Mouse + Root + Screen + Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Awesome")
+            .field("callbacks", &self.callbacks)
+            .field("lua", &self.lua)
+            .finish()
+    }
+}
+
+impl<T> Drop for Awesome<T>
+    where T: 'static + callbacks::Awesome + Button + Client + Drawin +
+    Keygrabber + Mousegrabber + Mouse + Root + Screen + Tag {
+    fn drop(&mut self) {
+        unsafe {
+            lua_close(self.lua.0);
+        }
+    }
+}
+
+/// Creates a Lua state that can reach the configuration and callbacks.
+fn new_lua<T: 'static>(conf: &Mutex<GlobalConf>, callbacks: &T) -> Lua {
+    let lua = Lua::new();
+    unsafe {
+        state::get(lua.0).attach(conf, callbacks);
+    }
+    lua
+}
+
+/// The objects that survive a reload, moved out of the old Lua state.
+struct DetachedObjects {
+    screens: Vec<(*mut ScreenState, Box<ScreenState>)>,
+    tags: Vec<(*mut TagState, Box<TagState>)>,
+    clients: Vec<(*mut ClientState, Box<ClientState>)>
+}
+
+/// Moves the screens, tags and clients out of the Lua state, and forgets
+/// about everything else that lives in it.
+unsafe fn detach_objects(lua: &Lua, global_conf: &mut GlobalConf)
+                         -> DetachedObjects {
+    let screens = global_conf.screens.drain(..)
+        .map(|screen| (screen, luaA::object_detach(lua.0, screen)))
+        .collect();
+    let tags = global_conf.tags.drain(..)
+        .map(|tag| (tag, luaA::object_detach(lua.0, tag)))
+        .collect();
+    let mut clients: Vec<(_, Box<ClientState>)> = global_conf.clients.drain(..)
+        .map(|client| (client, luaA::object_detach(lua.0, client)))
+        .collect();
+    for &mut (_, ref mut client) in clients.iter_mut() {
+        /* These live in the old state */
+        client.window.buttons.clear();
+        client.keys.clear();
+        client.titlebar.clear();
+    }
+    /* Drawins, buttons and keys are created by the configuration */
+    global_conf.drawins.clear();
+    global_conf.buttons.clear();
+    global_conf.keys.clear();
+    global_conf.drawable_under_mouse = ptr::null_mut();
+    global_conf.systray.parent = ptr::null_mut();
+    DetachedObjects { screens, tags, clients }
+}
+
+/// Moves the objects in to the new Lua state, fixing up every pointer to
+/// them as they now live at a different address.
+unsafe fn attach_objects(lua: &Lua, global_conf: &mut GlobalConf,
+                         objects: DetachedObjects) {
+    let state = state::get(lua.0);
+    let mut screens = HashMap::new();
+    for (old, screen) in objects.screens {
+        if let Some(new) = luaA::object_attach(lua.0, &state.screen_class, screen) {
+            screens.insert(old, new);
+            global_conf.screens.push(new);
+        }
+    }
+    for (_, tag) in objects.tags {
+        if let Some(new) = luaA::object_attach(lua.0, &state.tag_class, tag) {
+            global_conf.tags.push(new);
+        }
+    }
+    let mut clients = HashMap::new();
+    for (old, client) in objects.clients {
+        if let Some(new) = luaA::object_attach(lua.0, &state.client_class, client) {
+            clients.insert(old, new);
+            global_conf.clients.push(new);
+        }
+    }
+    remap(global_conf, &screens, &clients);
+}
+
+unsafe fn remap(global_conf: &mut GlobalConf,
+                screens: &HashMap<*mut ScreenState, *mut ScreenState>,
+                clients: &HashMap<*mut ClientState, *mut ClientState>) {
+    let screen = |old| screens.get(&old).cloned().unwrap_or(ptr::null_mut());
+    let client = |old| clients.get(&old).cloned().unwrap_or(ptr::null_mut());
+    global_conf.primary_screen = screen(global_conf.primary_screen);
+    global_conf.focus.client = client(global_conf.focus.client);
+    global_conf.focus.history = global_conf.focus.history.iter()
+        .filter_map(|old| clients.get(old).cloned())
+        .collect();
+    global_conf.stack = global_conf.stack.iter()
+        .filter_map(|old| clients.get(old).cloned())
+        .collect();
+    for &new in &global_conf.clients {
+        (*new).screen = screen((*new).screen as _) as _;
+        (*new).transient_for = client((*new).transient_for);
+    }
+}
+
+/// Tells the new configuration about the screens and clients that were
+/// there before it.
+unsafe fn emit_reload_signals(lua: &Lua, conf: &Mutex<GlobalConf>) {
+    let (screens, clients) = {
+        let global_conf = conf.lock().unwrap();
+        (global_conf.screens.clone(), global_conf.clients.clone())
+    };
+    // NOTE The lock can't be held while the signals run
+    for screen in screens {
+        luaA::object_push(lua.0, screen as _);
+        luaA::object_emit_signal(lua.0, -1, c_str!("added"), 0);
+        lua_pop(lua.0, 1);
+    }
+    for client in clients {
+        luaA::object_push(lua.0, client as _);
+        luaA::object_emit_signal(lua.0, -1, c_str!("manage"), 0);
+        lua_pop(lua.0, 1);
+    }
+}
+
+/// Returns the paths the configuration is looked up at, in order.
+///
+/// These are the `explicit` path (if any), then
+/// `$XDG_CONFIG_HOME/awesome/rc.lua` (`$XDG_CONFIG_HOME` defaults to
+/// `~/.config`) and then `awesome/rc.lua` in each of the `$XDG_CONFIG_DIRS`
+/// (which defaults to `/etc/xdg`).
+pub fn config_search_paths(explicit: Option<PathBuf>) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = explicit.into_iter().collect();
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME")
+                 .map(|home| PathBuf::from(home).join(".config")));
+    if let Some(config_home) = config_home {
+        paths.push(config_home.join("awesome/rc.lua"));
+    }
+    let config_dirs = env::var_os("XDG_CONFIG_DIRS")
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".into());
+    for dir in env::split_paths(&config_dirs) {
+        // Relative paths are invalid according to the XDG spec
+        if dir.is_absolute() {
+            paths.push(dir.join("awesome/rc.lua"));
+        }
+    }
+    paths
+}
+
+/// Runs `load` on each of the paths until one succeeds.
+///
+/// Paths that don't exist are skipped, unless it is the `explicit` path
+/// given by the user.
+fn load_first<F>(conf: &Mutex<GlobalConf>, paths: Vec<PathBuf>,
+                 explicit: bool, mut load: F) -> Result<PathBuf, AwesomeErr>
+    where F: FnMut(PathBuf) -> Result<(), LuaErr>
+{
+    for (i, path) in paths.into_iter().enumerate() {
+        let is_explicit = explicit && i == 0;
+        if !is_explicit && !path.is_file() {
+            continue
+        }
+        // The configuration can already use `awesome.conffile` while loading
+        conf.lock().unwrap().conffile = Some(path.clone());
+        // NOTE The lock can't be held while the configuration runs,
+        // it might access `awesome` fields.
+        if let Err(err) = load(path.clone()) {
+            conf.lock().unwrap().startup_errors
+                .push(format!("{}: {}", path.display(), err));
+            continue
+        }
+        return Ok(path)
     }
+    conf.lock().unwrap().conffile = None;
+    Err(AwesomeErr::NoConfiguration)
 }
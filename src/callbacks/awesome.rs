@@ -2,7 +2,10 @@
 
 use ::lua::Lua;
 use ::luaA;
-use ::GLOBAL_CONF;
+use ::state;
+use ::introspection;
+use ::diagnostics;
+use ::snapshot;
 use libc::c_int;
 
 #[allow(non_snake_case)]
@@ -19,7 +22,18 @@ pub trait Awesome {
 
     fn awesome_spawn(&self, lua: &Lua) -> c_int;
 
-    fn awesome_restart(&self, lua: &Lua) -> c_int;
+    /// Requests a reload of the configuration.
+    ///
+    /// The compositor should check
+    /// [need_reload](../../awesome/struct.Awesome.html#method.need_reload)
+    /// and call [reload](../../awesome/struct.Awesome.html#method.reload)
+    /// once it's safe to do so, i.e not from within a Lua callback.
+    fn awesome_restart(&self, lua: &Lua) -> c_int {
+        unsafe {
+            state::global_conf(lua.0).lock().unwrap().need_reload = true;
+        }
+        0
+    }
 
     fn awesome_connect_signal(&self, lua: &Lua) -> c_int {
         unsafe {
@@ -27,6 +41,14 @@ pub trait Awesome {
         }
     }
 
+    /// Like `connect_signal`, but the function is dropped once it is
+    /// garbage collected.
+    fn awesome_weak_connect_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            luaA::awesome_weak_connect_signal(lua.0)
+        }
+    }
+
     fn awesome_disconnect_signal(&self, lua: &Lua) -> c_int {
         unsafe {
             luaA::awesome_disconnect_signal(lua.0)
@@ -55,6 +77,45 @@ pub trait Awesome {
     /// Explicitly not defined, because this will probably be compositor specific.
     fn awesome_get_xproperty(&self, lua: &Lua) -> c_int;
 
+    /// Returns a table of every class, see the
+    /// [introspection](../../introspection/index.html) module.
+    fn awesome_classes(&self, lua: &Lua) -> c_int {
+        unsafe {
+            introspection::awesome_classes(lua.0)
+        }
+    }
+
+    /// Returns the class with the given name, or of the given object.
+    fn awesome_class_info(&self, lua: &Lua) -> c_int {
+        unsafe {
+            introspection::awesome_class_info(lua.0)
+        }
+    }
+
+    /// Returns the signals connected on the given object, or on `awesome`.
+    fn awesome_signals(&self, lua: &Lua) -> c_int {
+        unsafe {
+            introspection::awesome_signals(lua.0)
+        }
+    }
+
+    /// Returns the references in the object registry, the instances of
+    /// each class and the misuses of the registry, see the
+    /// [diagnostics](../../diagnostics/index.html) module.
+    fn awesome_diagnostics(&self, lua: &Lua) -> c_int {
+        unsafe {
+            diagnostics::awesome_diagnostics(lua.0)
+        }
+    }
+
+    /// Returns the state managed by the compositor as JSON, to attach to
+    /// bug reports, see the [snapshot](../../snapshot/index.html) module.
+    fn awesome_snapshot(&self, lua: &Lua) -> c_int {
+        unsafe {
+            snapshot::awesome_snapshot(lua.0)
+        }
+    }
+
     fn awesome___index(&self, lua: &Lua) -> c_int {
         unsafe {
             awesome_index(lua.0)
@@ -82,7 +143,8 @@ pub trait Awesome {
 
 
 use lua_sys::*;
-use std::ffi::{CStr, CString};
+use std::ffi::CStr;
+use ::convert::ToLua;
 
 #[allow(unreachable_code)]
 unsafe fn awesome_index(lua: *mut lua_State) -> c_int {
@@ -93,12 +155,12 @@ unsafe fn awesome_index(lua: *mut lua_State) -> c_int {
 
     let buf_c = luaL_checklstring(lua, 2, ::std::ptr::null_mut());
     let buf = CStr::from_ptr(buf_c).to_str().unwrap();
-    let global_conf = GLOBAL_CONF.try_lock().unwrap();
+    let global_conf = state::global_conf(lua).try_lock().unwrap();
     match buf {
         "conffile" => {
-            // TODO Dynamic way to get the configuration file.
-            lua_pushstring(lua, c_str!("/usr/xdg/awesome/rc.lua"));
-            1
+            global_conf.conffile.as_ref()
+                .map(|path| path.to_string_lossy().into_owned())
+                .push_to_lua(lua)
         },
         "version" | "release" => {
             // TODO For realz
@@ -116,11 +178,7 @@ unsafe fn awesome_index(lua: *mut lua_State) -> c_int {
             if global_conf.startup_errors.len() == 0 {
                 0
             } else {
-                let error = CString::new(&*global_conf.startup_errors[0])
-                    .expect("Could not convert error string to C string");
-                lua_pushstring(lua, error.as_ptr());
-                ::std::mem::forget(error);
-                1
+                global_conf.startup_errors.join("\n").push_to_lua(lua)
             }
         },
         "composite_manager_running" => {
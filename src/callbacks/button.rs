@@ -1,16 +1,18 @@
 //! Callbacks for the `button` object in the Lua libraries
 
-use ::luaA::{self, pushmodifiers};
+use ::luaA;
+use ::convert::Modifiers;
 use ::lua::Lua;
-use ::object::signal::Signal;
+use ::object::signal::Signals;
 use ::object::class::{Class, Object};
+use ::object::property::Property;
 use libc::c_int;
 use lua_sys::*;
 use xcb::ffi::xproto::xcb_button_t;
 
 
-LUA_OBJECT_FUNCS!(luaA::BUTTON_CLASS, Class, button_new);
-LUA_CLASS_FUNCS!(luaA::BUTTON_CLASS,
+LUA_OBJECT_FUNCS!(button_class, Class, button_new);
+LUA_CLASS_FUNCS!(button_class,
                  button_class_add_signal,
                  button_class_connect_signal,
                  button_class_disconnect_signal,
@@ -18,17 +20,29 @@ LUA_CLASS_FUNCS!(luaA::BUTTON_CLASS,
                  button_class_instances,
                  button_set_index_miss_handler,
                  button_set_newindex_miss_handler);
-LUA_OBJECT_EXPORT_PROPERTY!(button_get_button, ButtonState, button, lua_pushinteger);
-LUA_OBJECT_EXPORT_PROPERTY!(button_get_modifiers, ButtonState, modifiers, pushmodifiers);
 
 /// State of the button
 #[repr(C)]
 pub struct ButtonState {
-    pub signals: Vec<Signal>,
+    pub signals: Signals,
     pub modifiers: u16,
     pub button: xcb_button_t
 }
 
+/// The properties of the button class.
+pub fn button_properties() -> Vec<Property> {
+    vec![
+        Property::read_write("button",
+                             |button: &ButtonState| button.button,
+                             |button: &mut ButtonState, value| button.button = value),
+        Property::read_write("modifiers",
+                             |button: &ButtonState| Modifiers(button.modifiers),
+                             |button: &mut ButtonState, value: Modifiers| {
+                                 button.modifiers = value.0
+                             })
+    ]
+}
+
 #[allow(non_snake_case)]
 pub trait Button {
     /* Methods */
@@ -1,17 +1,22 @@
 //! Callbacks for the `client` object in the Lua libraries
 
-use ::luaA::{self, CLIENT_CLASS, SCREEN_CLASS, area_t, object_push};
-use ::globalconf::GLOBAL_CONF;
-use ::lua::Lua;
+use ::luaA::{self, area_t};
+use ::state;
+use ::lua::{Lua, LuaErr};
 use libc::{c_int, c_void};
 use ::object::class::{Class, Object};
-use ::object::window::WindowState;
+use ::object::property::Property;
+use ::object::window::{WindowState, WindowType};
 use ::callbacks::drawable::DrawableState;
 use ::callbacks::screen::ScreenState;
-use std::ffi::{CString};
-
-LUA_OBJECT_FUNCS!(luaA::CLIENT_CLASS, Class, client_new);
-LUA_CLASS_FUNCS!(luaA::CLIENT_CLASS,
+use ::convert::{FromLua, ObjectRef, ToLua};
+use ::stack::{self, StackedWindow};
+use ::focus;
+use std::ffi::CStr;
+
+LUA_OBJECT_FUNCS!(client_class, ClientState, client_new,
+                  |client: &mut ClientState| client.valid = true);
+LUA_CLASS_FUNCS!(client_class,
                  client_class_add_signal,
                  client_class_connect_signal,
                  client_class_disconnect_signal,
@@ -84,6 +89,10 @@ pub struct ClientState {
     /// from Lua.
     pub focusable: bool,
     pub focusable_set: bool,
+    /// True if the client is a dock, e.g a panel. Overrides the type of the
+    /// window, and can be set from Lua.
+    pub dockable: bool,
+    pub dockable_set: bool,
     /// True if the client window has a _NET_WM_WINDOW_TYPE proeprty
     pub has_NET_WM_WINDOW_TYPE: bool,
     /// Window of the group leader
@@ -101,7 +110,7 @@ pub struct ClientState {
     /// True if we ever got an icon from _NET_WM_ICON
     pub have_ewmh_icon: bool,
     /// Size hints
-    pub size_hints: (),
+    pub size_hints: SizeHints,
     /// The visualtype that c->window uses
     pub visualtype: (),
     /// Do we honor the client's size hints?
@@ -117,7 +126,263 @@ pub struct ClientState {
     /// Value of WM_TRANSIENT_FOR
     pub transient_for_window: (),
     /// Titelbar information
-    pub titlebar: Vec<TitleBar>
+    pub titlebar: Vec<TitleBar>,
+    /// Whether the surface of the client exists, true when it's created
+    pub valid: bool
+}
+
+impl ClientState {
+    /// Marks the client as backed by a surface or not, e.g once the
+    /// surface is gone. The client can't be used from Lua while it's
+    /// invalid.
+    pub fn set_valid(&mut self, valid: bool) {
+        self.valid = valid;
+    }
+}
+
+pub unsafe fn client_checker(object: *mut Object) -> bool {
+    (*(object as *mut ClientState)).valid
+}
+
+/// The ICCCM size hints of a client, `WM_NORMAL_HINTS`.
+///
+/// Each hint is only set if the client gave it, sizes are in pixels and
+/// don't include the border.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeHints {
+    /// Position the user asked for, `USPosition`
+    pub user_position: Option<(i32, i32)>,
+    /// Size the user asked for, `USSize`
+    pub user_size: Option<(i32, i32)>,
+    /// Position the client asked for, `PPosition`
+    pub program_position: Option<(i32, i32)>,
+    /// Size the client asked for, `PSize`
+    pub program_size: Option<(i32, i32)>,
+    pub min_size: Option<(i32, i32)>,
+    /// A width or height of 0 is not limited
+    pub max_size: Option<(i32, i32)>,
+    /// The steps the size grows by from the base size, e.g the size of a
+    /// character in a terminal
+    pub resize_inc: Option<(i32, i32)>,
+    /// Minimum and maximum aspect ratios, as `(numerator, denominator)`
+    pub aspect: Option<((i32, i32), (i32, i32))>,
+    pub base_size: Option<(i32, i32)>,
+    /// The X gravity, e.g 1 for `NorthWest`
+    pub win_gravity: Option<u32>
+}
+
+impl SizeHints {
+    /// Applies the hints to a proposed size of the window, following the
+    /// ICCCM the way upstream awesome does, and returns the size to use.
+    pub fn apply(&self, width: i32, height: i32) -> (i32, i32) {
+        let (mut width, mut height) = (width, height);
+        /* The base size is only subtracted for the aspect ratio if the
+         * client gave one, otherwise the minimum size stands in for it. */
+        let real_base = self.base_size.unwrap_or((0, 0));
+        let base = self.base_size.or(self.min_size).unwrap_or((0, 0));
+        let min = self.min_size.or(self.base_size).unwrap_or((0, 0));
+
+        if let Some(((min_num, min_den), (max_num, max_den))) = self.aspect {
+            if min_den > 0 && max_den > 0
+                && width > real_base.0 && height > real_base.1 {
+                let dx = (width - real_base.0) as f64;
+                let dy = (height - real_base.1) as f64;
+                let ratio = dx / dy;
+                let min_ratio = min_num as f64 / min_den as f64;
+                let max_ratio = max_num as f64 / max_den as f64;
+                if min_ratio > 0.0 && max_ratio > 0.0 && ratio > 0.0 {
+                    if ratio < min_ratio {
+                        /* Too narrow, lower the height (+ 0.5 to round) */
+                        height = (dx / min_ratio + 0.5) as i32 + real_base.1;
+                    } else if ratio > max_ratio {
+                        /* Too wide, lower the width */
+                        width = (dy * max_ratio + 0.5) as i32 + real_base.0;
+                    }
+                }
+            }
+        }
+
+        width = width.max(min.0);
+        height = height.max(min.1);
+        if let Some((max_width, max_height)) = self.max_size {
+            if max_width > 0 {
+                width = width.min(max_width);
+            }
+            if max_height > 0 {
+                height = height.min(max_height);
+            }
+        }
+
+        if let Some((width_inc, height_inc)) = self.resize_inc {
+            /* Each axis on its own, a client may only grow by steps in one */
+            if width_inc > 0 {
+                width -= (width - base.0).max(0) % width_inc;
+            }
+            if height_inc > 0 {
+                height -= (height - base.1).max(0) % height_inc;
+            }
+        }
+        (width, height)
+    }
+}
+
+/// Name of the X gravity, as upstream awesome gives it to Lua.
+fn gravity_name(gravity: u32) -> &'static str {
+    match gravity {
+        2 => "north",
+        3 => "north_east",
+        4 => "west",
+        5 => "center",
+        6 => "east",
+        7 => "south_west",
+        8 => "south",
+        9 => "south_east",
+        10 => "static",
+        _ => "north_west"
+    }
+}
+
+/// A table with the hints the client gave, as `c.size_hints` in upstream
+/// awesome.
+impl ToLua for SizeHints {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        lua_newtable(lua);
+        let set = |name: *const libc::c_char, value: i32| {
+            lua_pushinteger(lua, value as lua_Integer);
+            lua_setfield(lua, -2, name);
+        };
+        let set_pair = |name: *const libc::c_char,
+                        keys: (*const libc::c_char, *const libc::c_char),
+                        value: (i32, i32)| {
+            lua_createtable(lua, 0, 2);
+            lua_pushinteger(lua, value.0 as lua_Integer);
+            lua_setfield(lua, -2, keys.0);
+            lua_pushinteger(lua, value.1 as lua_Integer);
+            lua_setfield(lua, -2, keys.1);
+            lua_setfield(lua, -2, name);
+        };
+        let position = (c_str!("x"), c_str!("y"));
+        let size = (c_str!("width"), c_str!("height"));
+        if let Some(value) = self.user_position {
+            set_pair(c_str!("user_position"), position, value);
+        }
+        if let Some(value) = self.user_size {
+            set_pair(c_str!("user_size"), size, value);
+        }
+        if let Some(value) = self.program_position {
+            set_pair(c_str!("program_position"), position, value);
+        }
+        if let Some(value) = self.program_size {
+            set_pair(c_str!("program_size"), size, value);
+        }
+        if let Some((width, height)) = self.max_size {
+            set(c_str!("max_width"), width);
+            set(c_str!("max_height"), height);
+        }
+        if let Some((width, height)) = self.min_size {
+            set(c_str!("min_width"), width);
+            set(c_str!("min_height"), height);
+        }
+        if let Some((width, height)) = self.resize_inc {
+            set(c_str!("width_inc"), width);
+            set(c_str!("height_inc"), height);
+        }
+        if let Some(gravity) = self.win_gravity {
+            gravity_name(gravity).push_to_lua(lua);
+            lua_setfield(lua, -2, c_str!("win_gravity"));
+        }
+        if let Some(((min_num, min_den), (max_num, max_den))) = self.aspect {
+            set(c_str!("min_aspect_num"), min_num);
+            set(c_str!("min_aspect_den"), min_den);
+            set(c_str!("max_aspect_num"), max_num);
+            set(c_str!("max_aspect_den"), max_den);
+        }
+        if let Some((width, height)) = self.base_size {
+            set(c_str!("base_width"), width);
+            set(c_str!("base_height"), height);
+        }
+        1
+    }
+}
+
+impl ClientState {
+    /// Applies the size hints to the geometry of the client, which includes
+    /// its border. Fullscreen clients are left as they are.
+    pub fn apply_size_hints(&self, geometry: area_t) -> area_t {
+        if self.fullscreen {
+            return geometry
+        }
+        let border = 2 * self.window.border_width as i32;
+        let (width, height) = self.size_hints.apply(geometry.width as i32 - border,
+                                                    geometry.height as i32 - border);
+        let clamp = |size: i32| (size + border).max(0).min(u16::max_value() as i32) as u16;
+        area_t {
+            width: clamp(width),
+            height: clamp(height),
+            .. geometry
+        }
+    }
+
+    /// Moves and resizes the client, honoring its size hints if
+    /// `size_hints_honor` is set.
+    ///
+    /// Use [client_resize](fn.client_resize.html) from Lua, which emits the
+    /// signals of the change.
+    pub fn resize(&mut self, geometry: area_t) {
+        self.geometry = if self.size_hints_honor {
+            self.apply_size_hints(geometry)
+        } else {
+            geometry
+        };
+    }
+}
+
+/// A property of the client that is one field of its geometry, set through
+/// [client_resize](fn.client_resize.html).
+fn geometry_property<V, G, F>(name: &str, get: G, set: F) -> Property
+    where V: ToLua + FromLua,
+          G: Fn(area_t) -> V + 'static,
+          F: Fn(area_t, V) -> area_t + 'static
+{
+    Property {
+        name: name.into(),
+        new: None,
+        index: None,
+        new_index: None,
+        getter: Some(Box::new(move |lua, client| unsafe {
+            get((*(client as *const ClientState)).geometry).push_to_lua(lua)
+        })),
+        setter: Some(Box::new(move |lua, client, oud, idx| unsafe {
+            let value = V::from_lua(lua, idx)?;
+            let client = client as *mut ClientState;
+            let geometry = set((*client).geometry, value);
+            Ok(client_resize(lua, client, oud, geometry))
+        }))
+    }
+}
+
+/// Moves and resizes the client at `oud`, honoring its size hints if
+/// `size_hints_honor` is set, and returns whether its geometry changed.
+///
+/// When it changed `property::geometry` is emitted on the client, then
+/// `property::position` if it moved and `property::size` if it was
+/// resized.
+pub unsafe fn client_resize(lua: *mut lua_State, client: *mut ClientState,
+                            oud: libc::c_int, geometry: area_t) -> bool {
+    let old = (*client).geometry;
+    (*client).resize(geometry);
+    let new = (*client).geometry;
+    if new == old {
+        return false
+    }
+    luaA::object_emit_signal(lua, oud, c_str!("property::geometry"), 0);
+    if (new.x, new.y) != (old.x, old.y) {
+        luaA::object_emit_signal(lua, oud, c_str!("property::position"), 0);
+    }
+    if (new.width, new.height) != (old.width, old.height) {
+        luaA::object_emit_signal(lua, oud, c_str!("property::size"), 0);
+    }
+    true
 }
 
 #[allow(dead_code)]
@@ -128,44 +393,143 @@ pub struct TitleBar {
     drawable: *mut DrawableState
 }
 
-/// Wrapper around pushstring that converts a Rust string to a *const i8
-unsafe fn lua_pushstring_wrapper(lua: *mut lua_State, input: String)
-                          -> *const libc::c_char {
-    let input = CString::new(input).unwrap();
-    let res = lua_pushstring(lua,input.as_ptr());
-    ::std::mem::forget(input);
-    return res;
+/// The properties of the client class.
+///
+/// Besides being set from Lua, most of them are only changed by the
+/// compositor, e.g `class` or `pid`, and are read-only.
+pub fn client_properties() -> Vec<Property> {
+    /* Flags that Lua can set */
+    macro_rules! flags {
+        ($($flag:ident),*) => {
+            vec![$(Property::read_write(stringify!($flag),
+                                        |client: &ClientState| client.$flag,
+                                        |client: &mut ClientState, value| {
+                                            client.$flag = value
+                                        })),*]
+        }
+    }
+    let mut properties = vec![
+        Property::read_write("name",
+                             |client: &ClientState| client.name.clone(),
+                             |client: &mut ClientState, value| client.name = value),
+        Property::read_write("icon_name",
+                             |client: &ClientState| client.icon_name.clone(),
+                             |client: &mut ClientState, value| client.icon_name = value),
+        Property::read_write("startup_id",
+                             |client: &ClientState| client.startup_id.clone(),
+                             |client: &mut ClientState, value| client.startup_id = value),
+        Property::read_only("class", |client: &ClientState| client.class.clone()),
+        Property::read_only("instance", |client: &ClientState| client.instance.clone()),
+        Property::read_only("role", |client: &ClientState| client.role.clone()),
+        Property::read_only("machine", |client: &ClientState| {
+            Some(client.machine.clone()).filter(|machine| !machine.is_empty())
+        }),
+        Property::read_only("pid", |client: &ClientState| {
+            Some(client.pid).filter(|&pid| pid != 0)
+        }),
+        Property::read_only("window", |client: &ClientState| client.window.window),
+        Property::read_only("type", |client: &ClientState| client.window.window_type),
+        Property::read_only("leader_window", |client: &ClientState| client.leader_window),
+        Property::read_only("group_window", |client: &ClientState| client.group_window),
+        Property::read_only("transient_for",
+                            |client: &ClientState| ObjectRef(client.transient_for)),
+        Property::read_write("screen",
+                             |client: &ClientState| {
+                                 ObjectRef(client.screen as *mut ScreenState)
+                             },
+                             |client: &mut ClientState, value: ObjectRef<ScreenState>| {
+                                 client.screen = value.0 as _
+                             }),
+        geometry_property("x", |geometry| geometry.x,
+                          |geometry, x| area_t { x, .. geometry }),
+        geometry_property("y", |geometry| geometry.y,
+                          |geometry, y| area_t { y, .. geometry }),
+        geometry_property("width", |geometry| geometry.width,
+                          |geometry, width| area_t { width, .. geometry }),
+        geometry_property("height", |geometry| geometry.height,
+                          |geometry, height| area_t { height, .. geometry }),
+        Property::read_only("size_hints", |client: &ClientState| client.size_hints),
+        Property::read_write("border_width",
+                             |client: &ClientState| client.window.border_width,
+                             |client: &mut ClientState, value| {
+                                 client.window.border_width = value
+                             }),
+        Property::read_write("border_color",
+                             |client: &ClientState| client.window.border_color.clone(),
+                             |client: &mut ClientState, value| {
+                                 client.window.border_color = value
+                             }),
+        Property::read_write("opacity",
+                             |client: &ClientState| client.window.opacity,
+                             |client: &mut ClientState, value: f64| {
+                                 client.window.opacity = value.max(0.0).min(1.0)
+                             }),
+        /* Unless Lua set it, a client is focusable if it accepts the focus */
+        Property::read_write("focusable",
+                             |client: &ClientState| focus::client_focusable(client),
+                             |client: &mut ClientState, value| {
+                                 client.focusable = value;
+                                 client.focusable_set = true;
+                             }),
+        /* Unless Lua set it, a client is dockable if it's a dock */
+        Property::read_write("dockable",
+                             |client: &ClientState| if client.dockable_set {
+                                 client.dockable
+                             } else {
+                                 client.window.window_type == WindowType::Dock
+                             },
+                             |client: &mut ClientState, value| {
+                                 client.dockable = value;
+                                 client.dockable_set = true;
+                             })
+    ];
+    properties.extend(flags!(skip_taskbar, hidden, minimized, fullscreen, modal,
+                             maximized, maximized_horizontal, maximized_vertical,
+                             ontop, above, below, sticky, urgent,
+                             size_hints_honor));
+    properties
 }
 
-LUA_OBJECT_EXPORT_PROPERTIES!(ClientState, [
-    client_get_class; class; lua_pushstring_wrapper,
-    client_get_instance; instance; lua_pushstring_wrapper,
-    client_get_role; role; lua_pushstring_wrapper,
-    client_get_transient_for; transient_for; object_push,
-    client_get_skip_taskbar; skip_taskbar; lua_pushboolean,
-    client_get_leader_window; leader_window; lua_pushinteger,
-    client_get_group_window; group_window; lua_pushinteger,
-    client_get_hidden; hidden; lua_pushboolean,
-    client_get_minimized; minimized; lua_pushboolean,
-    client_get_fullscreen; fullscreen; lua_pushboolean,
-    client_get_modal; modal; lua_pushboolean,
-    client_get_ontop; ontop; lua_pushboolean,
-    client_get_urgent; urgent; lua_pushboolean,
-    client_get_above; above; lua_pushboolean,
-    client_get_below; below; lua_pushboolean,
-    client_get_sticky; sticky; lua_pushboolean,
-    client_get_size_hints_honor; size_hints_honor; lua_pushboolean,
-    client_get_maximized_horizontal; maximized_horizontal; lua_pushboolean,
-    client_get_maximized_vertical; maximized_vertical; lua_pushboolean,
-    client_get_maximized; maximized; lua_pushboolean,
-    client_get_startup_id; startup_id; lua_pushstring_wrapper
-]);
-
-LUA_OBJECT_EXPORT_OPTIONAL_PROPERTIES!(ClientState, [
-    client_get_screen; screen; object_push; ptr::null_mut(),
-    client_get_machine; machine; lua_pushstring_wrapper; "",
-    client_get_pid; pid; lua_pushinteger; 0
-]);
+/// The properties of the client class that only the compositor can provide,
+/// e.g the icon. They are read from the `Client` callbacks of `T`.
+pub fn client_callback_properties<T: Client + 'static>() -> Vec<Property> {
+    macro_rules! callback {
+        (@property $name:expr, $getter:ident, $setter:expr) => {
+            Property {
+                name: $name.into(),
+                new: None,
+                index: None,
+                new_index: None,
+                getter: Some(Box::new(|lua, client| unsafe {
+                    let callbacks = state::callbacks::<T>(lua);
+                    callbacks.$getter(&Lua(lua), &mut *(client as *mut ClientState))
+                })),
+                setter: $setter
+            }
+        };
+        ($name:expr, $getter:ident) => {
+            callback!(@property $name, $getter, None)
+        };
+        /* The shapes Lua sets are given to the compositor */
+        ($name:expr, $getter:ident, $setter:ident) => {
+            callback!(@property $name, $getter, Some(Box::new(|lua, client, _oud, idx| unsafe {
+                let callbacks = state::callbacks::<T>(lua);
+                callbacks.$setter(&Lua(lua), &mut *(client as *mut ClientState), idx)
+            })))
+        };
+    }
+    vec![
+        callback!("content", client_content),
+        callback!("icon", client_icon),
+        callback!("icon_sizes", client_icon_sizes),
+        callback!("shape_bounding", client_shape_bounding, client_set_shape_bounding),
+        callback!("shape_clip", client_shape_clip, client_set_shape_clip),
+        callback!("shape_input", client_shape_input, client_set_shape_input),
+        callback!("client_shape_bounding", client_client_shape_bounding),
+        callback!("client_shape_clip", client_client_shape_clip),
+        callback!("first_tag", client_first_tag)
+    ]
+}
 
 #[allow(non_snake_case)]
 pub trait Client {
@@ -221,7 +585,7 @@ pub trait Client {
 
     fn client___call(&self, lua: &Lua) -> c_int {
         unsafe {
-            luaA::class_new(lua.0, &*CLIENT_CLASS)
+            luaA::class_new(lua.0, &state::get(lua.0).client_class)
         }
     }
 
@@ -268,9 +632,124 @@ pub trait Client {
         }
     }
 
-    fn client___index(&self, lua: &Lua) -> c_int;
+    fn client___index(&self, lua: &Lua) -> c_int {
+        unsafe {
+            if is_focus_field(lua.0) {
+                let global_conf = state::global_conf(lua.0).try_lock().unwrap();
+                return ObjectRef(global_conf.focus.client).push_to_lua(lua.0)
+            }
+            luaA::default_index(lua.0)
+        }
+    }
 
-    fn client___newindex(&self, lua: &Lua) -> c_int;
+    fn client___newindex(&self, lua: &Lua) -> c_int {
+        unsafe {
+            if is_focus_field(lua.0) {
+                let client = if ::lua::lua_isnonornil(lua.0, 3) {
+                    ::std::ptr::null_mut()
+                } else {
+                    checkclient(lua.0, 3)
+                };
+                client_focus(lua, self, client);
+                return 0
+            }
+            luaA::default_newindex(lua.0)
+        }
+    }
+
+    fn client_focus_history(&self, lua: &Lua) -> c_int {
+        unsafe {
+            client_focus_history(lua.0)
+        }
+    }
+
+    /// Called when Lua set a property of the client to a new value, before
+    /// `property::<name>` is emitted, so that the compositor can apply it,
+    /// e.g move the window when `x` changed.
+    fn client_property_changed(&self, _lua: &Lua, _client: &mut ClientState,
+                               _property: &str) {}
+
+    /// Called with the windows from the bottom to the top whenever the
+    /// stacking order may have changed, so that the compositor can restack
+    /// them.
+    fn client_restack(&self, _lua: &Lua, _windows: &[StackedWindow]) {}
+
+    /// Called when the focus moved from `old` to `new`, after `unfocus` and
+    /// `focus` were emitted. Either can be null, when no client had or has
+    /// the focus.
+    fn client_focus_changed(&self, _lua: &Lua, _old: *mut ClientState,
+                            _new: *mut ClientState) {}
+
+    /* Properties only the compositor knows about, they push the value of
+     * the property of the client and return how many values they pushed.
+     * The defaults push nothing, so the properties are nil. */
+    /// `c.content`, a surface with the content of the client
+    fn client_content(&self, _lua: &Lua, _client: &mut ClientState) -> c_int {
+        0
+    }
+
+    /// `c.icon`, a surface with the icon the client prefers
+    fn client_icon(&self, _lua: &Lua, _client: &mut ClientState) -> c_int {
+        0
+    }
+
+    /// `c.icon_sizes`, a table with the `{width, height}` of each icon the
+    /// client has
+    fn client_icon_sizes(&self, _lua: &Lua, _client: &mut ClientState) -> c_int {
+        0
+    }
+
+    /// `c.shape_bounding`, the shape of the client and its border
+    fn client_shape_bounding(&self, _lua: &Lua, _client: &mut ClientState) -> c_int {
+        0
+    }
+
+    /// `c.shape_clip`, the shape of the client where it's drawn
+    fn client_shape_clip(&self, _lua: &Lua, _client: &mut ClientState) -> c_int {
+        0
+    }
+
+    /// `c.shape_input`, the shape of the client that gets the input
+    fn client_shape_input(&self, _lua: &Lua, _client: &mut ClientState) -> c_int {
+        0
+    }
+
+    /// `c.client_shape_bounding`, the shape of the client set by itself
+    fn client_client_shape_bounding(&self, _lua: &Lua, _client: &mut ClientState)
+                                    -> c_int {
+        0
+    }
+
+    /// `c.client_shape_clip`, the clip shape of the client set by itself
+    fn client_client_shape_clip(&self, _lua: &Lua, _client: &mut ClientState)
+                                -> c_int {
+        0
+    }
+
+    /// `c.first_tag`, the first tag the client is on
+    fn client_first_tag(&self, _lua: &Lua, _client: &mut ClientState) -> c_int {
+        0
+    }
+
+    /// Called when Lua sets `c.shape_bounding` to the value at `idx`,
+    /// returning whether the shape changed so that `property::shape_bounding`
+    /// is emitted. The default ignores the shape.
+    fn client_set_shape_bounding(&self, _lua: &Lua, _client: &mut ClientState,
+                                 _idx: c_int) -> Result<bool, LuaErr> {
+        Ok(false)
+    }
+
+    /// Like `client_set_shape_bounding`, for `c.shape_clip`.
+    fn client_set_shape_clip(&self, _lua: &Lua, _client: &mut ClientState,
+                             _idx: c_int) -> Result<bool, LuaErr> {
+        Ok(false)
+    }
+
+    /// Like `client_set_shape_bounding`, for `c.shape_input`.
+    fn client_set_shape_input(&self, _lua: &Lua, _client: &mut ClientState,
+                              _idx: c_int) -> Result<bool, LuaErr> {
+        Ok(false)
+    }
 
     /* Meta */
     fn client_keys(&self, lua: &Lua) -> c_int;
@@ -279,7 +758,11 @@ pub trait Client {
 
     fn client_geometry(&self, lua: &Lua) -> c_int;
 
-    fn client_apply_size_hints(&self, lua: &Lua) -> c_int;
+    fn client_apply_size_hints(&self, lua: &Lua) -> c_int {
+        unsafe {
+            client_apply_size_hints(lua.0)
+        }
+    }
 
     fn client_tags(&self, lua: &Lua) -> c_int;
 
@@ -287,9 +770,31 @@ pub trait Client {
 
     fn client_swap(&self, lua: &Lua) -> c_int;
 
-    fn client_raise(&self, lua: &Lua) -> c_int;
+    fn client_raise(&self, lua: &Lua) -> c_int {
+        unsafe {
+            let client = checkclient(lua.0, 1);
+            {
+                let mut global_conf = state::global_conf(lua.0).try_lock().unwrap();
+                stack::client_raise(&mut global_conf, client);
+            }
+            stack_refresh(lua, self);
+            luaA::object_emit_signal(lua.0, 1, c_str!("raised"), 0);
+        }
+        0
+    }
 
-    fn client_lower(&self, lua: &Lua) -> c_int;
+    fn client_lower(&self, lua: &Lua) -> c_int {
+        unsafe {
+            let client = checkclient(lua.0, 1);
+            {
+                let mut global_conf = state::global_conf(lua.0).try_lock().unwrap();
+                stack::client_lower(&mut global_conf, client);
+            }
+            stack_refresh(lua, self);
+            luaA::object_emit_signal(lua.0, 1, c_str!("lowered"), 0);
+        }
+        0
+    }
 
     fn client_unmanange(&self, lua: &Lua) -> c_int;
 
@@ -313,39 +818,140 @@ pub unsafe fn client_get(lua: *mut lua_State) -> libc::c_int {
         luaA::checkboolean(lua, 2) != 0
     } else { false };
     lua_newtable(lua);
-    let global_conf = GLOBAL_CONF.try_lock().unwrap();
-    if stacked {
-        for stack in &global_conf.stack {
-            if screen.is_none() || stack.screen == screen.unwrap() as _ {
-                luaA::object_push(lua, stack as *const _ as *mut ClientState as _);
-                lua_rawseti(lua, -2, i);
-                i += 1;
-            }
-        }
+    let global_conf = state::global_conf(lua).try_lock().unwrap();
+    let clients = if stacked {
+        /* From the top to the bottom */
+        stack::stacked_clients(&global_conf).into_iter().rev().collect()
     } else {
-        for client in &global_conf.clients {
-            if screen.is_none() || client.screen == screen.unwrap() as _ {
-                luaA::object_push(lua, client as *const _ as *mut ClientState as _);
-                lua_rawseti(lua, -2, i);
-                i += 1;
-            }
+        global_conf.clients.clone()
+    };
+    for client in clients {
+        if screen.is_none() || (*client).screen == screen.unwrap() as _ {
+            luaA::object_push(lua, client as _);
+            ::compat::rawseti(lua, -2, i);
+            i += 1;
         }
     }
     1
 }
 
+/// `client.focus_history([screen])`, the clients of the screen that had the
+/// focus, the most recent first.
+pub unsafe fn client_focus_history(lua: *mut lua_State) -> libc::c_int {
+    let screen = if ::lua::lua_isnonornil(lua, 1) {
+        checkscreen(lua, 1)
+    } else { None };
+    let global_conf = state::global_conf(lua).try_lock().unwrap();
+    let history = focus::focus_history(&global_conf, screen);
+    history.into_iter().map(ObjectRef).collect::<Vec<_>>().push_to_lua(lua)
+}
+
+/// `c:apply_size_hints(width, height)`, the size the client would have
+/// with its size hints applied to the given one.
+///
+/// Raises an error if the size doesn't fit in the geometry of a client.
+pub unsafe fn client_apply_size_hints(lua: *mut lua_State) -> libc::c_int {
+    let client = &*checkclient(lua, 1);
+    /* The error is only formatted here, as it must be dropped before
+     * it's raised */
+    let message = match (u16::from_lua(lua, 2), u16::from_lua(lua, 3)) {
+        (Ok(width), Ok(height)) => {
+            let mut geometry = client.geometry;
+            if !client.fullscreen {
+                geometry.width = width;
+                geometry.height = height;
+                geometry = client.apply_size_hints(geometry);
+            }
+            lua_pushinteger(lua, geometry.width as lua_Integer);
+            lua_pushinteger(lua, geometry.height as lua_Integer);
+            return 2
+        },
+        (Err(err), _) | (_, Err(err)) => format!("bad size: {}", err)
+    };
+    luaA::error(lua, message)
+}
+
 pub unsafe fn checkscreen(lua: *mut lua_State, sidx: libc::c_int)
                             -> Option<*mut ScreenState> {
-    let mut global_conf = GLOBAL_CONF.try_lock().unwrap();
-    if lua_isnumber(lua, sidx) != 0 {
-        let screen = lua_tointeger(lua, sidx);
-        if screen < 1 || screen as usize > global_conf.screens.len() {
-            luaL_error(lua, c_str!("invalid screen number"));
-        }
-        return Some(&mut global_conf.screens[screen as usize - 1] as _);
-    } else {
-        let mut screen_class = SCREEN_CLASS.try_read().unwrap();
-        let class_ptr = &mut screen_class as *mut _ as *mut Class;
+    if lua_isnumber(lua, sidx) == 0 {
+        let class_ptr = &*state::get(lua).screen_class.try_read().unwrap()
+            as *const Class as *mut Class;
         return Some(luaA::checkudata(lua, sidx, class_ptr) as _)
     }
+    let screen = lua_tointeger(lua, sidx);
+    /* Not locked while raising the error, as it would stay locked */
+    let found = {
+        let global_conf = state::global_conf(lua).try_lock().unwrap();
+        if screen < 1 {
+            None
+        } else {
+            global_conf.screens.get(screen as usize - 1).cloned()
+        }
+    };
+    if found.is_none() {
+        luaL_error(lua, c_str!("invalid screen number"));
+    }
+    found
+}
+
+/// Checks that the value at `idx` is a valid client.
+pub unsafe fn checkclient(lua: *mut lua_State, idx: libc::c_int) -> *mut ClientState {
+    let class = &*state::get(lua).client_class.try_read().unwrap()
+        as *const Class as *mut Class;
+    luaA::checkudata(lua, idx, class) as *mut ClientState
+}
+
+/// Tells the compositor the current stacking order of the windows.
+pub fn stack_refresh<T: Client + ?Sized>(lua: &Lua, callbacks: &T) {
+    let order = unsafe {
+        let global_conf = state::global_conf(lua.0).try_lock().unwrap();
+        stack::stacking_order(&global_conf)
+    };
+    callbacks.client_restack(lua, &order);
+}
+
+/// Gives the focus to the client, or takes it away if it is null.
+///
+/// `unfocus` is emitted on the client that had the focus and `focus` on
+/// the new one, whose urgency is cleared. Returns false if the focus did
+/// not change, e.g because the client isn't focusable.
+pub fn client_focus<T: Client + ?Sized>(lua: &Lua, callbacks: &T,
+                                        client: *mut ClientState) -> bool {
+    unsafe {
+        let old = {
+            let mut global_conf = state::global_conf(lua.0).try_lock().unwrap();
+            match focus::client_focus(&mut global_conf, client) {
+                Some(old) => old,
+                None => return false
+            }
+        };
+        if !old.is_null() {
+            luaA::object_push(lua.0, old as _);
+            if lua_type(lua.0, -1) != LUA_TNIL as c_int {
+                luaA::object_emit_signal(lua.0, -1, c_str!("unfocus"), 0);
+            }
+            lua_pop(lua.0, 1);
+        }
+        if !client.is_null() {
+            luaA::object_push(lua.0, client as _);
+            /* according to EWMH, we have to remove the urgent state from a client */
+            if (*client).urgent {
+                (*client).urgent = false;
+                callbacks.client_property_changed(lua, &mut *client, "urgent");
+                luaA::object_emit_signal(lua.0, -1, c_str!("property::urgent"), 0);
+            }
+            luaA::object_emit_signal(lua.0, -1, c_str!("focus"), 0);
+            lua_pop(lua.0, 1);
+        }
+        callbacks.client_focus_changed(lua, old, client);
+        /* A fullscreen client is only above the others while it's focused */
+        stack_refresh(lua, callbacks);
+    }
+    true
+}
+
+/// Whether the field indexed on the client class is `focus`.
+unsafe fn is_focus_field(lua: *mut lua_State) -> bool {
+    lua_type(lua, 2) == LUA_TSTRING as c_int &&
+        CStr::from_ptr(lua_tolstring(lua, 2, ::std::ptr::null_mut())).to_bytes() == b"focus"
 }
@@ -2,19 +2,27 @@
 
 use ::luaA;
 use ::lua::Lua;
-use libc::{self, c_int};
-use ::object::Signal;
+use libc::c_int;
+use ::object::Signals;
 use ::object::class::{Class, Object};
 use lua_sys::*;
 use cairo::surface::Surface;
 
 pub type RefreshCallback = fn(*mut libc::c_void);
 
-LUA_OBJECT_FUNCS!(luaA::DRAWABLE_CLASS, Class, new);
+LUA_OBJECT_FUNCS!(drawable_class, Class, new);
+LUA_CLASS_FUNCS!(drawable_class,
+                 drawable_class_add_signal,
+                 drawable_class_connect_signal,
+                 drawable_class_disconnect_signal,
+                 drawable_class_emit_signal,
+                 drawable_class_instances,
+                 drawable_set_index_miss_handler,
+                 drawable_set_newindex_miss_handler);
 
 #[repr(C)]
 pub struct DrawableState {
-    pub signals: Vec<Signal>,
+    pub signals: Signals,
     /// The pixmap we are drawing to
     pub pixmap: *mut libc::c_void,
     /// Surface for drawing
@@ -35,19 +43,47 @@ pub struct DrawableState {
 pub trait Drawable {
     // Class Methods
 
-    fn drawable_add_signal(&self, lua: &Lua) -> c_int;
+    fn drawable_add_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            drawable_class_add_signal(lua.0)
+        }
+    }
 
-    fn drawable_connect_signal(&self, lua: &Lua) -> c_int;
+    fn drawable_connect_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            drawable_class_connect_signal(lua.0)
+        }
+    }
 
-    fn drawable_disconnect_signal(&self, lua: &Lua) -> c_int;
+    fn drawable_disconnect_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            drawable_class_disconnect_signal(lua.0)
+        }
+    }
 
-    fn drawable_emit_signal(&self, lua: &Lua) -> c_int;
+    fn drawable_emit_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            drawable_class_emit_signal(lua.0)
+        }
+    }
 
-    fn drawable_instances(&self, lua: &Lua) -> c_int;
+    fn drawable_instances(&self, lua: &Lua) -> c_int {
+        unsafe {
+            drawable_class_instances(lua.0)
+        }
+    }
 
-    fn drawable_set_index_miss_handler(&self, lua: &Lua) -> c_int;
+    fn drawable_set_index_miss_handler(&self, lua: &Lua) -> c_int {
+        unsafe {
+            drawable_set_index_miss_handler(lua.0)
+        }
+    }
 
-    fn drawable_set_newindex_miss_handler(&self, lua: &Lua) -> c_int;
+    fn drawable_set_newindex_miss_handler(&self, lua: &Lua) -> c_int {
+        unsafe {
+            drawable_set_newindex_miss_handler(lua.0)
+        }
+    }
     // Object meta methods
 
     fn drawable___tostring_meta(&self, lua: &Lua) -> c_int {
@@ -3,9 +3,22 @@
 use ::luaA;
 use ::lua::Lua;
 use libc::c_int;
+use lua_sys::*;
 use ::object::WindowState;
+use ::object::class::Object;
 use ::callbacks::drawable::DrawableState;
 
+LUA_OBJECT_FUNCS!(drawin_class, DrawinState, drawin_new,
+                  |drawin: &mut DrawinState| drawin.valid = true);
+LUA_CLASS_FUNCS!(drawin_class,
+                 drawin_class_add_signal,
+                 drawin_class_connect_signal,
+                 drawin_class_disconnect_signal,
+                 drawin_class_emit_signal,
+                 drawin_class_instances,
+                 drawin_set_index_miss_handler,
+                 drawin_set_newindex_miss_handler);
+
 #[repr(C)]
 pub struct DrawinState {
     pub window: WindowState,
@@ -17,19 +30,61 @@ pub struct DrawinState {
     /// The window geometry
     pub geometry: luaA::area_t,
     /// Do we have a pending geometry change that still needs to be applied?
-    pub geometry_dirty: bool
+    pub geometry_dirty: bool,
+    /// Whether the surface of the drawin exists, true when it's created
+    pub valid: bool
+}
+
+impl DrawinState {
+    /// Marks the drawin as backed by a surface or not, like
+    /// `ClientState::set_valid`.
+    pub fn set_valid(&mut self, valid: bool) {
+        self.valid = valid;
+    }
+}
+
+pub unsafe fn drawin_checker(object: *mut Object) -> bool {
+    (*(object as *mut DrawinState)).valid
 }
 
 #[allow(non_snake_case)]
 pub trait Drawin {
     // Class Methods
-    fn drawin_add_signal(&self, lua: &Lua) -> c_int;
-    fn drawin_connect_signal(&self, lua: &Lua) -> c_int;
-    fn drawin_disconnect_signal(&self, lua: &Lua) -> c_int;
-    fn drawin_emit_signal(&self, lua: &Lua) -> c_int;
-    fn drawin_instances(&self, lua: &Lua) -> c_int;
-    fn drawin_set_index_miss_handler(&self, lua: &Lua) -> c_int;
-    fn drawin_set_newindex_miss_handler(&self, lua: &Lua) -> c_int;
+    fn drawin_add_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            drawin_class_add_signal(lua.0)
+        }
+    }
+    fn drawin_connect_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            drawin_class_connect_signal(lua.0)
+        }
+    }
+    fn drawin_disconnect_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            drawin_class_disconnect_signal(lua.0)
+        }
+    }
+    fn drawin_emit_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            drawin_class_emit_signal(lua.0)
+        }
+    }
+    fn drawin_instances(&self, lua: &Lua) -> c_int {
+        unsafe {
+            drawin_class_instances(lua.0)
+        }
+    }
+    fn drawin_set_index_miss_handler(&self, lua: &Lua) -> c_int {
+        unsafe {
+            drawin_set_index_miss_handler(lua.0)
+        }
+    }
+    fn drawin_set_newindex_miss_handler(&self, lua: &Lua) -> c_int {
+        unsafe {
+            drawin_set_newindex_miss_handler(lua.0)
+        }
+    }
     // Methods
     fn drawin___call(&self, lua: &Lua) -> c_int;
     // Object meta methods
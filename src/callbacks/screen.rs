@@ -1,24 +1,103 @@
 //! Callbacks for the `screen` object in the Lua libraries
 
 use ::luaA;
-use ::lua::Lua;
+use ::lua::{Lua, LuaErr};
+use ::state;
+use ::convert::{self, FromLua, ObjectRef};
+use ::object::signal::Signals;
+use ::object::class::{Class, Object};
 use libc::c_int;
+use lua_sys::*;
+
+LUA_OBJECT_FUNCS!(screen_class, ScreenState, screen_new,
+                  |screen: &mut ScreenState| screen.valid = true);
+LUA_CLASS_FUNCS!(screen_class,
+                 screen_class_add_signal,
+                 screen_class_connect_signal,
+                 screen_class_disconnect_signal,
+                 screen_class_emit_signal,
+                 screen_class_instances,
+                 screen_set_index_miss_handler,
+                 screen_set_newindex_miss_handler);
 
 #[repr(C)]
 pub struct ScreenState {
+    pub signals: Signals,
+    /// Whether the output of the screen exists, true when it's created
+    pub valid: bool
     // TODO IMPLEMENT
 }
 
+impl ScreenState {
+    /// Marks the screen as backed by an output or not, e.g once the output
+    /// is gone. The screen can't be used from Lua while it's invalid.
+    pub fn set_valid(&mut self, valid: bool) {
+        self.valid = valid;
+    }
+}
+
+pub unsafe fn screen_checker(object: *mut Object) -> bool {
+    (*(object as *mut ScreenState)).valid
+}
+
+/// A screen object, or the number of a screen starting from 1.
+impl FromLua for ObjectRef<ScreenState> {
+    unsafe fn from_lua(lua: *mut lua_State, idx: c_int) -> Result<Self, LuaErr> {
+        if lua_type(lua, idx) == LUA_TNUMBER as c_int {
+            let number = lua_tointeger(lua, idx);
+            let global_conf = state::global_conf(lua).lock().unwrap();
+            return global_conf.screens.get((number - 1) as usize)
+                .map(|&screen| ObjectRef(screen))
+                .ok_or_else(|| LuaErr::OutOfRange(number.to_string()))
+        }
+        let class = &*state::get(lua).screen_class.try_read().unwrap()
+            as *const Class as *mut Class;
+        let screen = luaA::toudata(lua, idx, class);
+        if screen.is_null() {
+            return Err(convert::type_error(lua, idx, "screen"))
+        }
+        Ok(ObjectRef(screen as _))
+    }
+}
+
 #[allow(non_snake_case)]
 pub trait Screen {
     // Class Methods
-    fn screen_add_signal(&self, lua: &Lua) -> c_int;
-    fn screen_connect_signal(&self, lua: &Lua) -> c_int;
-    fn screen_disconnect_signal(&self, lua: &Lua) -> c_int;
-    fn screen_emit_signal(&self, lua: &Lua) -> c_int;
-    fn screen_instances(&self, lua: &Lua) -> c_int;
-    fn screen_set_index_miss_handler(&self, lua: &Lua) -> c_int;
-    fn screen_set_newindex_miss_handler(&self, lua: &Lua) -> c_int;
+    fn screen_add_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            screen_class_add_signal(lua.0)
+        }
+    }
+    fn screen_connect_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            screen_class_connect_signal(lua.0)
+        }
+    }
+    fn screen_disconnect_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            screen_class_disconnect_signal(lua.0)
+        }
+    }
+    fn screen_emit_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            screen_class_emit_signal(lua.0)
+        }
+    }
+    fn screen_instances(&self, lua: &Lua) -> c_int {
+        unsafe {
+            screen_class_instances(lua.0)
+        }
+    }
+    fn screen_set_index_miss_handler(&self, lua: &Lua) -> c_int {
+        unsafe {
+            screen_set_index_miss_handler(lua.0)
+        }
+    }
+    fn screen_set_newindex_miss_handler(&self, lua: &Lua) -> c_int {
+        unsafe {
+            screen_set_newindex_miss_handler(lua.0)
+        }
+    }
     // Methods
     fn screen_count(&self, lua: &Lua) -> c_int;
     fn screen___index(&self, lua: &Lua) -> c_int;
@@ -2,23 +2,65 @@
 
 use ::luaA;
 use ::lua::Lua;
+use ::object::signal::Signals;
+use ::object::class::Object;
 use libc::c_int;
+use lua_sys::*;
+
+LUA_OBJECT_FUNCS!(tag_class, TagState, tag_new);
+LUA_CLASS_FUNCS!(tag_class,
+                 tag_class_add_signal,
+                 tag_class_connect_signal,
+                 tag_class_disconnect_signal,
+                 tag_class_emit_signal,
+                 tag_class_instances,
+                 tag_set_index_miss_handler,
+                 tag_set_newindex_miss_handler);
 
 #[repr(C)]
 pub struct TagState {
+    pub signals: Signals
     // TODO Fill in
 }
 
 #[allow(non_snake_case)]
 pub trait Tag {
     // Class Methods
-    fn tag_add_signal(&self, lua: &Lua) -> c_int;
-    fn tag_connect_signal(&self, lua: &Lua) -> c_int;
-    fn tag_disconnect_signal(&self, lua: &Lua) -> c_int;
-    fn tag_emit_signal(&self, lua: &Lua) -> c_int;
-    fn tag_instances(&self, lua: &Lua) -> c_int;
-    fn tag_set_index_miss_handler(&self, lua: &Lua) -> c_int;
-    fn tag_set_newindex_miss_handler(&self, lua: &Lua) -> c_int;
+    fn tag_add_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            tag_class_add_signal(lua.0)
+        }
+    }
+    fn tag_connect_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            tag_class_connect_signal(lua.0)
+        }
+    }
+    fn tag_disconnect_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            tag_class_disconnect_signal(lua.0)
+        }
+    }
+    fn tag_emit_signal(&self, lua: &Lua) -> c_int {
+        unsafe {
+            tag_class_emit_signal(lua.0)
+        }
+    }
+    fn tag_instances(&self, lua: &Lua) -> c_int {
+        unsafe {
+            tag_class_instances(lua.0)
+        }
+    }
+    fn tag_set_index_miss_handler(&self, lua: &Lua) -> c_int {
+        unsafe {
+            tag_set_index_miss_handler(lua.0)
+        }
+    }
+    fn tag_set_newindex_miss_handler(&self, lua: &Lua) -> c_int {
+        unsafe {
+            tag_set_newindex_miss_handler(lua.0)
+        }
+    }
     // Methods
     fn tag___call(&self, lua: &Lua) -> c_int;
     fn tag___tostring_meta(&self, lua: &Lua) -> c_int {
@@ -0,0 +1,239 @@
+//! Compatibility layer between the supported versions of Lua.
+//!
+//! The version is picked at build time with the `lua51`, `lua52`, `lua53`
+//! (the default) and `luajit` features, which the build script turns in to
+//! the `lua_api` cfg. Lua 5.3 is bound by the `lua-sys` crate, the other
+//! versions by the [lua_sys](../lua_sys/index.html) module of this crate.
+//!
+//! Functions that exist in every version are used directly from `lua_sys`,
+//! the ones here paper over the differences.
+
+use lua_sys::*;
+use libc;
+
+/// Removes the element at the given index, shifting down the elements
+/// above it.
+#[cfg(lua_api = "53")]
+pub unsafe fn lua_remove(lua: *mut lua_State, idx: libc::c_int) {
+    lua_rotate(lua, idx, -1);
+    lua_pop(lua, 1);
+}
+
+#[cfg(not(lua_api = "53"))]
+pub unsafe fn lua_remove(lua: *mut lua_State, idx: libc::c_int) {
+    ::lua_sys::lua_remove(lua, idx)
+}
+
+/// Moves the top element to the given index, shifting up the elements
+/// above it.
+#[cfg(lua_api = "53")]
+pub unsafe fn lua_insert(lua: *mut lua_State, idx: libc::c_int) {
+    lua_rotate(lua, idx, 1);
+}
+
+#[cfg(not(lua_api = "53"))]
+pub unsafe fn lua_insert(lua: *mut lua_State, idx: libc::c_int) {
+    ::lua_sys::lua_insert(lua, idx)
+}
+
+/// Pops the top element and puts it at the given index, replacing the
+/// element there.
+#[cfg(lua_api = "53")]
+pub unsafe fn lua_replace(lua: *mut lua_State, idx: libc::c_int) {
+    lua_copy(lua, -1, idx);
+    lua_pop(lua, 1);
+}
+
+#[cfg(not(lua_api = "53"))]
+pub unsafe fn lua_replace(lua: *mut lua_State, idx: libc::c_int) {
+    ::lua_sys::lua_replace(lua, idx)
+}
+
+/// Pushes the user value of the userdata, its environment before Lua 5.2.
+#[cfg(not(lua_api = "51"))]
+pub unsafe fn getuservalue(lua: *mut lua_State, idx: libc::c_int) {
+    lua_getuservalue(lua, idx);
+}
+
+#[cfg(lua_api = "51")]
+pub unsafe fn getuservalue(lua: *mut lua_State, idx: libc::c_int) {
+    lua_getfenv(lua, idx);
+}
+
+/// Pops a table and sets it as the user value of the userdata.
+#[cfg(not(lua_api = "51"))]
+pub unsafe fn setuservalue(lua: *mut lua_State, idx: libc::c_int) {
+    lua_setuservalue(lua, idx);
+}
+
+#[cfg(lua_api = "51")]
+pub unsafe fn setuservalue(lua: *mut lua_State, idx: libc::c_int) {
+    lua_setfenv(lua, idx);
+}
+
+/// The length of the value without calling `__len`.
+#[cfg(not(lua_api = "51"))]
+pub unsafe fn rawlen(lua: *mut lua_State, idx: libc::c_int) -> libc::size_t {
+    lua_rawlen(lua, idx) as libc::size_t
+}
+
+#[cfg(lua_api = "51")]
+pub unsafe fn rawlen(lua: *mut lua_State, idx: libc::c_int) -> libc::size_t {
+    lua_objlen(lua, idx)
+}
+
+/// Pushes `t[n]`, where `t` is the table at `idx`, without calling
+/// `__index`.
+pub unsafe fn rawgeti(lua: *mut lua_State, idx: libc::c_int, n: lua_Integer) {
+    /* The key is an int before Lua 5.3 */
+    lua_rawgeti(lua, idx, n as _);
+}
+
+/// Pops a value and sets it as `t[n]`, where `t` is the table at `idx`,
+/// without calling `__newindex`.
+pub unsafe fn rawseti(lua: *mut lua_State, idx: libc::c_int, n: lua_Integer) {
+    lua_rawseti(lua, idx, n as _);
+}
+
+/// Converts the value at `idx` to a number, setting `isnum` to whether it
+/// could be converted.
+#[cfg(not(lua_api = "51"))]
+pub unsafe fn tonumberx(lua: *mut lua_State, idx: libc::c_int,
+                        isnum: *mut libc::c_int) -> lua_Number {
+    lua_tonumberx(lua, idx, isnum)
+}
+
+#[cfg(lua_api = "51")]
+pub unsafe fn tonumberx(lua: *mut lua_State, idx: libc::c_int,
+                        isnum: *mut libc::c_int) -> lua_Number {
+    if !isnum.is_null() {
+        *isnum = lua_isnumber(lua, idx);
+    }
+    lua_tonumber(lua, idx)
+}
+
+/// Converts the value at `idx` to an integer, setting `isnum` to whether it
+/// could be converted.
+#[cfg(lua_api = "53")]
+pub unsafe fn tointegerx(lua: *mut lua_State, idx: libc::c_int,
+                         isnum: *mut libc::c_int) -> lua_Integer {
+    lua_tointegerx(lua, idx, isnum)
+}
+
+#[cfg(not(lua_api = "53"))]
+pub unsafe fn tointegerx(lua: *mut lua_State, idx: libc::c_int,
+                         isnum: *mut libc::c_int) -> lua_Integer {
+    let mut is_num = 0;
+    let num = tonumberx(lua, idx, &mut is_num);
+    /* Numbers are all floats before Lua 5.3, only the ones without a
+     * fractional part are integers like they are in Lua 5.3 */
+    if num.fract() != 0.0 {
+        is_num = 0;
+    }
+    if !isnum.is_null() {
+        *isnum = is_num;
+    }
+    if is_num != 0 { num as lua_Integer } else { 0 }
+}
+
+/// Calls a function in protected mode, with `msgh` as the message handler.
+#[cfg(not(lua_api = "51"))]
+pub unsafe fn pcall(lua: *mut lua_State, nargs: libc::c_int,
+                    nresults: libc::c_int, msgh: libc::c_int) -> libc::c_int {
+    lua_pcallk(lua, nargs, nresults, msgh, 0, None)
+}
+
+#[cfg(lua_api = "51")]
+pub unsafe fn pcall(lua: *mut lua_State, nargs: libc::c_int,
+                    nresults: libc::c_int, msgh: libc::c_int) -> libc::c_int {
+    lua_pcall(lua, nargs, nresults, msgh)
+}
+
+/// Registers the functions in the table on the top of the stack.
+#[cfg(not(lua_api = "51"))]
+pub unsafe fn setfuncs(lua: *mut lua_State, l: &[luaL_Reg]) {
+    luaL_setfuncs(lua, l.as_ptr(), 0);
+}
+
+#[cfg(lua_api = "51")]
+pub unsafe fn setfuncs(lua: *mut lua_State, l: &[luaL_Reg]) {
+    luaL_register(lua, ::std::ptr::null(), l.as_ptr());
+}
+
+/// Compiles the buffer as a chunk named `name`.
+#[cfg(not(lua_api = "51"))]
+pub unsafe fn loadbuffer(lua: *mut lua_State, buf: &str,
+                         name: *const libc::c_char) -> libc::c_int {
+    luaL_loadbufferx(lua, buf.as_ptr() as _, buf.len(), name,
+                     ::std::ptr::null())
+}
+
+#[cfg(lua_api = "51")]
+pub unsafe fn loadbuffer(lua: *mut lua_State, buf: &str,
+                         name: *const libc::c_char) -> libc::c_int {
+    luaL_loadbuffer(lua, buf.as_ptr() as _, buf.len(), name)
+}
+
+/// Compiles the file as a chunk.
+#[cfg(not(lua_api = "51"))]
+pub unsafe fn loadfile(lua: *mut lua_State, path: *const libc::c_char)
+                       -> libc::c_int {
+    luaL_loadfilex(lua, path, ::std::ptr::null())
+}
+
+#[cfg(lua_api = "51")]
+pub unsafe fn loadfile(lua: *mut lua_State, path: *const libc::c_char)
+                       -> libc::c_int {
+    luaL_loadfile(lua, path)
+}
+
+/// Pushes the traceback of the stack, starting at `level`.
+#[cfg(not(lua_api = "51"))]
+pub unsafe fn traceback(lua: *mut lua_State, level: libc::c_int) {
+    luaL_traceback(lua, lua, ::std::ptr::null_mut(), level);
+}
+
+#[cfg(lua_api = "51")]
+pub unsafe fn traceback(lua: *mut lua_State, level: libc::c_int) {
+    /* There is no luaL_traceback, only debug.traceback */
+    lua_getglobal(lua, c_str!("debug"));
+    if lua_type(lua, -1) == LUA_TTABLE as i32 {
+        lua_getfield(lua, -1, c_str!("traceback"));
+        ::lua_sys::lua_remove(lua, -2);
+    }
+    if lua_type(lua, -1) != LUA_TFUNCTION as i32 {
+        lua_pop(lua, 1);
+        lua_pushstring(lua, c_str!(""));
+        return
+    }
+    lua_pushstring(lua, c_str!(""));
+    /* One more level, for debug.traceback itself */
+    lua_pushinteger(lua, (level + 1) as lua_Integer);
+    lua_call(lua, 2, 1);
+}
+
+/// The pseudo-index of the i-th upvalue of the running function.
+#[cfg(not(lua_api = "51"))]
+pub fn upvalueindex(i: libc::c_int) -> libc::c_int {
+    LUA_REGISTRYINDEX - i
+}
+
+#[cfg(lua_api = "51")]
+pub fn upvalueindex(i: libc::c_int) -> libc::c_int {
+    LUA_GLOBALSINDEX - i
+}
+
+/// Pops a table and makes it the global environment of the chunk at `idx`.
+#[cfg(not(lua_api = "51"))]
+pub unsafe fn setchunkenv(lua: *mut lua_State, idx: libc::c_int) {
+    /* _ENV is the first upvalue of a main chunk */
+    if lua_setupvalue(lua, idx, 1).is_null() {
+        /* The chunk doesn't use any globals */
+        lua_pop(lua, 1);
+    }
+}
+
+#[cfg(lua_api = "51")]
+pub unsafe fn setchunkenv(lua: *mut lua_State, idx: libc::c_int) {
+    lua_setfenv(lua, idx);
+}
@@ -0,0 +1,459 @@
+//! Conversions between Rust values and values on the Lua stack.
+//!
+//! Implement [ToLua](trait.ToLua.html) and [FromLua](trait.FromLua.html)
+//! for your own types so that they can be used with
+//! [Lua::push](../struct.Lua.html#method.push) and
+//! [Lua::get](../struct.Lua.html#method.get).
+
+use lua_sys::*;
+use libc;
+use std::collections::HashMap;
+use std::hash::Hash;
+use lua::{self, luaA, LuaErr, FFIErr, StackFrame};
+use luaA::area_t;
+
+/// A value that can be pushed on to the Lua stack.
+pub trait ToLua {
+    /// Pushes the value on to the stack of the given Lua state.
+    ///
+    /// Returns the number of values that were pushed, so that the result can
+    /// be returned directly from a Lua callback.
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int;
+}
+
+/// A value that can be read from the Lua stack.
+pub trait FromLua: Sized {
+    /// Reads the value at the given stack index.
+    ///
+    /// The stack is left untouched.
+    unsafe fn from_lua(lua: *mut lua_State, idx: libc::c_int)
+                       -> Result<Self, LuaErr>;
+}
+
+/// Builds the error for a value of the wrong type at `idx`.
+pub unsafe fn type_error(lua: *mut lua_State, idx: libc::c_int,
+                         expected: &str) -> LuaErr {
+    let got = lua_typename(lua, lua_type(lua, idx));
+    let got = ::std::ffi::CStr::from_ptr(got).to_string_lossy().into_owned();
+    LuaErr::Type(expected.into(), got)
+}
+
+macro_rules! impl_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl ToLua for $ty {
+                unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+                    lua_pushinteger(lua, self as lua_Integer);
+                    1
+                }
+            }
+
+            impl FromLua for $ty {
+                #[allow(unused_comparisons)]
+                unsafe fn from_lua(lua: *mut lua_State, idx: libc::c_int)
+                                   -> Result<Self, LuaErr> {
+                    let mut is_num = 0;
+                    let num = ::compat::tointegerx(lua, idx, &mut is_num);
+                    if is_num == 0 {
+                        return Err(type_error(lua, idx, "integer"))
+                    }
+                    let value = num as $ty;
+                    if value as lua_Integer != num || (num < 0) != (value < 0 as $ty) {
+                        return Err(LuaErr::OutOfRange(num.to_string()))
+                    }
+                    Ok(value)
+                }
+            }
+        )*
+    }
+}
+
+impl_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+macro_rules! impl_float {
+    ($($ty:ty),*) => {
+        $(
+            impl ToLua for $ty {
+                unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+                    lua_pushnumber(lua, self as lua_Number);
+                    1
+                }
+            }
+
+            impl FromLua for $ty {
+                unsafe fn from_lua(lua: *mut lua_State, idx: libc::c_int)
+                                   -> Result<Self, LuaErr> {
+                    let mut is_num = 0;
+                    let num = ::compat::tonumberx(lua, idx, &mut is_num);
+                    if is_num == 0 {
+                        return Err(type_error(lua, idx, "number"))
+                    }
+                    Ok(num as $ty)
+                }
+            }
+        )*
+    }
+}
+
+impl_float!(f32, f64);
+
+impl ToLua for bool {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        lua_pushboolean(lua, self as libc::c_int);
+        1
+    }
+}
+
+impl FromLua for bool {
+    unsafe fn from_lua(lua: *mut lua_State, idx: libc::c_int)
+                       -> Result<Self, LuaErr> {
+        if lua_type(lua, idx) != LUA_TBOOLEAN as i32 {
+            return Err(type_error(lua, idx, "boolean"))
+        }
+        Ok(lua_toboolean(lua, idx) != 0)
+    }
+}
+
+impl<'a> ToLua for &'a str {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        // Lua copies the bytes, so there is no need to keep a CString around.
+        lua_pushlstring(lua, self.as_ptr() as _, self.len());
+        1
+    }
+}
+
+impl ToLua for String {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        self.as_str().push_to_lua(lua)
+    }
+}
+
+impl<'a> ToLua for &'a String {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        self.as_str().push_to_lua(lua)
+    }
+}
+
+impl FromLua for String {
+    unsafe fn from_lua(lua: *mut lua_State, idx: libc::c_int)
+                       -> Result<Self, LuaErr> {
+        // Numbers are accepted too, like luaL_checkstring does.
+        let lua_t = lua_type(lua, idx);
+        if lua_t != LUA_TSTRING as i32 && lua_t != LUA_TNUMBER as i32 {
+            return Err(type_error(lua, idx, "string"))
+        }
+        let mut len = 0;
+        let ptr = lua_tolstring(lua, idx, &mut len);
+        let bytes = ::std::slice::from_raw_parts(ptr as *const u8, len);
+        String::from_utf8(bytes.to_vec())
+            .map_err(|err| LuaErr::EvalFFI(FFIErr::InvalidUTF(
+                String::from_utf8_lossy(err.as_bytes()).into_owned())))
+    }
+}
+
+impl ToLua for area_t {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        luaA::pusharea(lua, self)
+    }
+}
+
+impl FromLua for area_t {
+    unsafe fn from_lua(lua: *mut lua_State, idx: libc::c_int)
+                       -> Result<Self, LuaErr> {
+        if lua_type(lua, idx) != LUA_TTABLE as i32 {
+            return Err(type_error(lua, idx, "table"))
+        }
+        let idx = luaA::absindex(lua, idx);
+        Ok(area_t {
+            x: area_field(lua, idx, c_str!("x"))?,
+            y: area_field(lua, idx, c_str!("y"))?,
+            width: area_field(lua, idx, c_str!("width"))?,
+            height: area_field(lua, idx, c_str!("height"))?
+        })
+    }
+}
+
+/// Reads a field of an area, checking that it fits.
+/// Missing fields default to 0, like luaA_getopt_integer.
+unsafe fn area_field<T>(lua: *mut lua_State, idx: libc::c_int,
+                        name: *const libc::c_char) -> Result<T, LuaErr>
+    where T: FromLua + Default
+{
+    lua_getfield(lua, idx, name);
+    let value = Option::<T>::from_lua(lua, -1);
+    lua_pop(lua, 1);
+    value.map(|value| value.unwrap_or_default())
+}
+
+/// A mask of key modifiers, e.g `{"Mod4", "Shift"}` in Lua.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Modifiers(pub u16);
+
+impl ToLua for Modifiers {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        luaA::pushmodifiers(lua, self.0)
+    }
+}
+
+impl FromLua for Modifiers {
+    unsafe fn from_lua(lua: *mut lua_State, idx: libc::c_int)
+                       -> Result<Self, LuaErr> {
+        if lua_type(lua, idx) != LUA_TTABLE as i32 {
+            return Err(type_error(lua, idx, "table"))
+        }
+        Ok(Modifiers(luaA::tomodifiers(lua, luaA::absindex(lua, idx))))
+    }
+}
+
+/// An object that lives in the Lua state, e.g the screen of a client.
+///
+/// It is pushed as the object itself, or as `nil` if the pointer is null.
+#[derive(Debug)]
+pub struct ObjectRef<T>(pub *mut T);
+
+impl<T> Clone for ObjectRef<T> {
+    fn clone(&self) -> Self {
+        ObjectRef(self.0)
+    }
+}
+
+impl<T> Copy for ObjectRef<T> {}
+
+impl<T> PartialEq for ObjectRef<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T> ToLua for ObjectRef<T> {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        if self.0.is_null() {
+            lua_pushnil(lua);
+            1
+        } else {
+            luaA::object_push(lua, self.0 as _)
+        }
+    }
+}
+
+impl<T: ToLua> ToLua for Option<T> {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        match self {
+            Some(value) => value.push_to_lua(lua),
+            None => {
+                lua_pushnil(lua);
+                1
+            }
+        }
+    }
+}
+
+impl<T: FromLua> FromLua for Option<T> {
+    unsafe fn from_lua(lua: *mut lua_State, idx: libc::c_int)
+                       -> Result<Self, LuaErr> {
+        if lua::lua_isnonornil(lua, idx) {
+            Ok(None)
+        } else {
+            T::from_lua(lua, idx).map(Some)
+        }
+    }
+}
+
+impl<T: ToLua> ToLua for Vec<T> {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        lua_createtable(lua, self.len() as libc::c_int, 0);
+        for (i, value) in self.into_iter().enumerate() {
+            value.push_to_lua(lua);
+            ::compat::rawseti(lua, -2, (i + 1) as lua_Integer);
+        }
+        1
+    }
+}
+
+impl<T: FromLua> FromLua for Vec<T> {
+    unsafe fn from_lua(lua: *mut lua_State, idx: libc::c_int)
+                       -> Result<Self, LuaErr> {
+        if lua_type(lua, idx) != LUA_TTABLE as i32 {
+            return Err(type_error(lua, idx, "table"))
+        }
+        let idx = luaA::absindex(lua, idx);
+        let len = luaA::rawlen(lua, idx);
+        let mut result = Vec::with_capacity(len);
+        for i in 1..(len + 1) {
+            ::compat::rawgeti(lua, idx, i as lua_Integer);
+            let value = T::from_lua(lua, -1);
+            lua_pop(lua, 1);
+            result.push(value?);
+        }
+        Ok(result)
+    }
+}
+
+impl<K, V> ToLua for HashMap<K, V>
+    where K: ToLua + Eq + Hash, V: ToLua
+{
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        lua_createtable(lua, 0, self.len() as libc::c_int);
+        for (key, value) in self {
+            key.push_to_lua(lua);
+            value.push_to_lua(lua);
+            lua_rawset(lua, -3);
+        }
+        1
+    }
+}
+
+impl<V: FromLua> FromLua for HashMap<String, V> {
+    unsafe fn from_lua(lua: *mut lua_State, idx: libc::c_int)
+                       -> Result<Self, LuaErr> {
+        if lua_type(lua, idx) != LUA_TTABLE as i32 {
+            return Err(type_error(lua, idx, "table"))
+        }
+        let idx = luaA::absindex(lua, idx);
+        let mut result = HashMap::new();
+        lua_pushnil(lua);
+        while lua_next(lua, idx) != 0 {
+            /* Only string keys are considered. Calling tolstring on a number
+             * key would convert it in place and confuse lua_next() */
+            if lua_type(lua, -2) != LUA_TSTRING as i32 {
+                lua_pop(lua, 1);
+                continue
+            }
+            let entry = String::from_lua(lua, -2)
+                .and_then(|key| V::from_lua(lua, -1).map(|value| (key, value)));
+            match entry {
+                Ok((key, value)) => {
+                    result.insert(key, value);
+                    lua_pop(lua, 1);
+                },
+                Err(err) => {
+                    /* Pop the key and value */
+                    lua_pop(lua, 2);
+                    return Err(err)
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl ToLua for StackFrame {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        lua_createtable(lua, 0, 4);
+        self.source.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("source"));
+        self.line.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("line"));
+        self.name.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("name"));
+        self.what.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("what"));
+        1
+    }
+}
+
+impl FromLua for StackFrame {
+    unsafe fn from_lua(lua: *mut lua_State, idx: libc::c_int)
+                       -> Result<Self, LuaErr> {
+        if lua_type(lua, idx) != LUA_TTABLE as i32 {
+            return Err(type_error(lua, idx, "table"))
+        }
+        Ok(StackFrame {
+            source: luaA::getfield(lua, idx, c_str!("source"))?,
+            line: luaA::getfield(lua, idx, c_str!("line"))?,
+            name: luaA::getfield(lua, idx, c_str!("name"))?,
+            what: luaA::getfield(lua, idx, c_str!("what"))?
+        })
+    }
+}
+
+/// Values that can be pushed as the arguments of a function or signal,
+/// e.g `()` for none, a single value or a tuple of values.
+pub trait ToLuaMulti {
+    /// Pushes the values on to the stack, returning how many were pushed.
+    unsafe fn push_to_lua_multi(self, lua: *mut lua_State) -> libc::c_int;
+}
+
+impl ToLuaMulti for () {
+    unsafe fn push_to_lua_multi(self, _lua: *mut lua_State) -> libc::c_int {
+        0
+    }
+}
+
+impl<T: ToLua> ToLuaMulti for T {
+    unsafe fn push_to_lua_multi(self, lua: *mut lua_State) -> libc::c_int {
+        self.push_to_lua(lua)
+    }
+}
+
+macro_rules! impl_to_lua_multi_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: ToLua),+> ToLuaMulti for ($($name,)+) {
+            #[allow(non_snake_case)]
+            unsafe fn push_to_lua_multi(self, lua: *mut lua_State) -> libc::c_int {
+                let ($($name,)+) = self;
+                0 $(+ $name.push_to_lua(lua))+
+            }
+        }
+    }
+}
+
+impl_to_lua_multi_tuple!(A);
+impl_to_lua_multi_tuple!(A, B);
+impl_to_lua_multi_tuple!(A, B, C);
+impl_to_lua_multi_tuple!(A, B, C, D);
+impl_to_lua_multi_tuple!(A, B, C, D, E);
+
+/// Values that can be read from the results of a chunk or function call,
+/// e.g `()` to ignore them, a single value or a tuple of values.
+pub trait FromLuaMulti: Sized {
+    /// Reads the `nvalues` values at the top of the stack.
+    ///
+    /// Missing values are read as `nil`. The stack is left untouched.
+    unsafe fn from_lua_multi(lua: *mut lua_State, nvalues: libc::c_int)
+                             -> Result<Self, LuaErr>;
+}
+
+/// Reads the `n`th (starting from 0) of the `nvalues` values at the top of
+/// the stack, treating missing values as `nil`.
+unsafe fn from_lua_nth<T: FromLua>(lua: *mut lua_State, nvalues: libc::c_int,
+                                   n: libc::c_int) -> Result<T, LuaErr> {
+    if n < nvalues {
+        T::from_lua(lua, - nvalues + n)
+    } else {
+        lua_pushnil(lua);
+        let value = T::from_lua(lua, -1);
+        lua_pop(lua, 1);
+        value
+    }
+}
+
+impl FromLuaMulti for () {
+    unsafe fn from_lua_multi(_lua: *mut lua_State, _nvalues: libc::c_int)
+                             -> Result<Self, LuaErr> {
+        Ok(())
+    }
+}
+
+impl<T: FromLua> FromLuaMulti for T {
+    unsafe fn from_lua_multi(lua: *mut lua_State, nvalues: libc::c_int)
+                             -> Result<Self, LuaErr> {
+        from_lua_nth(lua, nvalues, 0)
+    }
+}
+
+macro_rules! impl_from_lua_multi_tuple {
+    ($($name:ident: $n:expr),+) => {
+        impl<$($name: FromLua),+> FromLuaMulti for ($($name,)+) {
+            unsafe fn from_lua_multi(lua: *mut lua_State, nvalues: libc::c_int)
+                                     -> Result<Self, LuaErr> {
+                Ok(($(from_lua_nth::<$name>(lua, nvalues, $n)?,)+))
+            }
+        }
+    }
+}
+
+impl_from_lua_multi_tuple!(A: 0, B: 1);
+impl_from_lua_multi_tuple!(A: 0, B: 1, C: 2);
+impl_from_lua_multi_tuple!(A: 0, B: 1, C: 2, D: 3);
+impl_from_lua_multi_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4);
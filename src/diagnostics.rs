@@ -0,0 +1,226 @@
+//! Diagnostics of the object registry, to track down leaks.
+//!
+//! Values that must outlive the Lua stack, e.g the functions connected to
+//! class and global signals, are referenced in the
+//! `awesome.object.registry` table with a reference count. A leak shows up
+//! as a reference that is never dropped, or as a class whose number of
+//! instances keeps growing.
+//!
+//! From Lua the same report is available with `awesome.diagnostics()`.
+
+use lua_sys::*;
+use libc;
+use std::ffi::CStr;
+use std::fmt;
+use ::convert::ToLua;
+use ::lua::{Lua, luaA};
+use ::state;
+
+/// A misuse of the object registry, or of the table of an object holding
+/// its signal handlers.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RegistryError {
+    /// A reference to the value with the given pointer was dropped, but it
+    /// was not referenced, e.g it was already dropped.
+    ReferenceNotFound(usize)
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RegistryError::ReferenceNotFound(pointer) =>
+                write!(f, "reference to {:#x} not found", pointer)
+        }
+    }
+}
+
+/// A signal of a class, or of `awesome`, that holds a reference.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SignalHolder {
+    /// Name of the class, or `awesome` for the global signals
+    pub owner: String,
+    /// Name of the signal
+    pub signal: String
+}
+
+/// A value referenced in the object registry.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Reference {
+    pub pointer: usize,
+    /// Name of the class of the object, or the Lua type of the value if
+    /// it is not an object, e.g `function`
+    pub class: String,
+    /// How many times it is referenced
+    pub refcount: i64,
+    /// The signals it is connected to
+    pub signals: Vec<SignalHolder>
+}
+
+/// How many objects of a class are alive.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ClassInstances {
+    pub class: String,
+    /// Objects that have not been garbage collected
+    pub instances: i32,
+    /// Of those, the ones referenced in the object registry
+    pub referenced: usize
+}
+
+/// Lists every value referenced in the object registry.
+pub fn references(lua: &Lua) -> Vec<Reference> {
+    let lua = lua.0;
+    let mut references = Vec::new();
+    unsafe {
+        luaA::object_registry_push(lua);
+        let registry = lua_gettop(lua);
+        lua_getmetatable(lua, registry);
+        let counts = lua_gettop(lua);
+        lua_pushnil(lua);
+        while lua_next(lua, registry) != 0 {
+            let pointer = lua_touserdata(lua, -2);
+            let class = luaA::class_get(lua, -1);
+            let class = if class.is_null() {
+                let name = lua_typename(lua, lua_type(lua, -1));
+                CStr::from_ptr(name).to_string_lossy().into_owned()
+            } else {
+                (*class).name.clone()
+            };
+            lua_pushvalue(lua, -2);
+            lua_rawget(lua, counts);
+            let refcount = lua_tointeger(lua, -1) as i64;
+            /* Pop the reference count and the value */
+            lua_pop(lua, 2);
+            references.push(Reference {
+                pointer: pointer as usize,
+                class,
+                refcount,
+                signals: holders(lua, pointer)
+            });
+        }
+        lua_pop(lua, 2);
+    }
+    references
+}
+
+/// The signals of the classes and of `awesome` holding the value.
+unsafe fn holders(lua: *mut lua_State, pointer: *const libc::c_void)
+                  -> Vec<SignalHolder> {
+    let state = state::get(lua);
+    let mut holders = Vec::new();
+    for class in state.classes.lock().unwrap().iter() {
+        let class = class.class();
+        for signal in (*class).signals.holding(pointer) {
+            holders.push(SignalHolder { owner: (*class).name.clone(), signal });
+        }
+    }
+    /* Skipped if a global signal is being connected or disconnected */
+    if let Ok(signals) = state.signals.try_lock() {
+        for signal in signals.holding(pointer) {
+            holders.push(SignalHolder { owner: "awesome".into(), signal });
+        }
+    }
+    holders
+}
+
+/// Lists how many objects of each class are alive, in the order the
+/// classes were set up.
+pub fn instances(lua: &Lua) -> Vec<ClassInstances> {
+    let references = references(lua);
+    unsafe {
+        state::get(lua.0).classes.lock().unwrap().iter()
+            .map(|class| {
+                let class = class.class();
+                ClassInstances {
+                    class: (*class).name.clone(),
+                    instances: (*class).instances.get(),
+                    referenced: references.iter()
+                        .filter(|reference| reference.class == (*class).name)
+                        .count()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Records the error, to be listed by [errors](fn.errors.html).
+pub unsafe fn report(lua: *mut lua_State, err: RegistryError) {
+    state::get(lua).registry_errors.lock().unwrap().push(err);
+}
+
+/// Lists the misuses of the object registry since the last call to
+/// [take_errors](fn.take_errors.html).
+pub fn errors(lua: &Lua) -> Vec<RegistryError> {
+    unsafe {
+        state::get(lua.0).registry_errors.lock().unwrap().clone()
+    }
+}
+
+/// Like [errors](fn.errors.html), but clears the list.
+pub fn take_errors(lua: &Lua) -> Vec<RegistryError> {
+    unsafe {
+        let mut errors = state::get(lua.0).registry_errors.lock().unwrap();
+        ::std::mem::replace(&mut *errors, Vec::new())
+    }
+}
+
+impl ToLua for SignalHolder {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        lua_newtable(lua);
+        self.owner.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("owner"));
+        self.signal.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("signal"));
+        1
+    }
+}
+
+impl ToLua for Reference {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        lua_newtable(lua);
+        format!("{:#x}", self.pointer).push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("pointer"));
+        self.class.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("class"));
+        self.refcount.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("refcount"));
+        self.signals.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("signals"));
+        1
+    }
+}
+
+impl ToLua for ClassInstances {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        lua_newtable(lua);
+        self.class.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("class"));
+        self.instances.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("instances"));
+        self.referenced.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("referenced"));
+        1
+    }
+}
+
+impl ToLua for RegistryError {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        self.to_string().push_to_lua(lua)
+    }
+}
+
+/// `awesome.diagnostics()`, a table with the `references` in the object
+/// registry, the `instances` of each class and the `errors` so far.
+pub unsafe fn awesome_diagnostics(lua: *mut lua_State) -> libc::c_int {
+    let lua = Lua(lua);
+    let references = references(&lua);
+    let instances = instances(&lua);
+    let errors = errors(&lua);
+    lua_newtable(lua.0);
+    references.push_to_lua(lua.0);
+    lua_setfield(lua.0, -2, c_str!("references"));
+    instances.push_to_lua(lua.0);
+    lua_setfield(lua.0, -2, c_str!("instances"));
+    errors.push_to_lua(lua.0);
+    lua_setfield(lua.0, -2, c_str!("errors"));
+    1
+}
@@ -0,0 +1,61 @@
+//! The focused client and the history of the focus.
+//! Based off of client_focus() in client.c of the C source.
+//!
+//! `GlobalConf.focus.history` holds every client that had the focus, the
+//! most recent first. The history of a screen is the part of it that is
+//! on that screen, so a client that moves to another screen takes its
+//! place in the history with it.
+
+use ::callbacks::client::ClientState;
+use ::callbacks::screen::ScreenState;
+use ::globalconf::GlobalConf;
+
+/// Whether the client can be given the focus, unless Lua set
+/// `focusable` it is whether the client accepts the focus.
+pub fn client_focusable(client: &ClientState) -> bool {
+    if client.focusable_set {
+        client.focusable
+    } else {
+        !client.nofocus
+    }
+}
+
+/// Gives the focus to the client, or takes it away if it is null.
+///
+/// Returns the client that had the focus before, `None` if the focus did
+/// not change, e.g because the client can't be focused.
+pub unsafe fn client_focus(conf: &mut GlobalConf, client: *mut ClientState)
+                           -> Option<*mut ClientState> {
+    let old = conf.focus.client;
+    if old == client || (!client.is_null() && !client_focusable(&*client)) {
+        return None
+    }
+    conf.focus.client = client;
+    conf.focus.need_update = true;
+    if !client.is_null() {
+        conf.focus.history.retain(|&c| c != client);
+        conf.focus.history.insert(0, client);
+    }
+    Some(old)
+}
+
+/// Forgets the client, e.g because it is unmanaged, taking the focus
+/// away from it without telling anyone.
+pub fn focus_history_remove(conf: &mut GlobalConf, client: *mut ClientState) {
+    conf.focus.history.retain(|&c| c != client);
+    if conf.focus.client == client {
+        conf.focus.client = ::std::ptr::null_mut();
+        conf.focus.need_update = true;
+    }
+}
+
+/// The clients of the screen that had the focus, the most recent first.
+///
+/// If the screen is `None` it's the history of every screen.
+pub unsafe fn focus_history(conf: &GlobalConf, screen: Option<*mut ScreenState>)
+                            -> Vec<*mut ClientState> {
+    conf.focus.history.iter()
+        .filter(|&&client| screen.map_or(true, |s| (*client).screen == s as _))
+        .cloned()
+        .collect()
+}
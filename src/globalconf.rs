@@ -2,7 +2,8 @@
 //! Based off of globalconf.h in the C source.
 
 use libc::c_void;
-use std::sync::Mutex;
+use std::ptr;
+use std::path::PathBuf;
 use std::default::Default;
 use ::callbacks::button::ButtonState;
 use ::callbacks::client::ClientState;
@@ -37,7 +38,7 @@ pub struct GlobalConf {
     /// Keys symbol table
     pub keysyms: *mut xcb_key_symbols_t,
     /// Logical screens
-    pub screens: Vec<ScreenState>,
+    pub screens: Vec<*mut ScreenState>,
     /// The primary screen, access through screen_get_primary()
     pub primary_screen: *mut ScreenState,
     /// Root window key bindings
@@ -66,13 +67,15 @@ pub struct GlobalConf {
     pub event_base_xkb: u8,
     pub event_base_randr: u8,
     /// Clients list
-    pub clients: Vec<ClientState>,
+    pub clients: Vec<*mut ClientState>,
     /// Embedded windows
     pub embedded: Vec<XembedWindow>,
     /// Stack client history
-    pub stack: Vec<ClientState>,
+    pub stack: Vec<*mut ClientState>,
     /// All errors messages from loading config files
     pub startup_errors: Vec<String>,
+    /// The configuration file that was loaded
+    pub conffile: Option<PathBuf>,
     /// main loop that awesome is running on
     pub g_loop: *mut GMainLoop,
     /// The key grabber function
@@ -83,7 +86,7 @@ pub struct GlobalConf {
     pub drawable_under_mouse: *mut DrawableState,
     pub focus: Focus,
     /// Drawins
-    pub drawins: Vec<DrawinState>,
+    pub drawins: Vec<*mut DrawinState>,
     /// The startup notification display struct
     pub sndisplay: *mut SnDisplay,
     /// Latest timestamp we got from the X server
@@ -106,7 +109,7 @@ pub struct GlobalConf {
     /// Do we have to reban clients?
     pub need_lazy_banning: bool,
     /// Tag list
-    pub tags: Vec<TagState>,
+    pub tags: Vec<*mut TagState>,
     /// List of registered xproperties
     pub xproperties: Vec<xcb_property_t>,
     /// xkb context
@@ -131,7 +134,9 @@ pub struct GlobalConf {
     /// Pending event that still needs to be handled
     pub pending_event: *mut xcb_generic_event_t,
     /// The exit code that main() will return with
-    pub exit_code: i32
+    pub exit_code: i32,
+    /// Has a reload of the configuration been requested?
+    pub need_reload: bool
 }
 
 impl Default for GlobalConf {
@@ -167,6 +172,7 @@ impl Default for GlobalConf {
                 embedded: Vec::new(),
                 stack: Vec::new(),
                 startup_errors: Vec::new(),
+                conffile: None,
                 g_loop:  NULL as _,
                 keygrabber: 0,
                 mousegrabber: 0,
@@ -197,19 +203,46 @@ impl Default for GlobalConf {
                 pending_enter_leave_begin: xcb_grab_server(connection),
                 destroy_later_windows: Vec::new(),
                 pending_event: NULL as _,
-                exit_code: 0
+                exit_code: 0,
+                need_reload: false
             }
         }
     }
 }
 
+impl GlobalConf {
+    /// Forgets the object, e.g because it was invalidated, so that nothing
+    /// points to it once it is collected.
+    ///
+    /// It is removed from every list, and the fields and clients that
+    /// point to it are reset.
+    pub unsafe fn remove_object(&mut self, object: *mut c_void) {
+        self.clients.retain(|&client| client as *mut c_void != object);
+        ::stack::stack_client_remove(self, object as _);
+        ::focus::focus_history_remove(self, object as _);
+        for &client in &self.clients {
+            if (*client).transient_for as *mut c_void == object {
+                (*client).transient_for = ptr::null_mut();
+            }
+            if (*client).screen == object {
+                (*client).screen = ptr::null_mut();
+            }
+        }
+        self.screens.retain(|&screen| screen as *mut c_void != object);
+        if self.primary_screen as *mut c_void == object {
+            self.primary_screen = ptr::null_mut();
+        }
+        self.tags.retain(|&tag| tag as *mut c_void != object);
+        self.drawins.retain(|&drawin| drawin as *mut c_void != object);
+        if self.drawable_under_mouse as *mut c_void == object {
+            self.drawable_under_mouse = ptr::null_mut();
+        }
+    }
+}
+
 unsafe impl Send for GlobalConf {}
 unsafe impl Sync for GlobalConf {}
 
-lazy_static! {
-    pub static ref GLOBAL_CONF: Mutex<GlobalConf> = Mutex::new(GlobalConf::default());
-}
-
 #[repr(C)]
 pub struct Focus {
     /// Focused client
@@ -217,7 +250,9 @@ pub struct Focus {
     /// Is there a focuse change pending?
     pub need_update: bool,
     /// When nothing has the input focus, this window actually is focused.
-    pub window_no_focus: xcb_window_t
+    pub window_no_focus: xcb_window_t,
+    /// Clients that had the focus, the most recent first
+    pub history: Vec<*mut ClientState>
 }
 
 impl Default for Focus {
@@ -225,7 +260,8 @@ impl Default for Focus {
         Focus {
             client: ::std::ptr::null_mut(),
             need_update: false,
-            window_no_focus: 0
+            window_no_focus: 0,
+            history: Vec::new()
         }
     }
 }
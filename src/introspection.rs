@@ -0,0 +1,219 @@
+//! Introspection of the classes set up in a Lua state.
+//!
+//! From Lua the same information is available with `awesome.classes()`,
+//! which returns a table of every class, and `awesome.class_info(x)`, where
+//! `x` is the name of a class or one of its objects. The signals connected
+//! on an object, or on `awesome` itself, are listed by `awesome.signals(x)`.
+//!
+//! Classes are read under their lock, so none of this can be used while a
+//! class is being changed, e.g from the setup of a class.
+
+use lua_sys::*;
+use libc;
+use std::collections::{HashMap, LinkedList};
+use ::convert::ToLua;
+use ::lua::{Lua, luaA};
+use ::lua::luaA::ClassWrapper;
+use ::object::class::{Class, Object};
+use ::object::signal::Signals;
+use ::state;
+
+/// A property of a class.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PropertyInfo {
+    pub name: String,
+    /// Can it be read from Lua?
+    pub readable: bool,
+    /// Can it be assigned to from Lua?
+    pub writable: bool
+}
+
+/// A signal with functions connected to it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SignalInfo {
+    pub name: String,
+    /// How many functions are connected
+    pub handlers: usize
+}
+
+impl SignalInfo {
+    /// Lists the signals with functions connected to them, sorted by name.
+    pub fn from_signals(signals: &Signals) -> Vec<Self> {
+        signals.counts().into_iter()
+            .map(|(name, handlers)| SignalInfo { name, handlers })
+            .collect()
+    }
+}
+
+/// Everything that is known about a class.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ClassInfo {
+    pub name: String,
+    /// The names of the parent classes, closest first
+    pub parents: Vec<String>,
+    /// The properties of the class, not including those of its parents
+    pub properties: Vec<PropertyInfo>,
+    /// The signals declared with `add_signal`, sorted by name
+    pub signals: Vec<String>,
+    /// The signals connected to on the class
+    pub connected: Vec<SignalInfo>,
+    /// How many objects of the class are alive
+    pub instances: i32
+}
+
+/// Reads the information of the class, under its lock and the locks of its
+/// parents.
+///
+/// # Panics
+/// If one of them is locked for writing.
+unsafe fn class_info(classes: &LinkedList<ClassWrapper>, class: &ClassWrapper)
+                     -> ClassInfo {
+    let class = (*class.lock()).try_read().unwrap();
+    let mut parents = Vec::new();
+    let mut parent = class.parent as *const Class;
+    while let Some(wrapper) = find_class(classes, parent) {
+        let parent_class = (*wrapper.lock()).try_read().unwrap();
+        parents.push(parent_class.name.clone());
+        parent = parent_class.parent;
+    }
+    let properties = class.properties.iter()
+        .map(|prop| PropertyInfo {
+            name: prop.name.clone(),
+            readable: prop.getter.is_some() || prop.index.is_some(),
+            writable: prop.is_writable()
+        })
+        .collect();
+    ClassInfo {
+        name: class.name.clone(),
+        parents,
+        properties,
+        signals: class.declared_signals.clone(),
+        connected: SignalInfo::from_signals(&class.signals),
+        instances: class.instances.get()
+    }
+}
+
+fn find_class(classes: &LinkedList<ClassWrapper>, class: *const Class)
+              -> Option<&ClassWrapper> {
+    classes.iter().find(|wrapper| wrapper.class() as *const Class == class)
+}
+
+/// Lists all of the classes set up in the Lua state, in the order they were
+/// set up.
+pub fn classes(lua: &Lua) -> Vec<ClassInfo> {
+    unsafe {
+        let classes = state::get(lua.0).classes.lock().unwrap();
+        classes.iter()
+            .map(|class| class_info(&classes, class))
+            .collect()
+    }
+}
+
+/// Gets the class with the given name.
+pub fn class(lua: &Lua, name: &str) -> Option<ClassInfo> {
+    classes(lua).into_iter().find(|class| class.name == name)
+}
+
+/// Gets the class of the object at the given stack index.
+pub fn class_of(lua: &Lua, idx: libc::c_int) -> Option<ClassInfo> {
+    unsafe {
+        let class = luaA::class_get(lua.0, idx);
+        let classes = state::get(lua.0).classes.lock().unwrap();
+        find_class(&classes, class).map(|class| class_info(&classes, class))
+    }
+}
+
+/// Gets the signals connected on the object at the given stack index.
+pub fn object_signals(lua: &Lua, idx: libc::c_int) -> Option<Vec<SignalInfo>> {
+    unsafe {
+        let class = luaA::class_get(lua.0, idx);
+        if class.is_null() {
+            return None
+        }
+        let object = lua_touserdata(lua.0, idx) as *mut Object;
+        (*object).signals.prune(lua.0);
+        Some(SignalInfo::from_signals(&(*object).signals))
+    }
+}
+
+/// Gets the signals connected on the `awesome` global.
+pub fn global_signals(lua: &Lua) -> Vec<SignalInfo> {
+    unsafe {
+        let mut signals = state::get(lua.0).signals.lock().unwrap();
+        signals.prune(lua.0);
+        SignalInfo::from_signals(&*signals)
+    }
+}
+
+impl ToLua for SignalInfo {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        lua_newtable(lua);
+        self.name.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("name"));
+        self.handlers.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("handlers"));
+        1
+    }
+}
+
+impl ToLua for PropertyInfo {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        lua_newtable(lua);
+        self.name.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("name"));
+        self.readable.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("readable"));
+        self.writable.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("writable"));
+        1
+    }
+}
+
+impl ToLua for ClassInfo {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        lua_newtable(lua);
+        self.name.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("name"));
+        self.parents.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("parents"));
+        self.properties.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("properties"));
+        self.signals.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("signals"));
+        self.connected.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("connected"));
+        self.instances.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("instances"));
+        1
+    }
+}
+
+/// `awesome.classes()`, a table of the classes by name.
+pub unsafe fn awesome_classes(lua: *mut lua_State) -> libc::c_int {
+    let classes: HashMap<String, ClassInfo> = classes(&Lua(lua)).into_iter()
+        .map(|class| (class.name.clone(), class))
+        .collect();
+    classes.push_to_lua(lua)
+}
+
+/// `awesome.class_info(x)`, the class named `x` or of the object `x`,
+/// or nil.
+pub unsafe fn awesome_class_info(lua: *mut lua_State) -> libc::c_int {
+    let lua = Lua(lua);
+    let info = if lua_type(lua.0, 1) == LUA_TSTRING as i32 {
+        lua.get::<String>(1).ok().and_then(|name| class(&lua, &name))
+    } else {
+        class_of(&lua, 1)
+    };
+    info.push_to_lua(lua.0)
+}
+
+/// `awesome.signals([object])`, the signals connected on the object, or on
+/// `awesome` when called without one.
+pub unsafe fn awesome_signals(lua: *mut lua_State) -> libc::c_int {
+    let lua = Lua(lua);
+    if lua_type(lua.0, 1) <= LUA_TNIL as i32 {
+        return global_signals(&lua).push_to_lua(lua.0)
+    }
+    object_signals(&lua, 1).push_to_lua(lua.0)
+}
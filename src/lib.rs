@@ -7,13 +7,12 @@
 
 // Library for handling C types
 extern crate libc;
-// Bindings to the system's Lua
-extern crate lua_sys;
+// Bindings to the system's Lua, when it's Lua 5.3
+#[cfg(feature = "lua53")]
+pub extern crate lua_sys;
 // XCB definitions, this is mostly so that we can use old values as Lua
 // expects them, and to ensure that the right types are defined per arch
 extern crate xcb;
-// So we can have static variables (that are thread safe)
-#[macro_use] extern crate lazy_static;
 // Cairo bindings, letting us draw on the screen
 // and pass surfaces to the compositor
 extern crate cairo;
@@ -29,6 +28,10 @@ extern crate xcb_util_xrm_sys;
 extern crate xcb_cursor_sys;
 // xkbcommon bindings
 extern crate xkbcommon_sys;
+// Serialization of snapshots of the state
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+extern crate serde_json;
 
 // generated cairo bindings, so that we can have the cairo creation using xcb
 #[allow(dead_code)]
@@ -40,14 +43,30 @@ mod cairo_xcb {
 }
 
 #[macro_use] mod utils;
+// Bindings to the other versions of Lua
+#[cfg(not(feature = "lua53"))]
+pub mod lua_sys;
+mod compat;
 mod lua;
+pub mod convert;
+pub mod sandbox;
+pub mod profiler;
+pub mod introspection;
+pub mod diagnostics;
+pub mod snapshot;
+pub mod stack;
+pub mod focus;
 
 pub mod object;
 pub mod awesome;
 pub mod callbacks;
 pub use lua::*;
 pub use utils::*;
+pub use convert::{ToLua, FromLua, FromLuaMulti, ToLuaMulti, ObjectRef};
+pub use sandbox::{Sandbox, SandboxLib};
+pub use object::{LuaClass, ClassProperty};
 pub mod globalconf;
+pub mod state;
 
-pub use globalconf::GLOBAL_CONF;
+pub use globalconf::GlobalConf;
 pub use awesome::Awesome;
@@ -2,9 +2,16 @@
 //! correct use of the Lua stack.
 
 use lua_sys::*;
+use libc;
 use std::path::PathBuf;
-use std::ffi::{CString, CStr};
+use std::ffi::CString;
 use std::ops::{Deref, DerefMut};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::RwLock;
+use convert::{ToLua, FromLua, FromLuaMulti, ToLuaMulti};
+use object::class::Class;
+use sandbox::Sandbox;
 
 
 const ALREADY_DEFINED: i32 = 0;
@@ -17,13 +24,24 @@ pub struct Lua(pub *mut lua_State);
 unsafe impl Send for Lua {}
 unsafe impl Sync for Lua {}
 
+/// A Rust function that can be called from Lua.
+///
+/// Like a `lua_CFunction`, it returns the number of values it pushed.
+/// An error, or a panic, is raised as a Lua error.
+pub type RustFn = Box<Fn(&Lua) -> Result<libc::c_int, LuaErr>>;
+
 /// Errors while interacting with Lua
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum LuaErr {
     /// There was an error loading the configuration file
     Load(FFIErr),
-    /// Evaluation error from Lua
-    Eval(String),
+    /// The chunk could not be compiled.
+    /// Contains the error message from Lua.
+    Syntax(String),
+    /// Evaluation error from Lua.
+    /// Contains the error message and the stack at the time of the error,
+    /// innermost frame first.
+    Eval(String, Vec<StackFrame>),
     /// There was an FFI error during evalution
     EvalFFI(FFIErr),
     /// There was an error loading in arguments from the Lua call
@@ -32,7 +50,81 @@ pub enum LuaErr {
     AlreadyDefined(String),
     /// Could not find configuration file with the given path.
     /// Reason given from Lua as a string.
-    FileNotFound(PathBuf, String)
+    FileNotFound(PathBuf, String),
+    /// A value had the wrong type.
+    /// Contains the expected type and the type that was found.
+    Type(String, String),
+    /// A number did not fit in the requested Rust type.
+    OutOfRange(String),
+    /// The object is not referenced in the object registry of the state,
+    /// so it can't be pushed.
+    ObjectNotFound
+}
+
+impl fmt::Display for LuaErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LuaErr::Load(ref err) | LuaErr::EvalFFI(ref err) =>
+                write!(f, "{}", err),
+            LuaErr::Syntax(ref message) => write!(f, "{}", message),
+            LuaErr::Eval(ref message, ref frames) => {
+                write!(f, "{}", message)?;
+                if !frames.is_empty() {
+                    write!(f, "\n{}", format_traceback(frames))?;
+                }
+                Ok(())
+            },
+            LuaErr::ArgumentInvalid => write!(f, "invalid argument"),
+            LuaErr::AlreadyDefined(ref name) =>
+                write!(f, "{} is already defined", name),
+            LuaErr::FileNotFound(_, ref reason) => write!(f, "{}", reason),
+            LuaErr::Type(ref expected, ref got) =>
+                write!(f, "{} expected, got {}", expected, got),
+            LuaErr::OutOfRange(ref number) =>
+                write!(f, "number {} is out of range", number),
+            LuaErr::ObjectNotFound =>
+                write!(f, "object is not referenced in the Lua state")
+        }
+    }
+}
+
+/// A frame of a Lua stack traceback, e.g the location of a function call
+/// at the time of an error.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StackFrame {
+    /// Short name of the source of the chunk, e.g the file name
+    pub source: String,
+    /// Line that was executing, if known
+    pub line: Option<i32>,
+    /// Name of the function, if known
+    pub name: Option<String>,
+    /// What kind of function this is: "Lua", "C", "main" or "tail"
+    pub what: String
+}
+
+impl fmt::Display for StackFrame {
+    /// Formats the frame the same way `debug.traceback` does.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:", self.source)?;
+        if let Some(line) = self.line {
+            write!(f, "{}:", line)?;
+        }
+        match (self.name.as_ref(), self.what.as_str()) {
+            (Some(name), _) => write!(f, " in function '{}'", name),
+            (None, "main") => write!(f, " in main chunk"),
+            (None, "C") => write!(f, " in ?"),
+            (None, _) => write!(f, " in function <{}>", self.source)
+        }
+    }
+}
+
+/// Formats the frames as a `debug.traceback` style string.
+pub fn format_traceback(frames: &[StackFrame]) -> String {
+    let mut traceback = String::from("stack traceback:");
+    for frame in frames {
+        traceback.push_str(&format!("\n\t{}", frame));
+    }
+    traceback
 }
 
 /// Errors while interfacing with C
@@ -46,6 +138,19 @@ pub enum FFIErr {
     Conversion(CString)
 }
 
+impl fmt::Display for FFIErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FFIErr::InvalidUTF(ref string) =>
+                write!(f, "{} is not valid UTF-8", string),
+            FFIErr::NullByte(ref string) =>
+                write!(f, "{} contains a null byte", string),
+            FFIErr::Conversion(ref string) =>
+                write!(f, "could not convert {:?}", string)
+        }
+    }
+}
+
 impl Lua {
     pub fn new() -> Self {
         unsafe {
@@ -54,6 +159,7 @@ impl Lua {
                 panic!("luaL_newstate returned NULL");
             }
             luaL_openlibs(lua);
+            ::state::setup(lua);
             luaA::object_setup(lua);
             Lua(lua)
         }
@@ -69,56 +175,324 @@ impl Lua {
     }
 
     /// Loads and runs the Lua file that the path points to.
+    ///
+    /// Any values returned by the file are discarded, use
+    /// [eval_file](#method.eval_file) to get them.
     pub fn load_and_run(&self, path: PathBuf) -> Result<(), LuaErr> {
+        self.eval_file(path)
+    }
+
+    /// Evaluates the chunk of Lua code and returns the values it produced.
+    ///
+    /// Errors refer to the chunk by its source, use
+    /// [eval_named](#method.eval_named) to give it a better name.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let count: i32 = lua.eval("return #client.get()")?;
+    /// ```
+    pub fn eval<R: FromLuaMulti>(&self, code: &str) -> Result<R, LuaErr> {
+        self.eval_named(code, code)
+    }
+
+    /// Evaluates the chunk of Lua code with the given chunk name and returns
+    /// the values it produced.
+    ///
+    /// The name follows the Lua conventions: if it starts with a `=` or `@`
+    /// it is used (almost) as is in error messages, e.g `"=my-widget"`.
+    pub fn eval_named<R: FromLuaMulti>(&self, code: &str, name: &str)
+                                       -> Result<R, LuaErr> {
+        unsafe {
+            self.load_buffer(code, name)?;
+            self.call_loaded(LUA_MULTRET)
+                .and_then(|nresults| self.take_results(nresults))
+        }
+    }
+
+    /// Evaluates the Lua file that the path points to and returns the values
+    /// it produced.
+    pub fn eval_file<R: FromLuaMulti>(&self, path: PathBuf) -> Result<R, LuaErr> {
+        unsafe {
+            self.load_file(&path)?;
+            self.call_loaded(LUA_MULTRET)
+                .and_then(|nresults| self.take_results(nresults))
+        }
+    }
+
+    /// Evaluates the chunk of Lua code in the sandbox and returns the values
+    /// it produced.
+    ///
+    /// See [eval_named](#method.eval_named) for the meaning of `name`.
+    pub fn eval_sandboxed<R: FromLuaMulti>(&self, code: &str, name: &str,
+                                           sandbox: &Sandbox)
+                                           -> Result<R, LuaErr> {
+        unsafe {
+            self.load_buffer(code, name)?;
+            sandbox.set_env(self.0, -1);
+            self.call_loaded(LUA_MULTRET)
+                .and_then(|nresults| self.take_results(nresults))
+        }
+    }
+
+    /// Evaluates the Lua file that the path points to in the sandbox and
+    /// returns the values it produced.
+    pub fn eval_file_sandboxed<R: FromLuaMulti>(&self, path: PathBuf,
+                                                sandbox: &Sandbox)
+                                                -> Result<R, LuaErr> {
+        unsafe {
+            self.load_file(&path)?;
+            sandbox.set_env(self.0, -1);
+            self.call_loaded(LUA_MULTRET)
+                .and_then(|nresults| self.take_results(nresults))
+        }
+    }
+
+    /// Compiles the chunk and pushes it on to the stack.
+    unsafe fn load_buffer(&self, code: &str, name: &str) -> Result<(), LuaErr> {
+        let c_name = CString::new(name)
+            .map_err(|_| LuaErr::Load(FFIErr::NullByte(name.into())))?;
+        let status = ::compat::loadbuffer(self.0, code, c_name.as_ptr());
+        if status != 0 {
+            return Err(LuaErr::Syntax(self.pop_error()))
+        }
+        Ok(())
+    }
+
+    /// Compiles the file and pushes it on to the stack.
+    unsafe fn load_file(&self, path: &PathBuf) -> Result<(), LuaErr> {
         let path_str = path.to_str()
             .ok_or_else(||
                         LuaErr::Load(FFIErr::InvalidUTF(format!("{:?}", path))))
             .and_then(|s| CString::new(s)
                       .map_err(|_|
                                LuaErr::Load(FFIErr::NullByte(format!("{:?}", path)))))?;
+        let status = ::compat::loadfile(self.0, path_str.as_ptr());
+        if status == LUA_ERRSYNTAX as i32 {
+            return Err(LuaErr::Syntax(self.pop_error()))
+        } else if status != 0 {
+            return Err(LuaErr::FileNotFound(path.clone(), self.pop_error()))
+        }
+        Ok(())
+    }
+
+    /// Calls the chunk on the top of the stack.
+    ///
+    /// On success returns the number of results left on the stack.
+    unsafe fn call_loaded(&self, nresults: i32) -> Result<i32, LuaErr> {
+        let lua = self.0;
+        /* Move the traceback handler before the chunk */
+        lua_pushcfunction(lua, Some(luaA::error_traceback));
+        lua_insert(lua, -2);
+        let error_func_pos = lua_gettop(lua) - 1;
+        let status = ::compat::pcall(lua, 0, nresults, error_func_pos);
+        if status != 0 {
+            let error = if lua_type(lua, -1) == LUA_TTABLE as i32 {
+                let message = luaA::getfield::<String>(lua, -1, c_str!("message"));
+                let frames = luaA::getfield::<Vec<StackFrame>>(lua, -1,
+                                                              c_str!("traceback"));
+                LuaErr::Eval(message.unwrap_or_default(), frames.unwrap_or_default())
+            } else {
+                // The handler did not run, e.g on a memory error
+                LuaErr::Eval(luaA::error_message(lua, -1), Vec::new())
+            };
+            /* Remove the error and the error handler */
+            lua_pop(lua, 2);
+            return Err(error)
+        }
+        lua_remove(lua, error_func_pos);
+        Ok(lua_gettop(lua) - error_func_pos + 1)
+    }
+
+    /// Converts the results at the top of the stack and pops them.
+    unsafe fn take_results<R: FromLuaMulti>(&self, nresults: i32)
+                                            -> Result<R, LuaErr> {
+        let result = R::from_lua_multi(self.0, nresults);
+        lua_pop(self.0, nresults);
+        result
+    }
+
+    /// Pops the error message on the top of the stack.
+    unsafe fn pop_error(&self) -> String {
+        let error = luaA::error_message(self.0, -1);
+        lua_pop(self.0, 1);
+        error
+    }
+
+    /// Pushes a value on to the Lua stack.
+    ///
+    /// Returns the number of values pushed, so the result of a callback
+    /// can simply be `lua.push(value)`.
+    pub fn push<T: ToLua>(&self, value: T) -> i32 {
         unsafe {
-            let lua = &mut *self.0;
-            let mut status = luaL_loadfile(lua, path_str.as_ptr());
-            if status != 0 {
-                // If something went wrong, error message is at the top of
-                // the stack.
-                let error = lua_tostring(lua, -1);
-                let error = CStr::from_ptr(error).to_string_lossy().into_owned();
-
-                Err(LuaErr::FileNotFound(path.clone(), error))?
-            }
+            value.push_to_lua(self.0)
+        }
+    }
 
-            // Run the file
-            status = lua_pcallk(lua, 0, LUA_MULTRET, 0, 0, None);
-            if status != 0 {
-                let error = lua_tostring(lua, -1);
-                let error = CStr::from_ptr(error).to_string_lossy().into_owned();
+    /// Reads the value at the given stack index as a `T`.
+    ///
+    /// The value is not popped from the stack.
+    pub fn get<T: FromLua>(&self, idx: i32) -> Result<T, LuaErr> {
+        unsafe {
+            T::from_lua(self.0, idx)
+        }
+    }
 
-                Err(LuaErr::Eval(error))?
+    /// Emits the signal on the object, calling the functions connected to
+    /// it on the object and then on its class with the object followed by
+    /// `args`, which is `()`, a value or a tuple of values.
+    ///
+    /// The object must be referenced in the object registry, e.g with
+    /// [object_attach](luaA/fn.object_attach.html).
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// lua.emit_signal(client, "property::geometry", ())?;
+    /// lua.emit_signal(client, "request::activate", ("rules", false))?;
+    /// ```
+    pub fn emit_signal<O, A>(&self, object: &O, name: &str, args: A)
+                             -> Result<(), LuaErr>
+        where A: ToLuaMulti
+    {
+        let c_name = signal_name(name)?;
+        unsafe {
+            let top = lua_gettop(self.0);
+            luaA::object_push(self.0, object as *const O as *mut _);
+            if lua_type(self.0, -1) != LUA_TUSERDATA as i32 {
+                lua_settop(self.0, top);
+                return Err(LuaErr::ObjectNotFound)
             }
+            let nargs = args.push_to_lua_multi(self.0);
+            luaA::object_emit_signal(self.0, - nargs - 1, c_name.as_ptr(), nargs);
+            lua_settop(self.0, top);
+        }
+        Ok(())
+    }
+
+    /// Emits the signal on the class, e.g the `button_class` of the
+    /// [State](../state/struct.State.html), with `args`.
+    pub fn emit_class_signal<A>(&self, class: &RwLock<Class>, name: &str, args: A)
+                                -> Result<(), LuaErr>
+        where A: ToLuaMulti
+    {
+        let c_name = signal_name(name)?;
+        /* Not locked, as the handlers may use the class */
+        let class = &*class.read().unwrap() as *const Class;
+        unsafe {
+            let nargs = args.push_to_lua_multi(self.0);
+            luaA::class_emit_signal(self.0, class, c_name.as_ptr(), nargs);
+        }
+        Ok(())
+    }
+
+    /// Emits the signal on the `awesome` global with `args`, like
+    /// `awesome.emit_signal` does.
+    pub fn emit_global_signal<A>(&self, name: &str, args: A) -> Result<(), LuaErr>
+        where A: ToLuaMulti
+    {
+        unsafe {
+            let nargs = args.push_to_lua_multi(self.0);
+            ::object::signal::signal_global_emit(self.0, name, nargs);
+        }
+        Ok(())
+    }
+
+    /// Invalidates the object once the resource it stands for is gone,
+    /// e.g a client that was unmapped.
+    ///
+    /// `unmanage` is emitted on clients, and `removed` on other objects,
+    /// before the object is invalidated. From then on `object.valid` is
+    /// false in Lua and any other access raises an error. The object must
+    /// be referenced in the object registry, like for
+    /// [emit_signal](#method.emit_signal), and should be unreferenced
+    /// afterwards.
+    ///
+    /// The checker of its class must still consider the object valid, e.g
+    /// don't mark a client invalid with `ClientState::set_valid` first, as
+    /// signals are not emitted on invalid objects.
+    ///
+    /// The object is then removed from the lists of the
+    /// [GlobalConf](../globalconf/struct.GlobalConf.html), see
+    /// `GlobalConf::remove_object`, so it must not be locked by the caller.
+    ///
+    /// # Safety
+    /// The collectors of the class run before this returns, and may drop
+    /// the data of the object. No reference to the object may be held
+    /// across the call, and `object` must not be dereferenced afterwards:
+    /// it may only be compared, or passed to `luaA::object_unref`.
+    pub unsafe fn invalidate<O>(&self, object: *mut O) -> Result<(), LuaErr> {
+        let object = object as *mut libc::c_void;
+        luaA::object_push(self.0, object);
+        let valid = lua_type(self.0, -1) == LUA_TUSERDATA as i32
+            && luaA::object_invalidate(self.0, -1);
+        lua_pop(self.0, 1);
+        if !valid {
+            return Err(LuaErr::ObjectNotFound)
+        }
+        if let Some(conf) = ::state::try_global_conf(self.0) {
+            conf.try_lock()
+                .expect("GlobalConf is locked while invalidating an object")
+                .remove_object(object);
         }
-        ::std::mem::forget(path_str);
         Ok(())
     }
 
     /// Registers the methods in the array to the given variable name.
+    pub fn register_methods<S>(&self, name: S, methods: &[luaL_Reg])
+                               -> Result<(), LuaErr>
+        where S: Into<String>
+    {
+        self.register_table(name.into(), |l| unsafe {
+            ::compat::setfuncs(l, methods);
+        })
+    }
+
+    /// Registers the Rust functions to the given variable name, like
+    /// [register_methods](#method.register_methods).
     ///
-    /// The requirement for the name to be static is to ensure that memory
-    /// does not leak. The mechanism to ensure that names can be dynamically
-    /// allocated is not available at this time.
-    pub fn register_methods(&self, name: &'static str, methods: &[luaL_Reg])
-                            -> Result<(), LuaErr> {
+    /// Unlike `luaL_Reg`s these can be closures that capture state,
+    /// which is dropped when the Lua state is closed.
+    pub fn register_functions<S>(&self, name: S, functions: Vec<(String, RustFn)>)
+                                 -> Result<(), LuaErr>
+        where S: Into<String>
+    {
+        let functions = functions.into_iter()
+            .map(|(name, function)| {
+                CString::new(name.clone())
+                    .map(|c_name| (c_name, function))
+                    .map_err(|_| LuaErr::EvalFFI(FFIErr::NullByte(name)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.register_table(name.into(), |l| unsafe {
+            for (c_name, function) in functions {
+                push_rust_fn(l, function);
+                lua_setfield(l, -2, c_name.as_ptr());
+            }
+        })
+    }
+
+    /// Pushes a Rust function on to the stack, as a Lua function.
+    pub fn push_function<F>(&self, function: F)
+        where F: Fn(&Lua) -> Result<libc::c_int, LuaErr> + 'static
+    {
+        unsafe {
+            push_rust_fn(self.0, Box::new(function));
+        }
+    }
+
+    /// Creates a global table with the given name, filled in by `fill`
+    /// while it's on the top of the stack.
+    fn register_table<F>(&self, name: String, fill: F) -> Result<(), LuaErr>
+        where F: FnOnce(*mut lua_State)
+    {
+        let c_name = CString::new(name.clone())
+            .map_err(|_| LuaErr::EvalFFI(FFIErr::NullByte(name.clone())))?;
         unsafe {
             let l = self.0;
-            // NOTE: This is safe because we guarentee that name is static
-            let c_name = CStr::from_bytes_with_nul(name.as_bytes())
-                .map_err(|_| LuaErr::EvalFFI(FFIErr::NullByte(name.into())))?;
             let result = luaL_newmetatable(l, c_name.as_ptr());
-            ::std::mem::forget(c_name);
             if result == ALREADY_DEFINED {
                 // variable is still pushed to the stack
                 lua_pop(l, 1);
-                return Err(LuaErr::AlreadyDefined(name.into()))
+                return Err(LuaErr::AlreadyDefined(name))
             }
             /* Set __index to be the metatable */
             // move meta table to top of stack
@@ -129,7 +503,7 @@ impl Lua {
 
             /* Add the methods to the table */
             lua_newtable(l);
-            luaL_setfuncs(l, methods.as_ptr(), 0);
+            fill(l);
             lua_pushvalue(l, -1);
             // NOTE Pops the value from the stack
             lua_setglobal(l, c_name.as_ptr());
@@ -151,10 +525,9 @@ impl Lua {
         unsafe {
             let c_name = CString::new(name)
                 .expect("Name contained null bytes");
-            self.load_and_run(lib_path)?;
-            println!("Setting global to {:#?}", c_name);
+            self.load_file(&lib_path)?;
+            self.call_loaded(1)?;
             lua_setglobal(self.0, c_name.as_ptr());
-            ::std::mem::forget(c_name);
         }
         Ok(())
     }
@@ -224,6 +597,57 @@ impl DerefMut for Lua {
     }
 }
 
+/// Converts the name of a signal for the C API.
+fn signal_name(name: &str) -> Result<CString, LuaErr> {
+    CString::new(name).map_err(|_| LuaErr::EvalFFI(FFIErr::NullByte(name.into())))
+}
+
+/// Pushes the Rust function as a C closure, with the function stored in a
+/// userdata upvalue so that it is dropped when it's garbage collected.
+unsafe fn push_rust_fn(lua: *mut lua_State, function: RustFn) {
+    let size = ::std::mem::size_of::<RustFn>();
+    let ud = lua_newuserdata(lua, size) as *mut RustFn;
+    ::std::ptr::write(ud, function);
+    if luaL_newmetatable(lua, c_str!("awesome.rust_function")) != 0 {
+        lua_pushcfunction(lua, Some(rust_fn_gc));
+        lua_setfield(lua, -2, c_str!("__gc"));
+    }
+    lua_setmetatable(lua, -2);
+    lua_pushcclosure(lua, Some(call_rust_fn), 1);
+}
+
+unsafe extern fn call_rust_fn(lua: *mut lua_State) -> libc::c_int {
+    /* The function is the first upvalue */
+    let function = lua_touserdata(lua, ::compat::upvalueindex(1)) as *const RustFn;
+    /* Unwinding in to the C of Lua is undefined behaviour */
+    let result = panic::catch_unwind(AssertUnwindSafe(|| (*function)(&Lua(lua))));
+    let message = match result {
+        Ok(Ok(nresults)) => return nresults,
+        Ok(Err(err)) => err.to_string(),
+        Err(panic) => match panic.downcast::<String>() {
+            Ok(message) => *message,
+            Err(panic) => match panic.downcast::<&'static str>() {
+                Ok(message) => (*message).into(),
+                Err(_) => "Rust function panicked".into()
+            }
+        }
+    };
+    push_error_message(lua, message);
+    /* lua_error doesn't return, so nothing can be left to drop */
+    lua_error(lua)
+}
+
+/// Pushes the message of an error and drops it, before it is raised.
+unsafe fn push_error_message(lua: *mut lua_State, message: String) {
+    lua_pushlstring(lua, message.as_ptr() as *const libc::c_char,
+                    message.len());
+}
+
+unsafe extern fn rust_fn_gc(lua: *mut lua_State) -> libc::c_int {
+    ::std::ptr::drop_in_place(lua_touserdata(lua, 1) as *mut RustFn);
+    0
+}
+
 /// These are methods that are straight Rust-version copies of their equivalents
 /// defined in the Awesome library.
 ///
@@ -235,54 +659,45 @@ pub mod luaA {
     use std::process::Command;
     use std::cell::Cell;
     use std::ffi::{CString, CStr};
-    use std::collections::LinkedList;
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::Hasher;
-    use std::sync::{Mutex, RwLock};
+    use std::sync::RwLock;
     use ::object::Property;
+    use ::object::signal::{Signals, SignalFunc};
+    use ::convert::{ToLua, FromLua};
+    use super::{LuaErr, StackFrame};
+    use ::diagnostics::RegistryError;
     use ::object::class::{Class, Object, AllocatorF, CheckerF, CollectorF,
                           PropF};
-    use callbacks::button::ButtonState;
     // This weird line is so that I can use luaA namespace explicitly here.
     use super::luaA;
 
-    // Global button class definitions
-    lazy_static! {
-        pub static ref BUTTON_CLASS: RwLock<Class> =
-            RwLock::new(Class::default());
-        pub static ref CLIENT_CLASS: RwLock<Class> =
-            RwLock::new(Class::default());
-        pub static ref DRAWIN_CLASS: RwLock<Class> =
-            RwLock::new(Class::default());
-        pub static ref DRAWABLE_CLASS: RwLock<Class> =
-            RwLock::new(Class::default());
-        pub static ref SCREEN_CLASS: RwLock<Class> =
-            RwLock::new(Class::default());
-    }
-
     const NULL: *mut libc::c_void = 0 as _;
 
-    pub struct ClassWrapper(*mut Class);
+    /// A class that was set up, with the lock it is in.
+    pub struct ClassWrapper(*const RwLock<Class>, *mut Class);
 
     unsafe impl Send for ClassWrapper {}
     unsafe impl Sync for ClassWrapper {}
 
     impl ClassWrapper {
-        pub fn new(class: *mut Class) -> Self {
-            ClassWrapper(class)
+        pub fn new(lock: *const RwLock<Class>, class: *mut Class) -> Self {
+            ClassWrapper(lock, class)
+        }
+
+        /// The lock of the class, to read it safely.
+        pub fn lock(&self) -> *const RwLock<Class> {
+            self.0
         }
-    }
 
-    lazy_static! {
-        /// Lua function to call on dofunction() error
-        pub static ref ERROR_FUNC: RwLock<lua_CFunction> =
-            RwLock::new(None);
-        pub static ref CLASSES: Mutex<LinkedList<ClassWrapper>> =
-            Mutex::new(LinkedList::new());
+        /// The class, without locking it.
+        pub fn class(&self) -> *mut Class {
+            self.1
+        }
     }
 
+
     // TODO move this somewhere else...
     #[repr(C)]
+    #[derive(Debug, Clone, Copy, Eq, PartialEq)]
     pub struct area_t {
         pub x: i16,
         pub y: i16,
@@ -307,11 +722,11 @@ pub mod luaA {
                               l: &[luaL_Reg]) {
         if ! libname.is_null() {
             lua_newtable(lua);
-            luaL_setfuncs(lua, l.as_ptr(), 0);
+            ::compat::setfuncs(lua, l);
             lua_pushvalue(lua, -1);
             lua_setglobal(lua, libname);
         } else {
-            luaL_setfuncs(lua, l.as_ptr(), 0);
+            ::compat::setfuncs(lua, l);
         }
     }
 
@@ -319,7 +734,7 @@ pub mod luaA {
                             tname: *const libc::c_char) -> libc::c_int {
         let msg = lua_pushfstring(lua, c_str!("%s expected, got %s"),
                                   tname, lua_typename(lua, narg));
-        luaL_traceback(lua, lua, ::std::ptr::null_mut(), 2);
+        ::compat::traceback(lua, 2);
         lua_concat(lua, 2);
         return luaL_argerror(lua, narg, msg);
     }
@@ -329,21 +744,21 @@ pub mod luaA {
                              -> libc::c_int {
         let msg = lua_pushfstring(lua, c_str!("value in [%f, %f] expected, got %f"),
                                   min, max, lua_tonumber(lua, narg) as libc::c_double);
-        luaL_traceback(lua, lua, ::std::ptr::null_mut(), 2);
+        ::compat::traceback(lua, 2);
         lua_concat(lua, 2);
         return luaL_argerror(lua, narg, msg);
     }
 
     pub unsafe fn getuservalue(lua: *mut lua_State, idx: libc::c_int) {
-        lua_getuservalue(lua, idx);
+        ::compat::getuservalue(lua, idx);
     }
 
     pub unsafe fn setuservalue(lua: *mut lua_State, idx: libc::c_int) {
-        lua_setuservalue(lua, idx);
+        ::compat::setuservalue(lua, idx);
     }
 
     pub unsafe fn rawlen(lua: *mut lua_State, idx: libc::c_int) -> libc::size_t {
-        return lua_rawlen(lua, idx) as libc::size_t ;
+        return ::compat::rawlen(lua, idx);
     }
 
     pub unsafe fn checkfunction(lua: *mut lua_State, idx: libc::c_int) {
@@ -519,18 +934,14 @@ pub mod luaA {
     pub unsafe fn class_index_miss_property(lua: *mut lua_State,
                                             _object: *mut Object)
                                             -> libc::c_int {
-        use object::{GLOBAL_SIGNALS, signal_object_emit};
-        let global_signals = GLOBAL_SIGNALS.lock().unwrap();
-        signal_object_emit(lua, &*global_signals, "debug::index::miss", 2);
+        ::object::signal::signal_global_emit(lua, "debug::index::miss", 2);
         return 0
     }
 
     pub unsafe fn class_newindex_miss_property(lua: *mut lua_State,
                                                _object: *mut Object)
                                                -> libc::c_int {
-        use object::{GLOBAL_SIGNALS, signal_object_emit};
-        let global_signals = GLOBAL_SIGNALS.lock().unwrap();
-        signal_object_emit(lua, &*global_signals, "debug::newindex::miss", 3);
+        ::object::signal::signal_global_emit(lua, "debug::newindex::miss", 3);
         return 0
     }
 
@@ -556,8 +967,8 @@ pub mod luaA {
         /* Move error handling function before args and functions */
         ::lua::lua_insert(lua, - nargs -2);
         let error_func_pos = lua_gettop(lua) - nargs -1;
-        if lua_pcallk(lua, nargs, nret, - nargs -2, 0, None) != 0{
-            eprintln!("{:?}", lua_tostring(lua, -1));
+        if ::compat::pcall(lua, nargs, nret, - nargs -2) != 0{
+            eprintln!("{}", luaA::error_message(lua, -1));
             /* Remove error function and error string */
             lua_pop(lua, 2);
             return 0;
@@ -568,7 +979,7 @@ pub mod luaA {
     }
 
     pub unsafe extern fn dofunction_error(lua: *mut lua_State) -> libc::c_int {
-        match ERROR_FUNC.read() {
+        match ::state::get(lua).error_func.read() {
             Ok(error_f_guard) => {
                 if let Some(error_f) = *error_f_guard {
                     return error_f(lua)
@@ -579,6 +990,91 @@ pub mod luaA {
         0
     }
 
+    /// Default handler for errors in dofunction().
+    ///
+    /// Emits `debug::error` on the `awesome` global with the error message
+    /// and replaces the error with the message followed by a traceback.
+    pub unsafe extern fn dofunction_on_error(lua: *mut lua_State) -> libc::c_int {
+        use object::signal::signal_global_emit;
+        /* Convert error to string, to prevent a follow-up error with lua_concat */
+        let message = luaA::error_message(lua, -1);
+        /* Skip the handler itself */
+        let frames = luaA::traceback(lua, 1);
+        /* An error in a handler of debug::error is only reported on stderr,
+         * instead of emitting debug::error again. */
+        let state = ::state::get(lua);
+        if !state.handling_error.get() {
+            state.handling_error.set(true);
+            message.push_to_lua(lua);
+            signal_global_emit(lua, "debug::error", 1);
+            state.handling_error.set(false);
+        }
+        format!("error while running function\n{}\nerror: {}",
+                super::format_traceback(&frames), message).push_to_lua(lua)
+    }
+
+    /// Error handler that replaces the error with a table containing the
+    /// error `message` and the `traceback` frames of where it occurred.
+    pub unsafe extern fn error_traceback(lua: *mut lua_State) -> libc::c_int {
+        let message = luaA::error_message(lua, -1);
+        /* Skip the handler itself */
+        let frames = luaA::traceback(lua, 1);
+        lua_createtable(lua, 0, 2);
+        message.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("message"));
+        frames.push_to_lua(lua);
+        lua_setfield(lua, -2, c_str!("traceback"));
+        1
+    }
+
+    /// Collects the frames of the current stack, starting at `level`
+    /// (0 being the currently running function).
+    pub unsafe fn traceback(lua: *mut lua_State, level: libc::c_int)
+                            -> Vec<StackFrame> {
+        let mut frames = Vec::new();
+        let mut level = level;
+        let mut ar: lua_Debug = ::std::mem::zeroed();
+        while lua_getstack(lua, level, &mut ar) != 0 {
+            lua_getinfo(lua, c_str!("Sln"), &mut ar);
+            let name = if ar.name.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(ar.name).to_string_lossy().into_owned())
+            };
+            frames.push(StackFrame {
+                source: CStr::from_ptr(ar.short_src.as_ptr())
+                    .to_string_lossy().into_owned(),
+                line: if ar.currentline > 0 { Some(ar.currentline) } else { None },
+                name,
+                what: CStr::from_ptr(ar.what).to_string_lossy().into_owned()
+            });
+            level += 1;
+        }
+        frames
+    }
+
+    /// Converts the error object at `idx` to a message, without raising
+    /// another error if it isn't a string.
+    pub unsafe fn error_message(lua: *mut lua_State, idx: libc::c_int) -> String {
+        let error = lua_tolstring(lua, idx, ::std::ptr::null_mut());
+        if error.is_null() {
+            let ty = CStr::from_ptr(lua_typename(lua, lua_type(lua, idx)));
+            format!("(error object is a {} value)", ty.to_string_lossy())
+        } else {
+            CStr::from_ptr(error).to_string_lossy().into_owned()
+        }
+    }
+
+    /// Reads the field `name` of the table at `idx`.
+    pub unsafe fn getfield<T: FromLua>(lua: *mut lua_State, idx: libc::c_int,
+                                       name: *const libc::c_char)
+                                       -> Result<T, LuaErr> {
+        lua_getfield(lua, idx, name);
+        let value = T::from_lua(lua, -1);
+        lua_pop(lua, 1);
+        value
+    }
+
     pub unsafe fn checktable(lua: *mut lua_State, idx: libc::c_int) {
         let istable = lua_type(lua, idx) == LUA_TTABLE as i32;
         if !istable {
@@ -607,7 +1103,7 @@ pub mod luaA {
     }
 
     pub unsafe extern fn button_new(lua: *mut lua_State) -> libc::c_int {
-        luaA::class_new(lua, &*BUTTON_CLASS)
+        luaA::class_new(lua, &::state::get(lua).button_class)
     }
 
     pub unsafe fn class_new(lua: *mut lua_State, global_class: &RwLock<Class>)
@@ -615,9 +1111,15 @@ pub mod luaA {
         /* Check we have a table that should contains some properties */
         luaA::checktable(lua, 2);
 
+        /* The class is not locked while Lua runs, in the allocator and the
+         * setters, as it may be used from the handlers of their signals */
+        let (allocator, class) = {
+            let class = global_class.try_read().unwrap();
+            (class.allocator.unwrap(), &*class as *const Class)
+        };
         /* Create a new object */
-        let class = global_class.try_read().unwrap();
-        let object_ptr = (class.allocator.unwrap())(lua);
+        let object_ptr = allocator(lua);
+        let object_idx = lua_gettop(lua);
 
         /* Push the first key before iterating */
         lua_pushnil(lua);
@@ -628,10 +1130,14 @@ pub mod luaA {
              * number TO A STRING, confusing lua_next() */
             let is_string = lua_type(lua, -2) == LUA_TSTRING as i32;
             if is_string {
-                let prop = luaA::class_property_get(lua, &*class as _, -2);
+                let prop = luaA::class_property_get(lua, class, -2);
 
-                if !prop.is_null() && (*prop).new.is_some() {
-                    (*prop).new.unwrap()(lua, object_ptr);
+                if !prop.is_null() {
+                    if (*prop).setter.is_some() {
+                        luaA::class_property_set(lua, prop, object_ptr, object_idx, -1);
+                    } else if let Some(new) = (*prop).new {
+                        new(lua, object_ptr);
+                    }
                 }
             }
             /* Remove value */
@@ -653,18 +1159,19 @@ pub mod luaA {
         return ::std::ptr::null_mut();
     }
 
+    /// Raises the error in the first upvalue, see `object_collect`.
     pub unsafe extern fn class_newindex_invalid(lua: *mut lua_State)
                                                 -> libc::c_int {
-        return luaL_error(lua, c_str!("attempt to index an object that \
-                                       was already garbage collected"))
+        let message = lua_tolstring(lua, ::compat::upvalueindex(1),
+                                    ::std::ptr::null_mut());
+        return luaL_error(lua, c_str!("%s"), message)
     }
 
     pub unsafe extern fn class_index_invalid(lua: *mut lua_State)
                                              -> libc::c_int {
-        let attr = CString::from_raw(
-            luaL_checklstring(lua, 2, ::std::ptr::null_mut()) as _)
-            .into_string().unwrap();
-        if &*attr == "valid" {
+        let attr = CStr::from_ptr(
+            luaL_checklstring(lua, 2, ::std::ptr::null_mut()));
+        if attr.to_bytes() == b"valid" {
             lua_pushboolean(lua, 0);
             return 1;
         }
@@ -673,16 +1180,27 @@ pub mod luaA {
     }
 
     pub unsafe extern fn class_gc(lua: *mut lua_State) -> libc::c_int {
-        let item = lua_touserdata(lua, 1) as *mut Object;
+        luaA::object_collect(lua, 1, c_str!("attempt to index an object that \
+                                             was already garbage collected"));
+        return 0;
+    }
+
+    /// Drops the signals of the object at `idx` and calls the collectors of
+    /// its class, after which the object is invalid: `object.valid` is
+    /// false and any other access raises `message`.
+    pub unsafe fn object_collect(lua: *mut lua_State, idx: libc::c_int,
+                                 message: *const libc::c_char) {
+        let idx = luaA::absindex(lua, idx);
+        let item = lua_touserdata(lua, idx) as *mut Object;
         (*item).signals.clear();
         /* Get the object class */
-        let class = luaA::class_get(lua, 1);
+        let class = luaA::class_get(lua, idx);
         let old_instances = (*class).instances.get();
         (*class).instances.set(old_instances - 1);
         /* Call the collector function of the class, and all its parent classes */
         let mut cur_class = class;
         while ! cur_class.is_null() {
-            if let Some(collector) = (*class).collector {
+            if let Some(collector) = (*cur_class).collector {
                 collector(item);
             }
             cur_class = (*cur_class).parent
@@ -692,12 +1210,41 @@ pub mod luaA {
          * We also make sure that `item.valid == false`.
          */
         lua_newtable(lua);
-        lua_pushcfunction(lua, Some(luaA::class_index_invalid));
+        lua_pushstring(lua, message);
+        lua_pushcclosure(lua, Some(luaA::class_index_invalid), 1);
         lua_setfield(lua, -2, c_str!("__index"));
-        lua_pushcfunction(lua, Some(luaA::class_newindex_invalid));
+        lua_pushstring(lua, message);
+        lua_pushcclosure(lua, Some(luaA::class_newindex_invalid), 1);
         lua_setfield(lua, -2, c_str!("__newindex"));
-        lua_setmetatable(lua, 1);
-        return 0;
+        lua_setmetatable(lua, idx);
+    }
+
+    /// Invalidates the object at `idx`, e.g because the client it stands
+    /// for was unmapped, instead of waiting for it to be garbage collected.
+    ///
+    /// The `removed_signal` of its class is emitted on it first, e.g
+    /// `unmanage` for clients. Afterwards `object.valid` is false and any
+    /// other access raises an error.
+    ///
+    /// Returns false if the value is not a valid object.
+    pub unsafe fn object_invalidate(lua: *mut lua_State, idx: libc::c_int) -> bool {
+        let idx = luaA::absindex(lua, idx);
+        let class = luaA::class_get(lua, idx);
+        if class.is_null() {
+            return false
+        }
+        if let Ok(signal) = CString::new((*class).removed_signal.clone()) {
+            luaA::object_emit_signal(lua, idx, signal.as_ptr(), 0);
+        }
+        /* A handler may have invalidated it already */
+        if luaA::class_get(lua, idx).is_null() {
+            return true
+        }
+        let message = format!("attempt to index an invalid {} object",
+                              (*class).name);
+        let message = CString::new(message).unwrap_or_default();
+        luaA::object_collect(lua, idx, message.as_ptr());
+        true
     }
 
     pub unsafe fn object_setup(lua: *mut lua_State) {
@@ -712,9 +1259,127 @@ pub mod luaA {
         lua_setmetatable(lua, -2);
         /* Register table inside registry */
         lua_rawset(lua, LUA_REGISTRYINDEX);
+
+        /* Table of the signal handlers that are held weakly */
+        lua_pushstring(lua, c_str!("awesome.object.weak"));
+        lua_newtable(lua);
+        lua_newtable(lua);
+        lua_pushstring(lua, c_str!("v"));
+        lua_setfield(lua, -2, c_str!("__mode"));
+        lua_setmetatable(lua, -2);
+        lua_rawset(lua, LUA_REGISTRYINDEX);
+    }
+
+    /// Stores the value at `idx` in the table of weak references,
+    /// returning its key.
+    ///
+    /// Keys are never handed out twice, so a key whose value has been
+    /// collected can't end up pointing at another value.
+    pub unsafe fn object_weak_ref(lua: *mut lua_State, idx: libc::c_int)
+                                  -> libc::c_int {
+        let idx = luaA::absindex(lua, idx);
+        let weak_keys = &::state::get(lua).weak_keys;
+        let key = weak_keys.get() + 1;
+        weak_keys.set(key);
+        lua_pushstring(lua, c_str!("awesome.object.weak"));
+        lua_rawget(lua, LUA_REGISTRYINDEX);
+        lua_pushvalue(lua, idx);
+        ::compat::rawseti(lua, -2, key as lua_Integer);
+        lua_pop(lua, 1);
+        key
+    }
+
+    /// Pushes the weakly referenced value, which is nil if it has been
+    /// garbage collected.
+    pub unsafe fn object_weak_push(lua: *mut lua_State, key: libc::c_int) {
+        lua_pushstring(lua, c_str!("awesome.object.weak"));
+        lua_rawget(lua, LUA_REGISTRYINDEX);
+        ::compat::rawgeti(lua, -1, key as lua_Integer);
+        ::lua::lua_remove(lua, -2);
+    }
+
+    pub unsafe fn object_weak_unref(lua: *mut lua_State, key: libc::c_int) {
+        lua_pushstring(lua, c_str!("awesome.object.weak"));
+        lua_rawget(lua, LUA_REGISTRYINDEX);
+        lua_pushnil(lua);
+        ::compat::rawseti(lua, -2, key as lua_Integer);
+        lua_pop(lua, 1);
+    }
+
+    /// Handles the `connect_signal{name = ..., func = ..., weak = true}`
+    /// form of connecting to a signal, where the table is at `idx` instead
+    /// of the name and the function.
+    ///
+    /// The table is replaced by the name and the function, and whether the
+    /// function should be held weakly is returned.
+    pub unsafe fn signal_connect_args(lua: *mut lua_State, idx: libc::c_int)
+                                      -> bool {
+        if lua_type(lua, idx) != LUA_TTABLE as i32 {
+            return false
+        }
+        let idx = luaA::absindex(lua, idx);
+        lua_getfield(lua, idx, c_str!("weak"));
+        let weak = lua_toboolean(lua, -1) != 0;
+        lua_pop(lua, 1);
+        lua_getfield(lua, idx, c_str!("func"));
+        lua_getfield(lua, idx, c_str!("name"));
+        ::compat::lua_replace(lua, idx);
+        lua_settop(lua, idx + 1);
+        weak
+    }
+
+    /// Checks that the value at `ud` is a function and references it, in
+    /// the object registry or, if `weak`, only weakly.
+    ///
+    /// This raises a Lua error if it isn't a function, so call it before
+    /// locking the signals the function is connected to.
+    pub unsafe fn signal_func_from_stack(lua: *mut lua_State, ud: libc::c_int,
+                                         weak: bool) -> SignalFunc {
+        luaA::checkfunction(lua, ud);
+        if weak {
+            let ptr = lua_topointer(lua, ud) as *mut libc::c_void;
+            SignalFunc::weak(ptr, luaA::object_weak_ref(lua, ud))
+        } else {
+            SignalFunc::strong(luaA::object_ref(lua, ud))
+        }
+    }
+
+    /// Drops the reference to a function that was disconnected.
+    pub unsafe fn signal_func_unref(lua: *mut lua_State, func: SignalFunc) {
+        match func.weak {
+            Some(key) => luaA::object_weak_unref(lua, key),
+            None => luaA::object_unref(lua, func.ptr)
+        }
+    }
+
+    /// Connects the function at `ud` to the signal, holding it in the
+    /// object registry or, if `weak`, only weakly.
+    pub unsafe fn signal_connect_from_stack(lua: *mut lua_State,
+                                            signals: &mut Signals,
+                                            name: *const libc::c_char,
+                                            ud: libc::c_int, weak: bool) {
+        let func = luaA::signal_func_from_stack(lua, ud, weak);
+        signals.prune(lua);
+        signals.connect(CStr::from_ptr(name).to_string_lossy(), func);
+    }
+
+    /// Disconnects the function at `ud` from the signal, dropping the
+    /// reference to it.
+    pub unsafe fn signal_disconnect_from_stack(lua: *mut lua_State,
+                                               signals: &mut Signals,
+                                               name: *const libc::c_char,
+                                               ud: libc::c_int) {
+        luaA::checkfunction(lua, ud);
+        let ptr = lua_topointer(lua, ud) as *mut libc::c_void;
+        if let Some(func) = signals.disconnect(&CStr::from_ptr(name).to_string_lossy(), ptr) {
+            luaA::signal_func_unref(lua, func);
+        }
     }
 
+    /// Sets up the class in the lock, which is usually write locked by the
+    /// caller so `class` is the `Class` in it.
     pub unsafe fn class_setup(lua: *mut lua_State, class: *mut Class,
+                              lock: *const RwLock<Class>,
                               name: *const libc::c_char,
                               parent: *mut Class,
                               allocator: AllocatorF,
@@ -748,13 +1413,19 @@ pub mod luaA {
 
         luaA::registerlib(lua, ::std::ptr::null_mut(), meta);                 /* 1 */
         luaA::registerlib(lua, name, methods);                                /* 2 */
+        /* Signal handlers can be held weakly on every object and class */
+        lua_pushcfunction(lua, Some(luaA::object_weak_connect_signal));
+        lua_setfield(lua, -3, c_str!("weak_connect_signal"));
+        lua_pushlightuserdata(lua, class as _);
+        lua_pushcclosure(lua, Some(luaA::class_weak_connect_signal), 1);
+        lua_setfield(lua, -2, c_str!("weak_connect_signal"));
         lua_pushvalue(lua, -1);           /* dup self as metatable              3 */
         lua_setmetatable(lua, -2);        /* set self as metatable              2 */
         lua_pop(lua, 2);
 
         (*class).collector = collector;
         (*class).allocator = Some(allocator);
-        (*class).name = CString::from_raw(name as _).into_string().unwrap();
+        (*class).name = CStr::from_ptr(name).to_string_lossy().into_owned();
         (*class).index_miss_prop = index_miss_property;
         (*class).newindex_miss_prop = newindex_miss_property;
         (*class).checker = checker;
@@ -764,7 +1435,41 @@ pub mod luaA {
         (*class).index_miss_handler = LUA_REFNIL;
         (*class).newindex_miss_handler = LUA_REFNIL;
 
-        luaA::CLASSES.lock().unwrap().push_back(ClassWrapper::new(class));
+        ::state::get(lua).classes.lock().unwrap()
+            .push_back(ClassWrapper::new(lock, class));
+    }
+
+    /// Moves an object out of its Lua state, so that it outlives it.
+    ///
+    /// The userdata left behind no longer has a metatable, so the `__gc`
+    /// of its class won't run when the state is closed.
+    /// All of the signals connected to the object are dropped, as they are
+    /// references into the state.
+    pub unsafe fn object_detach<O>(lua: *mut lua_State, obj: *mut O) -> Box<O> {
+        luaA::object_push(lua, obj as _);
+        if lua_type(lua, -1) == LUA_TUSERDATA as i32 {
+            lua_pushnil(lua);
+            lua_setmetatable(lua, -2);
+        }
+        lua_pop(lua, 1);
+        let obj = Box::new(::std::ptr::read(obj));
+        (*(&*obj as *const O as *mut Object)).signals.clear();
+        obj
+    }
+
+    /// Moves an object that was [detached](fn.object_detach.html) in to a
+    /// new instance of the class, and references it in the object registry.
+    ///
+    /// Returns `None` if the class has not been set up in this state,
+    /// in which case the object is dropped.
+    pub unsafe fn object_attach<O>(lua: *mut lua_State, class: &RwLock<Class>,
+                                   obj: Box<O>) -> Option<*mut O> {
+        let allocator = class.try_read().unwrap().allocator?;
+        let p = allocator(lua) as *mut O;
+        /* Don't drop the zeroed memory of the new object */
+        ::std::ptr::write(p, *obj);
+        luaA::object_ref(lua, -1);
+        Some(p)
     }
 
     pub unsafe extern fn object_tostring(lua: *mut lua_State) -> libc::c_int {
@@ -773,7 +1478,7 @@ pub mod luaA {
             eprintln!("lua class was null!");
             return 0;
         }
-        let object = luaA::checkudata(lua, 1, lua_class);
+        let object = luaA::toudata(lua, 1, lua_class);
         let mut offset = 0;
         while ! lua_class.is_null() {
             if offset != 0 {
@@ -836,14 +1541,37 @@ pub mod luaA {
             let name = CString::new((*class).name.clone()).unwrap();
             luaA::typeerror(lua, ud, name.as_ptr());
             ::std::mem::forget(name);
-        } else if let Some(checker) = (*class).checker {
-            checker(p as _);
-            luaL_error(lua, c_str!("invalid object"));
+        } else if !luaA::object_valid(class, p) {
+            luaA::error(lua, format!("invalid {} object (argument #{})",
+                                     (*class).name, ud));
         }
         return p;
 
     }
 
+    /// Like `checkudata`, but the error for an invalid object names the
+    /// property that was accessed.
+    pub unsafe fn checkproperty(lua: *mut lua_State, ud: libc::c_int,
+                                class: *mut Class, property: &str)
+                                -> *mut libc::c_void {
+        let p = luaA::toudata(lua, ud, class);
+        if !p.is_null() && !luaA::object_valid(class, p) {
+            luaA::error(lua, format!("attempt to access property '{}' of an \
+                                      invalid {} object",
+                                     property, (*class).name));
+        }
+        luaA::checkudata(lua, ud, class)
+    }
+
+    /// Is the object still backed by a resource, according to the checker
+    /// of its class? Objects of classes without a checker always are.
+    pub unsafe fn object_valid(class: *const Class, object: *mut libc::c_void) -> bool {
+        match (*class).checker {
+            Some(checker) => checker(object as _),
+            None => true
+        }
+    }
+
     pub unsafe fn checkudataornil(lua: *mut lua_State, udx: libc::c_int,
                                   class: *mut Class) -> *mut libc::c_void {
         let lua_t = lua_type(lua, udx);
@@ -898,9 +1626,10 @@ pub mod luaA {
     }
 
     pub unsafe fn object_decref(lua: *mut lua_State, tud: libc::c_int,
-                                pointer: *mut libc::c_void) {
+                                pointer: *mut libc::c_void)
+                                -> Result<(), RegistryError> {
         if pointer.is_null() {
-            return;
+            return Ok(());
         }
 
         /* First, refcount-- */
@@ -914,10 +1643,9 @@ pub mod luaA {
         let count = lua_tointeger(lua, -1) - 1;
         /* Did we find the item in our table? (tointeger(nil)-1) is -1 */
         if count < 0 {
-            eprintln!("BUG: Reference not found");
             /* Pop reference count and metatable */
             lua_pop(lua, 2);
-            return;
+            return Err(RegistryError::ReferenceNotFound(pointer as usize));
         }
         lua_pop(lua, 1);
         /* Push the pointer (key) */
@@ -944,6 +1672,7 @@ pub mod luaA {
             /* table[pointer] = nil */
             lua_rawset(lua, if tud < 0 {tud - 2} else {tud});
         }
+        Ok(())
     }
 
     pub unsafe fn object_ref_item(lua: *mut lua_State, ud: libc::c_int,
@@ -962,28 +1691,66 @@ pub mod luaA {
         /* Get the env table from the object */
         luaA::getuservalue(lua, ud);
         /* Decrement */
-        luaA::object_decref(lua, -1, ptr);
+        let result = luaA::object_decref(lua, -1, ptr);
         /* Remove env table */
         lua_pop(lua, 1);
+        if let Err(err) = result {
+            ::diagnostics::report(lua, err);
+        }
     }
 
     pub unsafe fn object_connect_signal_simple_from_stack(lua: *mut lua_State,
                                                         oud: libc::c_int,
                                                         name: *mut libc::c_char,
                                                         ud: libc::c_int) {
+        luaA::object_connect_signal_from_stack(lua, oud, name, ud, false)
+    }
+
+    /// Connects the function at `ud` to the signal of the object at `oud`.
+    ///
+    /// The function is referenced by the object, unless it's `weak`.
+    pub unsafe fn object_connect_signal_from_stack(lua: *mut lua_State,
+                                                   oud: libc::c_int,
+                                                   name: *const libc::c_char,
+                                                   ud: libc::c_int,
+                                                   weak: bool) {
         luaA::checkfunction(lua, ud);
         let obj = lua_touserdata(lua, oud) as *mut Object;
-        let ref_item = luaA::object_ref_item(lua, oud, ud);
-        ::object::signal::signal_connect(&mut (*obj).signals, name, ref_item);
+        (*obj).signals.prune(lua);
+        let func = if weak {
+            let ptr = lua_topointer(lua, ud) as *mut libc::c_void;
+            SignalFunc::weak(ptr, luaA::object_weak_ref(lua, ud))
+        } else {
+            SignalFunc::strong(luaA::object_ref_item(lua, oud, ud))
+        };
+        (*obj).signals.connect(CStr::from_ptr(name).to_string_lossy(), func);
     }
 
     pub unsafe extern fn object_connect_signal_simple(lua: *mut lua_State)
                                                     -> libc::c_int {
+        let weak = luaA::signal_connect_args(lua, 2);
         let check_string = luaL_checklstring(lua, 2, ::std::ptr::null_mut());
-        luaA::object_connect_signal_simple_from_stack(lua,
-                                                    1,
-                                                    check_string as _,
-                                                    3);
+        luaA::object_connect_signal_from_stack(lua, 1, check_string, 3, weak);
+        0
+    }
+
+    /// `object:weak_connect_signal(name, func)`, connects a function that
+    /// is dropped once nothing else references it.
+    pub unsafe extern fn object_weak_connect_signal(lua: *mut lua_State)
+                                                    -> libc::c_int {
+        let check_string = luaL_checklstring(lua, 2, ::std::ptr::null_mut());
+        luaA::object_connect_signal_from_stack(lua, 1, check_string, 3, true);
+        0
+    }
+
+    /// `class.weak_connect_signal(name, func)`, the class is the first
+    /// upvalue.
+    pub unsafe extern fn class_weak_connect_signal(lua: *mut lua_State)
+                                                   -> libc::c_int {
+        let class = lua_touserdata(lua, ::compat::upvalueindex(1)) as *mut Class;
+        let check_string = luaL_checklstring(lua, 1, ::std::ptr::null_mut());
+        luaA::signal_connect_from_stack(lua, &mut (*class).signals,
+                                        check_string, 2, true);
         0
     }
 
@@ -994,10 +1761,12 @@ pub mod luaA {
         luaA::checkfunction(lua, ud);
         let obj = lua_touserdata(lua, oud) as *mut Object;
         let ptr = lua_topointer(lua, ud) as _;
-        if ::object::signal::signal_disconnect(&mut (*obj).signals,
-                                                name,
-                                                ptr) != 0 {
-            luaA::object_unref_item(lua, oud, ptr);
+        let name = CStr::from_ptr(name).to_string_lossy();
+        match (*obj).signals.disconnect(&name, ptr) {
+            Some(SignalFunc { weak: Some(key), .. }) =>
+                luaA::object_weak_unref(lua, key),
+            Some(_) => luaA::object_unref_item(lua, oud, ptr),
+            None => {}
         }
         ::lua::lua_remove(lua, ud);
 
@@ -1037,8 +1806,8 @@ pub mod luaA {
     pub unsafe fn class_emit_signal(lua: *mut lua_State, class: *const Class,
                                     name: *const libc::c_char,
                                     nargs: libc::c_int) {
-        let name = CStr::from_ptr(name).to_str().unwrap();
-        ::object::signal::signal_object_emit(lua, &(*class).signals, name, nargs)
+        let name = CStr::from_ptr(name).to_string_lossy();
+        ::object::signal::signal_object_emit(lua, &(*class).signals, &name, nargs)
     }
 
     pub unsafe extern fn object_emit_signal(lua: *mut lua_State,
@@ -1047,40 +1816,35 @@ pub mod luaA {
                                             nargs: libc::c_int) {
         let oud_abs = luaA::absindex(lua, oud);
         let lua_class = luaA::class_get(lua, oud);
-        let obj = luaA::toudata(lua, oud, lua_class) as *mut Class;
+        let obj = luaA::toudata(lua, oud, lua_class) as *mut Object;
+        let sig_name = CStr::from_ptr(name).to_string_lossy();
         if obj.is_null() {
-            eprintln!("Trying to emit signal '{:?}' on non object", name);
+            eprintln!("Trying to emit signal '{}' on non object", sig_name);
+            lua_pop(lua, nargs);
             return;
-        } else if let Some(checker) = (*lua_class).checker {
-            checker(obj as _);
-            eprintln!("Trying to emit signal '{:?}' on invalid object", name);
+        } else if !luaA::object_valid(lua_class, obj as _) {
+            eprintln!("Trying to emit signal '{}' on invalid {} object",
+                      sig_name, (*lua_class).name);
+            lua_pop(lua, nargs);
             return;
         }
-        let mut hasher = DefaultHasher::new();
-        hasher.write(CStr::from_ptr(name).to_str().unwrap().as_bytes());
-        let id = hasher.finish();
-        if let Some(sig) = (*obj).signals.iter_mut().find(|sig| sig.id == id) {
-            let nbfunc = sig.sigfuncs.len() as i32;
-            luaL_checkstack(lua, nbfunc + nargs + 2, c_str!("too much signal"));
-            /* Push all functions and then execute, because this list can change
-            * while executing funcs. */
-            for sigfunc in &mut sig.sigfuncs {
-                luaA::object_push_item(lua, oud_abs, sigfunc as *mut _ as *mut _);
-            }
-
-            for i in 0..nbfunc {
-                /* push object */
-                lua_pushvalue(lua, oud_abs);
-                /* push all args */
-                for _ in 0..nargs {
-                    lua_pushvalue(lua, - nargs - nbfunc - 1 + i);
-                }
-                /* push first function */
+        /* Push all functions and then execute, because this list can change
+        * while executing funcs. */
+        let nbfunc = ::object::signal::signal_push_handlers(
+            lua, &(*obj).signals, &sig_name, nargs + 2,
+            |ptr| { luaA::object_push_item(lua, oud_abs, ptr); });
+        for i in 0..nbfunc {
+            /* push object */
+            lua_pushvalue(lua, oud_abs);
+            /* push all args */
+            for _ in 0..nargs {
                 lua_pushvalue(lua, - nargs - nbfunc - 1 + i);
-                /* remove this first function */
-                ::lua::lua_remove(lua, - nargs - nbfunc - 2 + i);
-                luaA::dofunction(lua, nargs + 1, 0);
             }
+            /* push first function */
+            lua_pushvalue(lua, - nargs - nbfunc - 1 + i);
+            /* remove this first function */
+            ::lua::lua_remove(lua, - nargs - nbfunc - 2 + i);
+            luaA::dofunction(lua, nargs + 1, 0);
         }
 
         /* Then emit signal on the class */
@@ -1109,9 +1873,17 @@ pub mod luaA {
         /* Property does exist and has a newindex callback */
         if !prop.is_null()
         {
+            if (*prop).setter.is_some() {
+                let object = luaA::checkproperty(lua, 1, class, &(*prop).name) as _;
+                return luaA::class_property_set(lua, prop, object, 1, 3);
+            }
             if let Some(newindex) = (*prop).new_index {
-                return newindex(lua, luaA::checkudata(lua, 1, class) as _);
+                let object = luaA::checkproperty(lua, 1, class, &(*prop).name);
+                return newindex(lua, object as _);
             }
+            return luaA::error(lua, format!("property '{}' of class '{}' \
+                                             is read-only",
+                                            (*prop).name, (*class).name));
         } else {
             if (*class).newindex_miss_handler != LUA_REFNIL {
                 return luaA::class_call_handler(lua, (*class).newindex_miss_handler);
@@ -1136,19 +1908,9 @@ pub mod luaA {
         let attr = luaL_checklstring(lua, 2, NULL as _);
         let attr_str = CStr::from_ptr(attr).to_str().unwrap();
         if attr_str == "valid" {
-            let p = luaA::toudata(lua, 1, class) as _;
-            if let Some(checker) = (*class).checker {
-                let res = {
-                    if p != NULL as _ {
-                        if checker(p) {1} else {0}
-                    } else {
-                        0
-                    }
-                };
-                lua_pushboolean(lua, res);
-            } else {
-                lua_pushboolean(lua, if p != NULL as _ {1} else {0});
-            }
+            let p = luaA::toudata(lua, 1, class);
+            let valid = !p.is_null() && luaA::object_valid(class, p);
+            lua_pushboolean(lua, valid as libc::c_int);
             return 1;
         }
 
@@ -1158,7 +1920,7 @@ pub mod luaA {
         * thus not implemented as a lua_class_property_t.
         */
         if attr_str == "data" {
-            luaA::checkudata(lua, 1, class);
+            luaA::checkproperty(lua, 1, class, attr_str);
             luaA::getuservalue(lua, 1);
             lua_getfield(lua, -1, c_str!("data"));
             return 1;
@@ -1166,8 +1928,11 @@ pub mod luaA {
 
         /* Property does exist and has an index callback */
         if ! prop.is_null() {
+            if let Some(ref getter) = (*prop).getter {
+                return getter(lua, luaA::checkproperty(lua, 1, class, attr_str) as _);
+            }
             if let Some(indexF) = (*prop).index {
-                indexF(lua, luaA::checkudata(lua, 1, class) as _);
+                return indexF(lua, luaA::checkproperty(lua, 1, class, attr_str) as _);
             }
         } else {
             if (*class).index_miss_handler != LUA_REFNIL {
@@ -1221,11 +1986,11 @@ pub mod luaA {
         let error_func_pos = 1;
 
         /* push function and move it before args */
-        lua_rawgeti(lua, LUA_REGISTRYINDEX, handler as _);
+        ::compat::rawgeti(lua, LUA_REGISTRYINDEX, handler as _);
         ::lua::lua_insert(lua, - nargs - 1);
 
-        if lua_pcallk(lua, nargs, LUA_MULTRET, error_func_pos, 0, None) != 0 {
-            eprintln!("{:?}", lua_tostring(lua, -1));
+        if ::compat::pcall(lua, nargs, LUA_MULTRET, error_func_pos) != 0 {
+            eprintln!("{}", luaA::error_message(lua, -1));
             /* Remove error function and error string */
             lua_pop(lua, 2);
             return 0;
@@ -1260,20 +2025,18 @@ pub mod luaA {
 
     pub unsafe fn object_unref(lua: *mut lua_State, ptr: *mut libc::c_void) {
         luaA::object_registry_push(lua);
-        luaA::object_decref(lua, -1, ptr as _);
+        let result = luaA::object_decref(lua, -1, ptr as _);
         lua_pop(lua, 1);
+        if let Err(err) = result {
+            ::diagnostics::report(lua, err);
+        }
     }
 
     pub unsafe fn class_disconnect_signal_from_stack(lua: *mut lua_State,
                                                      class: *mut Class,
                                                      name: *const libc::c_char,
                                                      ud: libc::c_int) {
-        use ::object::signal::signal_disconnect;
-        luaA::checkfunction(lua, ud);
-        let ptr = lua_topointer(lua, ud);
-        if (signal_disconnect(&mut (*class).signals, name, ptr as _)) != 0 {
-            luaA::object_unref(lua, ptr as _);
-        }
+        luaA::signal_disconnect_from_stack(lua, &mut (*class).signals, name, ud);
         ::lua::lua_remove(lua, ud);
     }
 
@@ -1281,10 +2044,8 @@ pub mod luaA {
                                                   class: *mut Class,
                                                   name: *const libc::c_char,
                                                   ud: libc::c_int) {
-        luaA::checkfunction(lua, ud);
-        ::object::signal::signal_connect(&mut (*class).signals,
-                                         name,
-                                         luaA::object_ref(lua, ud))
+        luaA::signal_connect_from_stack(lua, &mut (*class).signals, name, ud,
+                                        false)
     }
 
     pub unsafe fn class_add_property<S>(class: *mut Class,
@@ -1298,18 +2059,65 @@ pub mod luaA {
             name: name.into(),
             new,
             index,
-            new_index
+            new_index,
+            getter: None,
+            setter: None
         };
         (*class).properties.push(prop);
     }
 
+    /// Adds a typed property, see [Property::read_write](../object/property/struct.Property.html#method.read_write).
+    pub unsafe fn class_add_typed_property(class: *mut Class, prop: Property) {
+        (*class).properties.push(prop);
+    }
+
+    /// Sets the typed property of the object at `oud` to the value at
+    /// `idx`, and emits `property::<name>` on it if the value changed.
+    ///
+    /// Raises a Lua error if the value is of the wrong type, so the callers
+    /// must not hold anything that has to be dropped, e.g a lock guard.
+    pub unsafe fn class_property_set(lua: *mut lua_State, prop: *const Property,
+                                     object: *mut Object, oud: libc::c_int,
+                                     idx: libc::c_int) -> libc::c_int {
+        let oud = luaA::absindex(lua, oud);
+        let idx = luaA::absindex(lua, idx);
+        let setter = match (*prop).setter {
+            Some(ref setter) => setter,
+            None => return 0
+        };
+        let message = match setter(lua, object, oud, idx) {
+            Ok(true) => {
+                let signal = CString::new(format!("property::{}", (*prop).name))
+                    .unwrap();
+                luaA::object_emit_signal(lua, oud, signal.as_ptr(), 0);
+                return 0
+            },
+            Ok(false) => return 0,
+            Err(err) => format!("bad value for property '{}': {}",
+                                (*prop).name, err)
+        };
+        /* The error is dropped by now, only the message is left and
+         * luaA::error drops it before raising it */
+        luaA::error(lua, message)
+    }
+
+    /// Raises a Lua error with the message.
+    pub unsafe fn error(lua: *mut lua_State, msg: String) -> libc::c_int {
+        {
+            /* Nothing may be left to drop, as lua_error doesn't return */
+            let msg = CString::new(msg).unwrap_or_default();
+            lua_pushstring(lua, msg.as_ptr());
+        }
+        lua_error(lua)
+    }
+
     pub unsafe fn tomodifiers(lua: *mut lua_State, ud: libc::c_int) -> u16 {
         use xcb::ffi::base::XCB_NONE;
         luaA::checktable(lua, ud);
         let len = luaA::rawlen(lua, ud);
         let mut modifiers = XCB_NONE;
-        for i in 1..(len as i64 + 1) {
-            lua_rawgeti(lua, ud, i);
+        for i in 1..(len as lua_Integer + 1) {
+            ::compat::rawgeti(lua, ud, i);
             let key = luaL_checklstring(lua, -1, NULL as _);
             let key_str = CStr::from_ptr(key).to_str().unwrap();
             modifiers |= super::xutil_key_mask_fromstr(key_str) as _;
@@ -1326,11 +2134,8 @@ pub mod luaA {
         let mut maski = MOD_MASK_SHIFT;
         while maski != MOD_MASK_ANY {
             if (maski & modifiers as u32) != 0 {
-                let modifier = super::xutil_key_mask_tostr(maski);
-                let modifier_c = CString::new(modifier).unwrap();
-                lua_pushlstring(lua, modifier_c.as_ptr() as _, modifier.len());
-                ::std::mem::forget(modifier_c);
-                lua_rawseti(lua, -2, i);
+                super::xutil_key_mask_tostr(maski).push_to_lua(lua);
+                ::compat::rawseti(lua, -2, i);
                 i += 1;
             }
             maski <<= 1;
@@ -1338,22 +2143,6 @@ pub mod luaA {
         return 1;
     }
 
-    pub unsafe fn button_set_button(lua: *mut lua_State, obj: *mut Object)
-                                       -> libc::c_int {
-        let b: *mut ButtonState = obj as _;
-        (*b).button = luaL_checkinteger(lua, -1) as _;
-        luaA::object_emit_signal(lua, -3, c_str!("property::button"), 0);
-        0
-    }
-
-    pub unsafe fn button_set_modifiers(lua: *mut lua_State, obj: *mut Object)
-                                       -> libc::c_int {
-        let b: *mut ButtonState = obj as _;
-        (*b).modifiers = luaA::tomodifiers(lua, -1);
-        luaA::object_emit_signal(lua, -3, c_str!("property::modifiers"), 0);
-        0
-    }
-
     #[allow(unused_variables)]
     pub unsafe fn quit(lua: *mut lua_State) -> libc::c_int {
         // TODO FIXME Kill g_main_loop
@@ -1372,34 +2161,51 @@ pub mod luaA {
     }
 
     pub unsafe fn awesome_connect_signal(lua: *mut lua_State) -> libc::c_int {
-        use object::signal::{signal_connect, GLOBAL_SIGNALS};
+        let weak = luaA::signal_connect_args(lua, 1);
         let name = luaL_checklstring(lua, 1, NULL as _);
-        luaA::checkfunction(lua, 2);
-        let mut global_signals = GLOBAL_SIGNALS.try_lock().unwrap();
-        signal_connect(&mut *global_signals, name, luaA::object_ref(lua, 2));
+        luaA::awesome_connect_signal_from_stack(lua, name, weak);
+        0
+    }
+
+    pub unsafe fn awesome_weak_connect_signal(lua: *mut lua_State) -> libc::c_int {
+        let name = luaL_checklstring(lua, 1, NULL as _);
+        luaA::awesome_connect_signal_from_stack(lua, name, true);
         0
     }
 
+    /// Connects the function at index 2 to the global signal.
+    ///
+    /// The function is checked and referenced before the signals are
+    /// locked, as raising a Lua error with the lock held would leak it.
+    unsafe fn awesome_connect_signal_from_stack(lua: *mut lua_State,
+                                                name: *const libc::c_char,
+                                                weak: bool) {
+        let func = luaA::signal_func_from_stack(lua, 2, weak);
+        let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+        let mut global_signals = ::state::get(lua).signals.try_lock().unwrap();
+        global_signals.prune(lua);
+        global_signals.connect(name, func);
+    }
+
     pub unsafe fn awesome_disconnect_signal(lua: *mut lua_State) -> libc::c_int {
-        use object::signal::{signal_disconnect, GLOBAL_SIGNALS};
         let name = luaL_checklstring(lua, 1, NULL as _);
         luaA::checkfunction(lua, 2);
-        let func = lua_topointer(lua, 2) as *mut libc::c_void;
-        let mut global_signals = GLOBAL_SIGNALS.try_lock().unwrap();
-        if signal_disconnect(&mut *global_signals, name, func) != 0 {
-            luaA::object_unref(lua, func);
+        let ptr = lua_topointer(lua, 2) as *mut libc::c_void;
+        let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+        let func = ::state::get(lua).signals.try_lock().unwrap()
+            .disconnect(&name, ptr);
+        if let Some(func) = func {
+            luaA::signal_func_unref(lua, func);
         }
         0
     }
 
     pub unsafe fn awesome_emit_signal(lua: *mut lua_State) -> libc::c_int {
-        use object::signal::{signal_object_emit, GLOBAL_SIGNALS};
+        use object::signal::signal_global_emit;
         let raw_string = luaL_checklstring(lua, 1, NULL as _);
-        let string = CStr::from_ptr(raw_string);
-        let string = string.to_str().unwrap();
+        let string = CStr::from_ptr(raw_string).to_string_lossy();
         let top = lua_gettop(lua) - 1;
-        let mut global_signals = GLOBAL_SIGNALS.try_lock().unwrap();
-        signal_object_emit(lua, &mut *global_signals, string, top);
+        signal_global_emit(lua, &string, top);
         0
     }
 
@@ -1420,15 +2226,7 @@ pub mod luaA {
 }
 
 use libc;
-
-pub unsafe fn lua_remove(lua: *mut lua_State, idx: libc::c_int) {
-    lua_rotate(lua, idx, -1);
-    lua_pop(lua, 1);
-}
-
-pub unsafe fn lua_insert(lua: *mut lua_State, idx: libc::c_int) {
-    lua_rotate(lua, idx, 1);
-}
+pub use compat::{lua_remove, lua_insert};
 
 pub unsafe fn lua_isnonornil(lua: *mut lua_State, index: libc::c_int) -> bool {
     let ty = lua_type(lua, index);
@@ -0,0 +1,259 @@
+//! Bindings to the Lua C API of Lua 5.1, LuaJIT and Lua 5.2.
+//!
+//! Lua 5.3 is bound by the `lua-sys` crate, this module replaces it when
+//! the crate is built with the `lua51`, `luajit` or `lua52` feature. It has
+//! the same names and signatures as `lua-sys` for what the crate uses, so
+//! that the rest of the crate doesn't depend on the version. What doesn't
+//! exist in every version, e.g `lua_rotate` or `luaL_setfuncs`, is only
+//! bound for the versions that have it and used through
+//! [compat](../compat/index.html).
+//!
+//! The functions defined as macros by `lua.h` and `lauxlib.h` are
+//! functions here, like in `lua-sys`.
+#![allow(non_camel_case_types, non_snake_case)]
+
+use libc::{c_char, c_int, c_void, size_t};
+
+/// An opaque Lua state
+#[repr(C)]
+pub struct lua_State {
+    _private: [u8; 0]
+}
+
+pub type lua_Number = f64;
+pub type lua_Integer = ::libc::ptrdiff_t;
+pub type lua_CFunction = Option<unsafe extern "C" fn(*mut lua_State) -> c_int>;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct luaL_Reg {
+    pub name: *const c_char,
+    pub func: lua_CFunction
+}
+
+pub const LUA_IDSIZE: usize = 60;
+
+#[cfg(lua_api = "51")]
+#[repr(C)]
+pub struct lua_Debug {
+    pub event: c_int,
+    pub name: *const c_char,
+    pub namewhat: *const c_char,
+    pub what: *const c_char,
+    pub source: *const c_char,
+    pub currentline: c_int,
+    pub nups: c_int,
+    pub linedefined: c_int,
+    pub lastlinedefined: c_int,
+    pub short_src: [c_char; LUA_IDSIZE],
+    i_ci: c_int
+}
+
+#[cfg(lua_api = "52")]
+#[repr(C)]
+pub struct lua_Debug {
+    pub event: c_int,
+    pub name: *const c_char,
+    pub namewhat: *const c_char,
+    pub what: *const c_char,
+    pub source: *const c_char,
+    pub currentline: c_int,
+    pub linedefined: c_int,
+    pub lastlinedefined: c_int,
+    pub nups: u8,
+    pub nparams: u8,
+    pub isvararg: c_char,
+    pub istailcall: c_char,
+    pub short_src: [c_char; LUA_IDSIZE],
+    i_ci: *mut c_void
+}
+
+pub const LUA_MULTRET: c_int = -1;
+pub const LUA_REFNIL: c_int = -1;
+pub const LUA_NOREF: c_int = -2;
+
+#[cfg(lua_api = "51")]
+pub const LUA_REGISTRYINDEX: c_int = -10000;
+#[cfg(lua_api = "51")]
+pub const LUA_ENVIRONINDEX: c_int = -10001;
+#[cfg(lua_api = "51")]
+pub const LUA_GLOBALSINDEX: c_int = -10002;
+
+/* -LUAI_MAXSTACK - 1000 */
+#[cfg(lua_api = "52")]
+pub const LUA_REGISTRYINDEX: c_int = -1001000;
+#[cfg(lua_api = "52")]
+pub const LUA_RIDX_GLOBALS: lua_Integer = 2;
+
+pub const LUA_TNONE: c_int = -1;
+pub const LUA_TNIL: u32 = 0;
+pub const LUA_TBOOLEAN: u32 = 1;
+pub const LUA_TLIGHTUSERDATA: u32 = 2;
+pub const LUA_TNUMBER: u32 = 3;
+pub const LUA_TSTRING: u32 = 4;
+pub const LUA_TTABLE: u32 = 5;
+pub const LUA_TFUNCTION: u32 = 6;
+pub const LUA_TUSERDATA: u32 = 7;
+pub const LUA_TTHREAD: u32 = 8;
+
+pub const LUA_YIELD: u32 = 1;
+pub const LUA_ERRRUN: u32 = 2;
+pub const LUA_ERRSYNTAX: u32 = 3;
+pub const LUA_ERRMEM: u32 = 4;
+
+extern "C" {
+    pub fn lua_close(L: *mut lua_State);
+
+    pub fn lua_gettop(L: *mut lua_State) -> c_int;
+    pub fn lua_settop(L: *mut lua_State, idx: c_int);
+    pub fn lua_pushvalue(L: *mut lua_State, idx: c_int);
+    pub fn lua_remove(L: *mut lua_State, idx: c_int);
+    pub fn lua_insert(L: *mut lua_State, idx: c_int);
+    pub fn lua_replace(L: *mut lua_State, idx: c_int);
+
+    pub fn lua_isnumber(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_type(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_typename(L: *mut lua_State, tp: c_int) -> *const c_char;
+    pub fn lua_toboolean(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_tolstring(L: *mut lua_State, idx: c_int, len: *mut size_t)
+                         -> *const c_char;
+    pub fn lua_touserdata(L: *mut lua_State, idx: c_int) -> *mut c_void;
+    pub fn lua_topointer(L: *mut lua_State, idx: c_int) -> *const c_void;
+
+    pub fn lua_pushnil(L: *mut lua_State);
+    pub fn lua_pushnumber(L: *mut lua_State, n: lua_Number);
+    pub fn lua_pushinteger(L: *mut lua_State, n: lua_Integer);
+    pub fn lua_pushfstring(L: *mut lua_State, fmt: *const c_char, ...)
+                           -> *const c_char;
+    pub fn lua_pushcclosure(L: *mut lua_State, f: lua_CFunction, n: c_int);
+    pub fn lua_pushboolean(L: *mut lua_State, b: c_int);
+    pub fn lua_pushlightuserdata(L: *mut lua_State, p: *mut c_void);
+
+    pub fn lua_gettable(L: *mut lua_State, idx: c_int);
+    pub fn lua_getfield(L: *mut lua_State, idx: c_int, k: *const c_char);
+    pub fn lua_rawget(L: *mut lua_State, idx: c_int);
+    pub fn lua_rawgeti(L: *mut lua_State, idx: c_int, n: c_int);
+    pub fn lua_createtable(L: *mut lua_State, narr: c_int, nrec: c_int);
+    pub fn lua_newuserdata(L: *mut lua_State, sz: size_t) -> *mut c_void;
+    pub fn lua_getmetatable(L: *mut lua_State, objindex: c_int) -> c_int;
+
+    pub fn lua_setfield(L: *mut lua_State, idx: c_int, k: *const c_char);
+    pub fn lua_rawset(L: *mut lua_State, idx: c_int);
+    pub fn lua_rawseti(L: *mut lua_State, idx: c_int, n: c_int);
+    pub fn lua_setmetatable(L: *mut lua_State, objindex: c_int) -> c_int;
+
+    pub fn lua_error(L: *mut lua_State) -> c_int;
+    pub fn lua_next(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_concat(L: *mut lua_State, n: c_int);
+
+    pub fn lua_getstack(L: *mut lua_State, level: c_int, ar: *mut lua_Debug)
+                        -> c_int;
+    pub fn lua_getinfo(L: *mut lua_State, what: *const c_char,
+                       ar: *mut lua_Debug) -> c_int;
+    pub fn lua_setupvalue(L: *mut lua_State, funcindex: c_int, n: c_int)
+                          -> *const c_char;
+
+    pub fn luaL_newmetatable(L: *mut lua_State, tname: *const c_char) -> c_int;
+    pub fn luaL_argerror(L: *mut lua_State, numarg: c_int,
+                         extramsg: *const c_char) -> c_int;
+    pub fn luaL_checklstring(L: *mut lua_State, numArg: c_int, l: *mut size_t)
+                             -> *const c_char;
+    pub fn luaL_optnumber(L: *mut lua_State, nArg: c_int, def: lua_Number)
+                          -> lua_Number;
+    pub fn luaL_checkstack(L: *mut lua_State, sz: c_int, msg: *const c_char);
+    pub fn luaL_checktype(L: *mut lua_State, narg: c_int, t: c_int);
+    pub fn luaL_error(L: *mut lua_State, fmt: *const c_char, ...) -> c_int;
+    pub fn luaL_ref(L: *mut lua_State, t: c_int) -> c_int;
+    pub fn luaL_unref(L: *mut lua_State, t: c_int, r: c_int);
+    pub fn luaL_newstate() -> *mut lua_State;
+    pub fn luaL_openlibs(L: *mut lua_State);
+}
+
+#[cfg(lua_api = "51")]
+extern "C" {
+    pub fn lua_tonumber(L: *mut lua_State, idx: c_int) -> lua_Number;
+    pub fn lua_tointeger(L: *mut lua_State, idx: c_int) -> lua_Integer;
+    pub fn lua_objlen(L: *mut lua_State, idx: c_int) -> size_t;
+    pub fn lua_pushlstring(L: *mut lua_State, s: *const c_char, l: size_t);
+    pub fn lua_pushstring(L: *mut lua_State, s: *const c_char);
+    pub fn lua_getfenv(L: *mut lua_State, idx: c_int);
+    pub fn lua_setfenv(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_call(L: *mut lua_State, nargs: c_int, nresults: c_int);
+    pub fn lua_pcall(L: *mut lua_State, nargs: c_int, nresults: c_int,
+                     errfunc: c_int) -> c_int;
+    pub fn luaL_register(L: *mut lua_State, libname: *const c_char,
+                         l: *const luaL_Reg);
+    pub fn luaL_loadbuffer(L: *mut lua_State, buff: *const c_char, sz: size_t,
+                           name: *const c_char) -> c_int;
+    pub fn luaL_loadfile(L: *mut lua_State, filename: *const c_char) -> c_int;
+}
+
+#[cfg(lua_api = "52")]
+extern "C" {
+    pub fn lua_tonumberx(L: *mut lua_State, idx: c_int, isnum: *mut c_int)
+                         -> lua_Number;
+    pub fn lua_tointegerx(L: *mut lua_State, idx: c_int, isnum: *mut c_int)
+                          -> lua_Integer;
+    pub fn lua_rawlen(L: *mut lua_State, idx: c_int) -> size_t;
+    pub fn lua_pushlstring(L: *mut lua_State, s: *const c_char, l: size_t)
+                           -> *const c_char;
+    pub fn lua_pushstring(L: *mut lua_State, s: *const c_char) -> *const c_char;
+    pub fn lua_getglobal(L: *mut lua_State, var: *const c_char);
+    pub fn lua_setglobal(L: *mut lua_State, var: *const c_char);
+    pub fn lua_getuservalue(L: *mut lua_State, idx: c_int);
+    pub fn lua_setuservalue(L: *mut lua_State, idx: c_int);
+    pub fn lua_callk(L: *mut lua_State, nargs: c_int, nresults: c_int,
+                     ctx: c_int, k: lua_CFunction);
+    pub fn lua_pcallk(L: *mut lua_State, nargs: c_int, nresults: c_int,
+                      errfunc: c_int, ctx: c_int, k: lua_CFunction) -> c_int;
+    pub fn luaL_setfuncs(L: *mut lua_State, l: *const luaL_Reg, nup: c_int);
+    pub fn luaL_loadbufferx(L: *mut lua_State, buff: *const c_char, sz: size_t,
+                            name: *const c_char, mode: *const c_char) -> c_int;
+    pub fn luaL_loadfilex(L: *mut lua_State, filename: *const c_char,
+                          mode: *const c_char) -> c_int;
+    pub fn luaL_traceback(L: *mut lua_State, L1: *mut lua_State,
+                          msg: *const c_char, level: c_int);
+}
+
+/* The macros of lua.h and lauxlib.h */
+
+pub unsafe fn lua_pop(L: *mut lua_State, n: c_int) {
+    lua_settop(L, -n - 1)
+}
+
+pub unsafe fn lua_newtable(L: *mut lua_State) {
+    lua_createtable(L, 0, 0)
+}
+
+pub unsafe fn lua_pushcfunction(L: *mut lua_State, f: lua_CFunction) {
+    lua_pushcclosure(L, f, 0)
+}
+
+pub unsafe fn luaL_checkstring(L: *mut lua_State, n: c_int) -> *const c_char {
+    luaL_checklstring(L, n, ::std::ptr::null_mut())
+}
+
+#[cfg(lua_api = "51")]
+pub unsafe fn lua_getglobal(L: *mut lua_State, var: *const c_char) {
+    lua_getfield(L, LUA_GLOBALSINDEX, var)
+}
+
+#[cfg(lua_api = "51")]
+pub unsafe fn lua_setglobal(L: *mut lua_State, var: *const c_char) {
+    lua_setfield(L, LUA_GLOBALSINDEX, var)
+}
+
+#[cfg(lua_api = "52")]
+pub unsafe fn lua_tonumber(L: *mut lua_State, idx: c_int) -> lua_Number {
+    lua_tonumberx(L, idx, ::std::ptr::null_mut())
+}
+
+#[cfg(lua_api = "52")]
+pub unsafe fn lua_tointeger(L: *mut lua_State, idx: c_int) -> lua_Integer {
+    lua_tointegerx(L, idx, ::std::ptr::null_mut())
+}
+
+#[cfg(lua_api = "52")]
+pub unsafe fn lua_call(L: *mut lua_State, nargs: c_int, nresults: c_int) {
+    lua_callk(L, nargs, nresults, 0, None)
+}
@@ -3,7 +3,7 @@
 use libc;
 use lua_sys::*;
 use std::cell::Cell;
-use super::signal::Signal;
+use super::signal::Signals;
 use super::property::Property;
 
 /// Method that allocates new objects for the class.
@@ -17,14 +17,16 @@ pub type CheckerF = unsafe fn(*mut Object) -> bool;
 
 #[repr(C)]
 pub struct Object {
-    pub signals: Vec<Signal>
+    pub signals: Signals
 }
 
 /// A Lua object that is a class.
 #[repr(C)]
 pub struct Class {
     pub name: String,
-    pub signals: Vec<Signal>,
+    pub signals: Signals,
+    /// Signals declared with `add_signal`, sorted by name
+    pub declared_signals: Vec<String>,
     pub parent: *mut Class,
     /// Method that allocates new objects for the class.
     pub allocator: Option<AllocatorF>,
@@ -37,7 +39,9 @@ pub struct Class {
     pub instances: Cell<i32>,
     pub tostring: Option<PropF>,
     pub index_miss_handler: libc::c_int,
-    pub newindex_miss_handler: libc::c_int
+    pub newindex_miss_handler: libc::c_int,
+    /// Signal emitted on an object when it is invalidated, e.g `unmanage`
+    pub removed_signal: String
 }
 
 unsafe impl Send for Class {}
@@ -47,7 +51,8 @@ impl Default for Class {
     fn default() -> Self {
         Class {
             name: String::new(),
-            signals: Vec::new(),
+            signals: Signals::new(),
+            declared_signals: Vec::new(),
             parent: 0 as _,
             allocator: None,
             collector: None,
@@ -58,7 +63,17 @@ impl Default for Class {
             instances: Cell::new(0),
             tostring: None,
             index_miss_handler: 0,
-            newindex_miss_handler: 0
+            newindex_miss_handler: 0,
+            removed_signal: "removed".into()
+        }
+    }
+}
+
+impl Class {
+    /// Declares a signal of the class, as `add_signal` does.
+    pub fn declare_signal(&mut self, name: String) {
+        if let Err(index) = self.declared_signals.binary_search(&name) {
+            self.declared_signals.insert(index, name);
         }
     }
 }
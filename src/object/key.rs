@@ -1,9 +1,9 @@
-use ::object::Signal;
+use ::object::Signals;
 use ::xcb_cursor_sys::{xcb_keycode_t, xcb_keysym_t};
 
 #[repr(C)]
 pub struct KeyState {
-    pub signals: Vec<Signal>,
+    pub signals: Signals,
     pub modifiers: u16,
     pub keysym: xcb_keysym_t,
     pub keycode: xcb_keycode_t
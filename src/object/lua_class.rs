@@ -0,0 +1,371 @@
+//! Safe interface to define new Lua classes from plain Rust structs.
+//!
+//! The built in classes (`client`, `screen`, ...) are defined with the
+//! `LUA_OBJECT_FUNCS!` and `LUA_CLASS_FUNCS!` macros and `#[repr(C)]`
+//! structs. Implementing [LuaClass](trait.LuaClass.html) instead generates
+//! the allocator, collector, properties and signal functions of the class,
+//! so the struct can be anything:
+//!
+//! ```rust,ignore
+//! #[derive(Default)]
+//! struct LayerSurface {
+//!     layer: u32
+//! }
+//!
+//! impl LuaClass for LayerSurface {
+//!     fn name() -> &'static str {
+//!         "layer_surface"
+//!     }
+//!
+//!     fn properties() -> Vec<ClassProperty<Self>> {
+//!         vec![ClassProperty::read_write("layer",
+//!                                        |surface: &Self| surface.layer,
+//!                                        |surface: &mut Self, layer| surface.layer = layer)]
+//!     }
+//! }
+//!
+//! lua_class::register::<LayerSurface>(&awesome.lua).unwrap();
+//! ```
+
+use lua_sys::*;
+use libc;
+use std::any::TypeId;
+use std::ffi::{CStr, CString};
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::RwLock;
+use ::convert::{ToLua, FromLua};
+use ::lua::{Lua, LuaErr, FFIErr, RustFn, luaA};
+use ::state;
+use super::class::{Class, Object};
+use super::property::Property;
+use super::signal::Signals;
+
+/// A Rust struct that is exposed to Lua as a class.
+pub trait LuaClass: Default + 'static {
+    /// Name of the class in Lua, e.g `"layer_surface"`.
+    fn name() -> &'static str;
+
+    /// The properties of the objects of the class.
+    fn properties() -> Vec<ClassProperty<Self>> {
+        Vec::new()
+    }
+
+    /// Functions of the class table, next to `connect_signal`,
+    /// `instances`, ...
+    fn class_methods() -> Vec<(String, RustFn)> {
+        Vec::new()
+    }
+
+    /// Methods of the objects, called as `object:method()`.
+    ///
+    /// The object is at index 1, use [to_object](fn.to_object.html) to
+    /// get it.
+    fn object_methods() -> Vec<(String, RustFn)> {
+        Vec::new()
+    }
+
+    /// Called when the object is garbage collected, right before it is
+    /// dropped.
+    fn collect(&mut self) {}
+}
+
+/// A typed [Property](../property/struct.Property.html) of the objects of a
+/// `LuaClass`.
+///
+/// Setting it from Lua emits `property::<name>` on the object when the
+/// value changes, and assigning to a read-only property raises an error.
+pub struct ClassProperty<T> {
+    property: Property,
+    _class: PhantomData<T>
+}
+
+impl<T: LuaClass> ClassProperty<T> {
+    /// A property that can only be read from Lua.
+    pub fn read_only<S, V, G>(name: S, get: G) -> Self
+        where S: Into<String>,
+              V: ToLua,
+              G: Fn(&T) -> V + 'static
+    {
+        ClassProperty {
+            property: Property::read_only(name, move |instance: &Instance<T>| {
+                get(&instance.data)
+            }),
+            _class: PhantomData
+        }
+    }
+
+    /// A property that can be read and set from Lua, including in the
+    /// table given to the constructor of the class.
+    pub fn read_write<S, V, G, F>(name: S, get: G, set: F) -> Self
+        where S: Into<String>,
+              V: ToLua + FromLua + PartialEq,
+              G: Fn(&T) -> V + 'static,
+              F: Fn(&mut T, V) + 'static
+    {
+        ClassProperty {
+            property: Property::read_write(
+                name,
+                move |instance: &Instance<T>| get(&instance.data),
+                move |instance: &mut Instance<T>, value| {
+                    set(&mut instance.data, value)
+                }),
+            _class: PhantomData
+        }
+    }
+}
+
+/// The layout of the objects of a `LuaClass`, which like every object must
+/// start with its signals.
+#[repr(C)]
+struct Instance<T> {
+    signals: Signals,
+    data: T
+}
+
+/// Sets up the class in the Lua state, making it available as a global
+/// named [T::name](trait.LuaClass.html#tymethod.name).
+pub fn register<T: LuaClass>(lua: &Lua) -> Result<(), LuaErr> {
+    let name = T::name();
+    let c_name = CString::new(name)
+        .map_err(|_| LuaErr::EvalFFI(FFIErr::NullByte(name.into())))?;
+    let type_id = TypeId::of::<T>();
+    unsafe {
+        let state = state::get(lua.0);
+        let (lock, class_ptr) = {
+            let mut classes = state.custom_classes.write().unwrap();
+            if classes.contains_key(&type_id) {
+                return Err(LuaErr::AlreadyDefined(name.into()))
+            }
+            let class = CustomClass::new();
+            let pointers = (&*class.lock as *const RwLock<Class>, class.class);
+            classes.insert(type_id, class);
+            pointers
+        };
+        let methods = [
+            reg(c_str!("connect_signal"), class_connect_signal::<T>),
+            reg(c_str!("disconnect_signal"), class_disconnect_signal::<T>),
+            reg(c_str!("emit_signal"), class_emit_signal::<T>),
+            reg(c_str!("instances"), class_instances::<T>),
+            reg(c_str!("set_index_miss_handler"), class_set_index_miss_handler::<T>),
+            reg(c_str!("set_newindex_miss_handler"),
+                class_set_newindex_miss_handler::<T>),
+            reg(c_str!("__call"), class_call::<T>),
+            luaL_Reg { name: ptr::null(), func: None }
+        ];
+        let meta = [
+            reg(c_str!("__tostring"), luaA::object_tostring),
+            reg(c_str!("connect_signal"), luaA::object_connect_signal_simple),
+            reg(c_str!("disconnect_signal"), luaA::object_disconnect_signal_simple),
+            reg(c_str!("emit_signal"), luaA::object_emit_signal_simple),
+            reg(c_str!("__index"), luaA::class_index),
+            reg(c_str!("__newindex"), luaA::class_newindex),
+            luaL_Reg { name: ptr::null(), func: None }
+        ];
+        luaA::class_setup(lua.0, class_ptr, lock, c_name.as_ptr(),
+                          ptr::null_mut(),
+                          allocate::<T>, Some(collect::<T>), None,
+                          Some(luaA::class_index_miss_property),
+                          Some(luaA::class_newindex_miss_property),
+                          &methods, &meta);
+        for property in T::properties() {
+            luaA::class_add_typed_property(class_ptr, property.property);
+        }
+
+        /* Object methods go in the object metatable, class methods in the
+         * class table */
+        lua_pushlightuserdata(lua.0, class_ptr as _);
+        lua_rawget(lua.0, LUA_REGISTRYINDEX);
+        set_functions(lua, T::object_methods())?;
+        lua_pop(lua.0, 1);
+        lua_getglobal(lua.0, c_name.as_ptr());
+        set_functions(lua, T::class_methods())?;
+        lua_pop(lua.0, 1);
+    }
+    Ok(())
+}
+
+/// Creates a new object of the class with the given value, and pushes it
+/// on to the stack.
+///
+/// Like objects created from Lua, the `new` signal is emitted on the class.
+///
+/// # Panics
+/// If the class has not been [registered](fn.register.html).
+pub fn push_object<T: LuaClass>(lua: &Lua, value: T) -> libc::c_int {
+    unsafe {
+        new_instance(lua.0, value);
+    }
+    1
+}
+
+/// Gets the object of the class at the given stack index, or `None` if the
+/// value there is not one.
+///
+/// # Safety
+/// The object is owned by Lua and the lifetime of the reference is picked
+/// by the caller, so the caller must make sure that:
+///
+/// * The object outlives the reference. Keep the value on the stack, or
+///   referenced from Lua, while the reference is used: once nothing
+///   references it the object may be collected and the reference dangles.
+/// * The reference is the only one to the object while it is used. Don't
+///   call `to_object` on the same object again, and don't run Lua code
+///   (e.g with `Lua::eval` or by emitting a signal) that could read or
+///   set its properties, which borrows the object too.
+/// * The reference isn't used after the Lua state is closed.
+///
+/// # Panics
+/// If the class has not been [registered](fn.register.html).
+pub unsafe fn to_object<'a, T: LuaClass>(lua: &Lua, idx: libc::c_int)
+                                         -> Option<&'a mut T> {
+    let instance = luaA::toudata(lua.0, idx, class_ptr::<T>(lua.0))
+        as *mut Instance<T>;
+    if instance.is_null() {
+        None
+    } else {
+        Some(&mut (*instance).data)
+    }
+}
+
+fn reg(name: *const libc::c_char,
+       func: unsafe extern "C" fn(*mut lua_State) -> libc::c_int) -> luaL_Reg {
+    luaL_Reg { name, func: Some(func) }
+}
+
+/// Sets the functions as fields of the table on the top of the stack.
+unsafe fn set_functions(lua: &Lua, functions: Vec<(String, RustFn)>)
+                        -> Result<(), LuaErr> {
+    for (name, function) in functions {
+        let c_name = CString::new(name.clone())
+            .map_err(|_| LuaErr::EvalFFI(FFIErr::NullByte(name)))?;
+        lua.push_function(move |lua| function(lua));
+        lua_setfield(lua.0, -2, c_name.as_ptr());
+    }
+    Ok(())
+}
+
+/// A class registered with [register](fn.register.html).
+///
+/// The lock is boxed so that it doesn't move when the map of the classes
+/// grows, which keeps the pointers to it valid.
+pub struct CustomClass {
+    lock: Box<RwLock<Class>>,
+    /// The `Class` in the lock, to use it without locking it like the
+    /// built in classes are
+    class: *mut Class
+}
+
+impl CustomClass {
+    fn new() -> Self {
+        let lock = Box::new(RwLock::new(Class::default()));
+        let class = &*lock.try_read().unwrap() as *const Class as *mut Class;
+        CustomClass { lock, class }
+    }
+}
+
+/// The lock and the `Class` of `T`.
+///
+/// Classes are never removed from the state, so the pointers are valid for
+/// as long as it is. They are copied out so that the map of the classes is
+/// not locked while the class is used, which may raise a Lua error.
+unsafe fn custom_class<T: LuaClass>(lua: *mut lua_State)
+                                    -> (*const RwLock<Class>, *mut Class) {
+    let classes = state::get(lua).custom_classes.read().unwrap();
+    match classes.get(&TypeId::of::<T>()) {
+        Some(class) => (&*class.lock as *const RwLock<Class>, class.class),
+        None => panic!("Lua class {} has not been registered", T::name())
+    }
+}
+
+/// The lock of the `Class` of `T`.
+unsafe fn class_lock<T: LuaClass>(lua: *mut lua_State) -> *const RwLock<Class> {
+    custom_class::<T>(lua).0
+}
+
+/// The `Class` of `T`, without locking it.
+unsafe fn class_ptr<T: LuaClass>(lua: *mut lua_State) -> *mut Class {
+    custom_class::<T>(lua).1
+}
+
+unsafe extern fn allocate<T: LuaClass>(lua: *mut lua_State) -> *mut Object {
+    new_instance(lua, T::default())
+}
+
+/// Pushes a new object holding `data`, and emits `new` on the class.
+unsafe fn new_instance<T: LuaClass>(lua: *mut lua_State, data: T) -> *mut Object {
+    let p = lua_newuserdata(lua, ::std::mem::size_of::<Instance<T>>())
+        as *mut Instance<T>;
+    ptr::write(p, Instance { signals: Signals::new(), data });
+    /* Not locked, as the handlers of the new signal may use the class */
+    let class = class_ptr::<T>(lua);
+    (*class).instances.set((*class).instances.get() + 1);
+    luaA::settype(lua, class);
+    lua_newtable(lua);
+    lua_newtable(lua);
+    lua_setmetatable(lua, -2);
+    lua_newtable(lua);
+    lua_setfield(lua, -2, c_str!("data"));
+    luaA::setuservalue(lua, -2);
+    lua_pushvalue(lua, -1);
+    luaA::class_emit_signal(lua, class, c_str!("new"), 1);
+    p as _
+}
+
+unsafe fn collect<T: LuaClass>(object: *mut Object) {
+    let instance = object as *mut Instance<T>;
+    (*instance).data.collect();
+    ptr::drop_in_place(instance);
+}
+
+unsafe extern fn class_connect_signal<T: LuaClass>(lua: *mut lua_State)
+                                                   -> libc::c_int {
+    let weak = luaA::signal_connect_args(lua, 1);
+    let name = luaL_checklstring(lua, 1, ptr::null_mut());
+    /* Checked before locking, as a Lua error would keep it locked */
+    let func = luaA::signal_func_from_stack(lua, 2, weak);
+    let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+    let mut class = (*class_lock::<T>(lua)).try_write().unwrap();
+    class.signals.prune(lua);
+    class.signals.connect(name, func);
+    0
+}
+
+unsafe extern fn class_disconnect_signal<T: LuaClass>(lua: *mut lua_State)
+                                                      -> libc::c_int {
+    let name = luaL_checklstring(lua, 1, ptr::null_mut());
+    luaA::checkfunction(lua, 2);
+    let mut class = (*class_lock::<T>(lua)).try_write().unwrap();
+    luaA::class_disconnect_signal_from_stack(lua, &mut *class, name, 2);
+    0
+}
+
+unsafe extern fn class_emit_signal<T: LuaClass>(lua: *mut lua_State)
+                                                -> libc::c_int {
+    let name = luaL_checklstring(lua, 1, ptr::null_mut());
+    luaA::class_emit_signal(lua, class_ptr::<T>(lua), name, lua_gettop(lua) - 1);
+    0
+}
+
+unsafe extern fn class_instances<T: LuaClass>(lua: *mut lua_State)
+                                              -> libc::c_int {
+    let class = (*class_lock::<T>(lua)).try_read().unwrap();
+    lua_pushinteger(lua, class.instances.get() as lua_Integer);
+    1
+}
+
+unsafe extern fn class_set_index_miss_handler<T: LuaClass>(lua: *mut lua_State)
+                                                           -> libc::c_int {
+    let mut class = (*class_lock::<T>(lua)).try_write().unwrap();
+    luaA::registerfct(lua, 1, &mut class.index_miss_handler)
+}
+
+unsafe extern fn class_set_newindex_miss_handler<T: LuaClass>(lua: *mut lua_State)
+                                                              -> libc::c_int {
+    let mut class = (*class_lock::<T>(lua)).try_write().unwrap();
+    luaA::registerfct(lua, 1, &mut class.newindex_miss_handler)
+}
+
+unsafe extern fn class_call<T: LuaClass>(lua: *mut lua_State) -> libc::c_int {
+    luaA::class_new(lua, &*class_lock::<T>(lua))
+}
@@ -3,8 +3,10 @@ pub mod property;
 pub mod signal;
 pub mod window;
 pub mod key;
+pub mod lua_class;
 
 pub use self::property::Property;
-pub use self::signal::{Signal, GLOBAL_SIGNALS, signal_object_emit};
-pub use self::window::WindowState;
+pub use self::signal::{Signals, SignalFunc, signal_object_emit};
+pub use self::window::{WindowState, WindowType};
 pub use self::key::KeyState;
+pub use self::lua_class::{LuaClass, ClassProperty};
@@ -1,5 +1,23 @@
 //! Definitions for the equivalent of `lua_class-property`
-use super::class::PropF;
+//!
+//! Besides the raw `PropF` callbacks of the C lib, a property can be typed:
+//! declared with a getter, an optional setter and the Rust type of its
+//! value. Setting a typed property checks the type of the Lua value, stores
+//! it and emits `property::<name>` on the object if it changed. Assigning to
+//! a property without a setter raises an error.
+use lua_sys::*;
+use libc;
+use std::rc::Rc;
+use ::convert::{ToLua, FromLua};
+use ::lua::LuaErr;
+use super::class::{Object, PropF};
+
+/// Pushes the value of a typed property of the object.
+pub type PropGetter = Box<Fn(*mut lua_State, *mut Object) -> libc::c_int>;
+/// Sets a typed property of the object, which is at the first stack index,
+/// to the value at the second one, returning whether the value changed.
+pub type PropSetter = Box<Fn(*mut lua_State, *mut Object, libc::c_int, libc::c_int)
+                             -> Result<bool, LuaErr>>;
 
 pub struct Property {
     /// Name of the property
@@ -9,5 +27,85 @@ pub struct Property {
     /// Callback called when the property is found in object __index
     pub index: Option<PropF>,
     /// Callback called when the property is found in object __newindex
-    pub new_index: Option<PropF>
+    pub new_index: Option<PropF>,
+    /// Getter of a typed property, used instead of `index`
+    pub getter: Option<PropGetter>,
+    /// Setter of a typed property, used instead of `new` and `new_index`
+    pub setter: Option<PropSetter>
+}
+
+impl Property {
+    /// A typed property of the objects of type `O` that can only be read.
+    pub fn read_only<S, O, V, G>(name: S, get: G) -> Self
+        where S: Into<String>,
+              O: 'static,
+              V: ToLua,
+              G: Fn(&O) -> V + 'static
+    {
+        Property {
+            name: name.into(),
+            new: None,
+            index: None,
+            new_index: None,
+            getter: Some(Box::new(move |lua, object| unsafe {
+                get(&*(object as *const O)).push_to_lua(lua)
+            })),
+            setter: None
+        }
+    }
+
+    /// A typed property of the objects of type `O` that can be read and
+    /// set, with values of type `V`.
+    pub fn read_write<S, O, V, G, F>(name: S, get: G, set: F) -> Self
+        where S: Into<String>,
+              O: 'static,
+              V: ToLua + FromLua + PartialEq,
+              G: Fn(&O) -> V + 'static,
+              F: Fn(&mut O, V) + 'static
+    {
+        let get = Rc::new(get);
+        let old = get.clone();
+        Property {
+            name: name.into(),
+            new: None,
+            index: None,
+            new_index: None,
+            getter: Some(Box::new(move |lua, object| unsafe {
+                get(&*(object as *const O)).push_to_lua(lua)
+            })),
+            setter: Some(Box::new(move |lua, object, _oud, idx| unsafe {
+                let value = V::from_lua(lua, idx)?;
+                let object = &mut *(object as *mut O);
+                if old(object) == value {
+                    return Ok(false)
+                }
+                set(object, value);
+                Ok(true)
+            }))
+        }
+    }
+
+    /// Calls `changed` with the object and the name of the property each
+    /// time the setter changes the value, before `property::<name>` is
+    /// emitted.
+    pub fn on_change<F>(mut self, changed: F) -> Self
+        where F: Fn(*mut lua_State, *mut Object, &str) + 'static
+    {
+        if let Some(setter) = self.setter.take() {
+            let name = self.name.clone();
+            self.setter = Some(Box::new(move |lua, object, oud, idx| {
+                let result = setter(lua, object, oud, idx);
+                if let Ok(true) = result {
+                    changed(lua, object, &name);
+                }
+                result
+            }));
+        }
+        self
+    }
+
+    /// Can the property be assigned to?
+    pub fn is_writable(&self) -> bool {
+        self.setter.is_some() || self.new_index.is_some()
+    }
 }
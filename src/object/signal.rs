@@ -1,108 +1,232 @@
+//! Signals of objects, classes and of the `awesome` global.
+//!
+//! The functions connected to a signal are kept by the name of the signal,
+//! in the order they were connected, which is the order they are called in.
+
 use libc::c_void;
-use std::cmp::{Eq, PartialEq};
-use std::collections::hash_map::DefaultHasher;
-use std::hash::Hasher;
-use std::sync::Mutex;
-use std::ffi::CStr;
-
-lazy_static! {
-    pub static ref GLOBAL_SIGNALS: Mutex<Vec<Signal>> = Mutex::new(vec![]);
-}
+use std::collections::BTreeMap;
 
-pub struct SignalFunc(*mut c_void);
+/// A function connected to a signal.
+pub struct SignalFunc {
+    /// Pointer of the function, its key where it is referenced
+    pub ptr: *mut c_void,
+    /// Key of the function in the table of weak handlers if it is held
+    /// weakly, see `luaA::object_weak_ref`
+    pub weak: Option<libc::c_int>
+}
 
 unsafe impl Send for SignalFunc {}
 unsafe impl Sync for SignalFunc {}
 
-pub struct Signal {
-    /// Unique ID for the signal
-    pub id: u64,
-    /// The functions to call for this signal
-    pub sigfuncs: Vec<SignalFunc>
-}
+impl SignalFunc {
+    /// A function that is referenced, e.g with `object_ref`.
+    pub fn strong(ptr: *mut c_void) -> Self {
+        SignalFunc { ptr, weak: None }
+    }
 
-unsafe impl Send for Signal {}
-unsafe impl Sync for Signal {}
+    /// A function that is only held weakly, under `key`.
+    pub fn weak(ptr: *mut c_void, key: libc::c_int) -> Self {
+        SignalFunc { ptr, weak: Some(key) }
+    }
 
-impl PartialEq for Signal {
-    fn eq(&self, other: &Signal) -> bool {
-        self.id == other.id
+    /// Pushes the function on to the stack, using `push_strong` to push it
+    /// if it is referenced.
+    ///
+    /// Returns false, and pushes nothing, if the function was held weakly
+    /// and has been garbage collected.
+    pub unsafe fn push<F>(&self, lua: *mut lua_State, push_strong: F) -> bool
+        where F: FnOnce(*mut c_void)
+    {
+        match self.weak {
+            None => push_strong(self.ptr),
+            Some(key) => {
+                luaA::object_weak_push(lua, key);
+                if lua_type(lua, -1) != LUA_TFUNCTION as i32 {
+                    lua_pop(lua, 1);
+                    return false
+                }
+            }
+        }
+        true
     }
 }
 
-impl Eq for Signal {}
+/// The signals of an object, a class or the `awesome` global: the functions
+/// connected to each signal, by name.
+///
+/// A signal is only listed while it has functions connected to it.
+#[derive(Default)]
+pub struct Signals {
+    handlers: BTreeMap<String, Vec<SignalFunc>>
+}
+
+unsafe impl Send for Signals {}
+unsafe impl Sync for Signals {}
+
+impl Signals {
+    pub fn new() -> Self {
+        Signals::default()
+    }
+
+    /// Connects the function to the signal, after those already connected.
+    pub fn connect<S: Into<String>>(&mut self, name: S, func: SignalFunc) {
+        self.handlers.entry(name.into()).or_insert_with(Vec::new).push(func);
+    }
+
+    /// Disconnects the function with the given pointer from the signal.
+    ///
+    /// Only the first matching function is removed, if it was connected
+    /// several times. It is returned so the caller can drop its reference.
+    pub fn disconnect(&mut self, name: &str, ptr: *const c_void)
+                      -> Option<SignalFunc> {
+        let func = {
+            let funcs = self.handlers.get_mut(name)?;
+            let index = funcs.iter().position(|func| func.ptr as *const _ == ptr)?;
+            funcs.remove(index)
+        };
+        if self.handlers.get(name).map(Vec::is_empty).unwrap_or(false) {
+            self.handlers.remove(name);
+        }
+        Some(func)
+    }
+
+    /// The functions connected to the signal, in the order they are called.
+    pub fn handlers(&self, name: &str) -> &[SignalFunc] {
+        self.handlers.get(name).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// How many functions are connected to the signal.
+    ///
+    /// Weak functions that have been collected count until they are
+    /// [pruned](#method.prune).
+    pub fn count(&self, name: &str) -> usize {
+        self.handlers(name).len()
+    }
+
+    /// The names of the signals with functions connected, sorted.
+    pub fn names(&self) -> Vec<String> {
+        self.handlers.keys().cloned().collect()
+    }
+
+    /// The names of the signals with how many functions are connected to
+    /// each of them, sorted by name.
+    pub fn counts(&self) -> Vec<(String, usize)> {
+        self.handlers.iter()
+            .map(|(name, funcs)| (name.clone(), funcs.len()))
+            .collect()
+    }
+
+    /// The names of the signals the function with the given pointer is
+    /// connected to, and referenced by.
+    pub fn holding(&self, ptr: *const c_void) -> Vec<String> {
+        self.handlers.iter()
+            .filter(|&(_, funcs)| funcs.iter().any(|func| {
+                func.weak.is_none() && func.ptr as *const _ == ptr
+            }))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    /// Drops every signal, without releasing the references to the
+    /// functions.
+    pub fn clear(&mut self) {
+        self.handlers.clear()
+    }
+
+    /// Drops the weakly held functions that have been garbage collected.
+    pub unsafe fn prune(&mut self, lua: *mut lua_State) {
+        for funcs in self.handlers.values_mut() {
+            funcs.retain(|func| {
+                let key = match func.weak {
+                    Some(key) => key,
+                    None => return true
+                };
+                luaA::object_weak_push(lua, key);
+                let alive = lua_type(lua, -1) == LUA_TFUNCTION as i32;
+                lua_pop(lua, 1);
+                if !alive {
+                    luaA::object_weak_unref(lua, key);
+                }
+                alive
+            });
+        }
+        self.handlers.retain(|_, funcs| !funcs.is_empty());
+    }
+}
 
 use lua_sys::*;
 use libc;
 use lua::{self, luaA};
-pub unsafe fn signal_object_emit(lua: *mut lua_State, signals: &[Signal],
-                          name: &str, nargs: libc::c_int) {
-    let mut hasher = DefaultHasher::new();
-    hasher.write(name.as_bytes());
-    let id = hasher.finish();
-    if let Some(sig) = signals.iter().find(|sig| sig.id == id) {
-        let nbfunc = sig.sigfuncs.len() as i32;
-        luaL_checkstack(lua, nbfunc + nargs + 1, c_str!("too much signal"));
-        /* Push all functions and then execute, because this list can change
-         * while executing funcs. */
-        for func in &sig.sigfuncs {
-            luaA::object_push(lua, func.0);
+use profiler::{profile, CallKind};
+
+/// Pushes the functions connected to the signal, using `push_strong` to
+/// push those that are referenced.
+///
+/// Returns how many were pushed, collected weak functions are skipped.
+pub unsafe fn signal_push_handlers<F>(lua: *mut lua_State, signals: &Signals,
+                                      name: &str, extra: libc::c_int,
+                                      push_strong: F) -> libc::c_int
+    where F: Fn(*mut c_void)
+{
+    let funcs = signals.handlers(name);
+    luaL_checkstack(lua, funcs.len() as i32 + extra, c_str!("too much signal"));
+    let mut nbfunc = 0;
+    for func in funcs {
+        if func.push(lua, |ptr| push_strong(ptr)) {
+            nbfunc += 1;
         }
-        for i in 0..nbfunc {
-            /* push all args */
-            for _ in 0..nargs {
-                lua_pushvalue(lua, - nargs - nbfunc + i);
-            }
-            /* push first function */
+    }
+    nbfunc
+}
+
+/// Calls the `nbfunc` functions pushed after the `nargs` arguments, each
+/// with all of the arguments. Both are popped.
+pub unsafe fn signal_call_handlers(lua: *mut lua_State, nbfunc: libc::c_int,
+                                   nargs: libc::c_int) {
+    for i in 0..nbfunc {
+        /* push all args */
+        for _ in 0..nargs {
             lua_pushvalue(lua, - nargs - nbfunc + i);
-            /* remove this first function */
-            lua::lua_remove(lua, - nargs - nbfunc -1 + i);
-            luaA::dofunction(lua, nargs, 0);
         }
+        /* push first function */
+        lua_pushvalue(lua, - nargs - nbfunc + i);
+        /* remove this first function */
+        lua::lua_remove(lua, - nargs - nbfunc -1 + i);
+        luaA::dofunction(lua, nargs, 0);
     }
     lua_pop(lua, nargs);
 }
 
-pub unsafe fn signal_connect(signals: &mut Vec<Signal>, name: *const libc::c_char,
-                             ptr: *mut libc::c_void) {
-    let mut hasher = DefaultHasher::new();
-    let c_name = CStr::from_ptr(name);
-    let c_name_str = c_name.to_str().unwrap();
-    hasher.write(c_name_str.as_bytes());
-    ::std::mem::forget(c_name);
-    ::std::mem::forget(c_name_str);
-    let id = hasher.finish();
-    if let Some(mut sig) = signals.iter_mut().find(|sig| sig.id == id) {
-        sig.sigfuncs.push(SignalFunc(ptr));
-        return;
-    }
-    let sig = Signal {
-        id,
-        sigfuncs: vec![]
-    };
-    signals.push(sig);
+pub unsafe fn signal_object_emit(lua: *mut lua_State, signals: &Signals,
+                          name: &str, nargs: libc::c_int) {
+    profile(lua, CallKind::Signal, name, || {
+        /* Push all functions and then execute, because this list can change
+         * while executing funcs. */
+        let nbfunc = signal_push_handlers(lua, signals, name, nargs + 1, |ptr| {
+            luaA::object_push(lua, ptr);
+        });
+        signal_call_handlers(lua, nbfunc, nargs);
+    });
 }
 
-pub unsafe fn signal_disconnect(signals: &mut Vec<Signal>,
-                                name: *const libc::c_char,
-                                ptr: *mut libc::c_void) -> libc::c_int {
-    let mut hasher = DefaultHasher::new();
-    let c_name = CStr::from_ptr(name);
-    let c_name_str = c_name.to_str().unwrap();
-    hasher.write(c_name_str.as_bytes());
-    ::std::mem::forget(c_name);
-    ::std::mem::forget(c_name_str);
-    let id = hasher.finish();
-    if let Some(index) = signals.iter().position(|sig| sig.id == id) {
-        for i in 0..signals[index].sigfuncs.len() {
-            if signals[index].sigfuncs[i].0 == ptr {
-                // TODO This might be a memory leak
-                signals.remove(i);
-                break;
-            }
-        }
-        1
-    } else {
-        0
-    }
+/// Emits the signal on the `awesome` global, with the `nargs` values on the
+/// top of the stack.
+///
+/// Unlike `signal_object_emit` on the locked global signals, the handlers
+/// can connect and emit global signals themselves.
+pub unsafe fn signal_global_emit(lua: *mut lua_State, name: &str,
+                                 nargs: libc::c_int) {
+    profile(lua, CallKind::Signal, name, || {
+        let nbfunc = {
+            let global_signals = ::state::get(lua).signals.lock().unwrap();
+            signal_push_handlers(lua, &*global_signals, name, nargs + 1, |ptr| {
+                luaA::object_push(lua, ptr);
+            })
+        };
+        signal_call_handlers(lua, nbfunc, nargs);
+    });
 }
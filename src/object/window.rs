@@ -1,15 +1,17 @@
 //! Definition for the Window class. This class is a super class to various
 //! objects, including drawable.
 
-use ::object::signal::Signal;
-use libc::{c_void};
+use ::object::signal::Signals;
+use libc::{self, c_void};
+use lua_sys::lua_State;
 use ::callbacks::button::ButtonState;
+use ::convert::ToLua;
 
 // TODO Remove this lint
 #[allow(unused_variables)]
 #[repr(C)]
 pub struct WindowState {
-    pub signals: Vec<Signal>,
+    pub signals: Signals,
     /// The X window number
     pub window: i32,
     /// The frame window, might be XCB_NONE
@@ -20,16 +22,71 @@ pub struct WindowState {
     pub strut: Struct,
     /// Button bindings
     pub buttons: Vec<*mut ButtonState>,
+    /// Border width
+    pub border_width: u16,
     /// Do we have pending border changes?
     pub border_need_update: bool,
-    /// Border color
-    pub border_color: (),
+    /// Border color, as given by Lua e.g `#ff0000`
+    pub border_color: String,
     /// The window type
-    pub window_type: (),
+    pub window_type: WindowType,
     /// The border width callback
     pub border_width_callback: fn(*mut c_void, u16, u16)
 }
 
+/// The type of a window, `_NET_WM_WINDOW_TYPE`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowType {
+    Normal = 0,
+    Desktop,
+    Dock,
+    Splash,
+    Dialog,
+    Menu,
+    Toolbar,
+    Utility,
+    DropdownMenu,
+    PopupMenu,
+    Tooltip,
+    Notification,
+    Combo,
+    Dnd
+}
+
+impl Default for WindowType {
+    fn default() -> Self {
+        WindowType::Normal
+    }
+}
+
+impl WindowType {
+    /// The name Lua knows the type by, e.g `dropdown_menu`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            WindowType::Normal => "normal",
+            WindowType::Desktop => "desktop",
+            WindowType::Dock => "dock",
+            WindowType::Splash => "splash",
+            WindowType::Dialog => "dialog",
+            WindowType::Menu => "menu",
+            WindowType::Toolbar => "toolbar",
+            WindowType::Utility => "utility",
+            WindowType::DropdownMenu => "dropdown_menu",
+            WindowType::PopupMenu => "popup_menu",
+            WindowType::Tooltip => "tooltip",
+            WindowType::Notification => "notification",
+            WindowType::Combo => "combo",
+            WindowType::Dnd => "dnd"
+        }
+    }
+}
+
+impl ToLua for WindowType {
+    unsafe fn push_to_lua(self, lua: *mut lua_State) -> libc::c_int {
+        self.name().push_to_lua(lua)
+    }
+}
 
 #[repr(C)]
 pub struct Struct {
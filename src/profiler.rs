@@ -0,0 +1,154 @@
+//! Opt-in profiling of the calls between Rust and Lua.
+//!
+//! When enabled, every call from Lua to one of the callbacks registered
+//! with the register_*! macros and every emitted signal is timed, so that
+//! slow callbacks can be told apart from slow signal handlers.
+//!
+//! Calls that raise a Lua error are not counted: `lua_error` jumps past
+//! the code that records the call, and no destructor runs on the way.
+//!
+//! The profiler of an [Awesome](../awesome/struct.Awesome.html) is reached
+//! with its `profiler` method.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use lua_sys::*;
+use ::state;
+
+/// Statistics of the calls to one callback or signal.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct CallStats {
+    /// How many times it was called
+    pub count: u64,
+    /// The time spent in all of the calls
+    pub total: Duration,
+    /// The time spent in the slowest call
+    pub max: Duration
+}
+
+impl CallStats {
+    /// The mean time spent in a call.
+    pub fn average(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::default()
+        }
+        let nanos = duration_nanos(self.total) / self.count;
+        Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32)
+    }
+
+    fn record(&mut self, elapsed: Duration) {
+        self.count += 1;
+        self.total += elapsed;
+        if elapsed > self.max {
+            self.max = elapsed;
+        }
+    }
+}
+
+/// What was called.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CallKind {
+    /// A Rust callback called from Lua
+    Method,
+    /// A signal emitted to its Lua handlers
+    Signal
+}
+
+/// Collects the `CallStats` of the callbacks and signals.
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    /// Nothing is recorded unless this is set
+    pub enabled: bool,
+    /// Stats of the Rust callbacks, by method name (e.g `client_get`)
+    pub methods: HashMap<String, CallStats>,
+    /// Stats of the signals, by signal name (e.g `property::name`)
+    pub signals: HashMap<String, CallStats>
+}
+
+impl Profiler {
+    /// Adds a call that took `elapsed` to the stats.
+    pub fn record(&mut self, kind: CallKind, name: &str, elapsed: Duration) {
+        let stats = match kind {
+            CallKind::Method => &mut self.methods,
+            CallKind::Signal => &mut self.signals
+        };
+        if let Some(stats) = stats.get_mut(name) {
+            stats.record(elapsed);
+            return
+        }
+        let mut new = CallStats::default();
+        new.record(elapsed);
+        stats.insert(name.into(), new);
+    }
+
+    /// Forgets all of the recorded stats.
+    pub fn reset(&mut self) {
+        self.methods.clear();
+        self.signals.clear();
+    }
+
+    /// The recorded stats, slowest total first.
+    pub fn sorted(&self, kind: CallKind) -> Vec<(&str, CallStats)> {
+        let stats = match kind {
+            CallKind::Method => &self.methods,
+            CallKind::Signal => &self.signals
+        };
+        let mut sorted: Vec<_> = stats.iter()
+            .map(|(name, stats)| (name.as_str(), *stats))
+            .collect();
+        sorted.sort_by(|a, b| b.1.total.cmp(&a.1.total).then(a.0.cmp(b.0)));
+        sorted
+    }
+
+    /// Writes a table of the stats, slowest total first.
+    pub fn dump<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for &(title, kind) in &[("methods", CallKind::Method),
+                                ("signals", CallKind::Signal)] {
+            writeln!(out, "{:<40} {:>10} {:>12} {:>12} {:>12}",
+                     title, "count", "total (us)", "avg (us)", "max (us)")?;
+            for (name, stats) in self.sorted(kind) {
+                writeln!(out, "{:<40} {:>10} {:>12} {:>12} {:>12}",
+                         name, stats.count,
+                         duration_nanos(stats.total) / 1000,
+                         duration_nanos(stats.average()) / 1000,
+                         duration_nanos(stats.max) / 1000)?;
+            }
+            writeln!(out, "")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the stats to the file at `path`, replacing it.
+    pub fn dump_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        self.dump(&mut file)
+    }
+}
+
+/// Runs `func`, timing it if the profiler of the Lua state is enabled.
+///
+/// The profiler is not locked while `func` runs, as it may call back in
+/// to Lua which can end up here again. Nothing is left to clean up while
+/// it runs either, so if it raises a Lua error the call is just not
+/// recorded.
+pub unsafe fn profile<R, F>(lua: *mut lua_State, kind: CallKind, name: &str,
+                            func: F) -> R
+    where F: FnOnce() -> R
+{
+    let profiler = &state::get(lua).profiler;
+    if !profiler.lock().unwrap().enabled {
+        return func()
+    }
+    let start = Instant::now();
+    let result = func();
+    let elapsed = start.elapsed();
+    profiler.lock().unwrap().record(kind, name, elapsed);
+    result
+}
+
+fn duration_nanos(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000_000 + duration.subsec_nanos() as u64
+}
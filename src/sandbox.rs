@@ -0,0 +1,288 @@
+//! Restricted environments to run untrusted Lua code in, e.g a configuration
+//! file or a third party widget module.
+//!
+//! Code in a sandbox runs with its own `_ENV`, which only contains the chosen
+//! parts of the standard library and the chosen awesome globals. Nothing that
+//! can access the file system or spawn processes (`io`, `os.execute`,
+//! `require`, `dofile`, `load`, ...) is ever made available.
+//!
+//! The awesome globals are exposed through read-only proxies, so the sandbox
+//! can't change them for the host. The proxies of `awesome` and `root` only
+//! expose the fields that can't spawn processes or control the compositor,
+//! and `getmetatable` only works on tables so the metatables of the host
+//! can't be reached.
+
+use lua_sys::*;
+use libc;
+use std::ffi::CString;
+use lua::lua_insert;
+
+/// Functions of the base library that are safe to expose.
+const BASE_FUNCTIONS: &[&str] = &[
+    "assert", "error", "ipairs", "next", "pairs", "pcall",
+    "print", "rawequal", "rawget", "rawlen", "rawset", "select",
+    "setmetatable", "tonumber", "tostring", "type", "xpcall", "_VERSION"
+];
+
+/// Functions of the `os` library that are safe to expose.
+const OS_FUNCTIONS: &[&str] = &["clock", "date", "difftime", "time"];
+
+/// The awesome globals exposed by the default sandbox.
+const AWESOME_GLOBALS: &[&str] = &[
+    "awesome", "button", "client", "drawable", "drawin", "keygrabber",
+    "mouse", "mousegrabber", "root", "screen", "tag"
+];
+
+/// Fields of `awesome` that are exposed to a sandbox.
+const AWESOME_ALLOWED: &[&str] = &[
+    "connect_signal", "weak_connect_signal", "disconnect_signal",
+    "emit_signal", "version", "release", "startup", "startup_errors",
+    "composite_manager_running", "themes_path", "icon_path", "classes",
+    "class_info", "signals", "xkb_get_layout_group", "xkb_get_group_names",
+    "get_xproperty"
+];
+
+/// Fields of `root` that are exposed to a sandbox, the others change the
+/// bindings, the cursor or fake input.
+const ROOT_ALLOWED: &[&str] = &["drawins", "size", "size_mm", "tags"];
+
+/// The fields of the global that can be exposed, or `None` if all of them
+/// can.
+fn global_allowed(name: &str) -> Option<&'static [&'static str]> {
+    match name {
+        "awesome" => Some(AWESOME_ALLOWED),
+        "root" => Some(ROOT_ALLOWED),
+        _ => None
+    }
+}
+
+/// Parts of the standard library that can be exposed in a sandbox.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SandboxLib {
+    /// The safe subset of the base functions (no `load`, `dofile`,
+    /// `require`, ...)
+    Base,
+    Coroutine,
+    Math,
+    String,
+    Table,
+    Utf8,
+    /// Only the time related functions of `os`
+    Os
+}
+
+impl SandboxLib {
+    /// Name of the library table in the global environment.
+    fn name(&self) -> &'static str {
+        match *self {
+            SandboxLib::Base => "_G",
+            SandboxLib::Coroutine => "coroutine",
+            SandboxLib::Math => "math",
+            SandboxLib::String => "string",
+            SandboxLib::Table => "table",
+            SandboxLib::Utf8 => "utf8",
+            SandboxLib::Os => "os"
+        }
+    }
+
+    /// The fields of the library that can be exposed,
+    /// or `None` if all of them can.
+    fn allowed(&self) -> Option<&'static [&'static str]> {
+        match *self {
+            SandboxLib::Base => Some(BASE_FUNCTIONS),
+            SandboxLib::Os => Some(OS_FUNCTIONS),
+            _ => None
+        }
+    }
+}
+
+/// Describes what is available to code running in a sandbox.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Sandbox {
+    /// Standard libraries available in the sandbox
+    pub libraries: Vec<SandboxLib>,
+    /// Globals copied from the global environment, e.g `"client"`
+    pub globals: Vec<String>
+}
+
+impl Default for Sandbox {
+    /// All of the safe standard libraries and the awesome globals.
+    fn default() -> Self {
+        Sandbox {
+            libraries: vec![SandboxLib::Base, SandboxLib::Coroutine,
+                            SandboxLib::Math, SandboxLib::String,
+                            SandboxLib::Table, SandboxLib::Utf8,
+                            SandboxLib::Os],
+            globals: AWESOME_GLOBALS.iter().map(|&name| name.into()).collect()
+        }
+    }
+}
+
+impl Sandbox {
+    pub fn new(libraries: Vec<SandboxLib>, globals: Vec<String>) -> Self {
+        Sandbox { libraries, globals }
+    }
+
+    /// Builds the environment of the sandbox and pushes it on to the stack.
+    pub unsafe fn push_env(&self, lua: *mut lua_State) {
+        lua_newtable(lua);
+        let env = lua_gettop(lua);
+        for lib in &self.libraries {
+            let name = CString::new(lib.name()).unwrap();
+            lua_getglobal(lua, name.as_ptr());
+            if lua_type(lua, -1) != LUA_TTABLE as i32 {
+                // Library was not loaded (e.g no utf8 in this Lua version)
+                lua_pop(lua, 1);
+                continue
+            }
+            if *lib == SandboxLib::Base {
+                copy_fields(lua, env, lib.allowed());
+                lua_pop(lua, 1);
+                lua_pushcfunction(lua, Some(sandbox_getmetatable));
+                lua_setfield(lua, env, c_str!("getmetatable"));
+            } else {
+                /* Copy the library so the sandbox can't change the real one */
+                lua_newtable(lua);
+                let lib_idx = lua_gettop(lua);
+                lua_pushvalue(lua, -2);
+                copy_fields(lua, lib_idx, lib.allowed());
+                lua_pop(lua, 1);
+                lua_setfield(lua, env, name.as_ptr());
+                lua_pop(lua, 1);
+            }
+        }
+        for global in &self.globals {
+            let name = match CString::new(global.as_str()) {
+                Ok(name) => name,
+                Err(_) => continue
+            };
+            lua_getglobal(lua, name.as_ptr());
+            if lua_type(lua, -1) == LUA_TTABLE as i32 {
+                push_read_only(lua, global, global_allowed(global));
+            }
+            lua_setfield(lua, env, name.as_ptr());
+        }
+        lua_pushvalue(lua, env);
+        lua_setfield(lua, env, c_str!("_G"));
+    }
+
+    /// Sets the sandbox environment as the `_ENV` of the chunk at `idx`.
+    pub unsafe fn set_env(&self, lua: *mut lua_State, idx: libc::c_int) {
+        let idx = ::luaA::absindex(lua, idx);
+        self.push_env(lua);
+        ::compat::setchunkenv(lua, idx);
+    }
+}
+
+/// Copies the fields of the table on the top of the stack to the table at
+/// `dst`. If `names` is given only those fields are copied.
+unsafe fn copy_fields(lua: *mut lua_State, dst: libc::c_int,
+                      names: Option<&[&str]>) {
+    match names {
+        Some(names) => {
+            for name in names {
+                let name = CString::new(*name).unwrap();
+                lua_getfield(lua, -1, name.as_ptr());
+                lua_setfield(lua, dst, name.as_ptr());
+            }
+        },
+        None => {
+            lua_pushnil(lua);
+            while lua_next(lua, -2) != 0 {
+                /* Duplicate the key, so it is still there for lua_next() */
+                lua_pushvalue(lua, -2);
+                lua_insert(lua, -2);
+                lua_rawset(lua, dst);
+            }
+        }
+    }
+}
+
+/// Replaces the table on the top of the stack with a read-only proxy to it,
+/// named `name` in errors. If `allowed` is given the other fields are nil.
+///
+/// Calling the proxy calls the table, e.g `button({})`. The proxy hides its
+/// metatable, so the table can't be reached from it.
+unsafe fn push_read_only(lua: *mut lua_State, name: &str,
+                         allowed: Option<&[&str]>) {
+    let table = lua_gettop(lua);
+    lua_newtable(lua);
+    lua_newtable(lua);
+    lua_pushvalue(lua, table);
+    match allowed {
+        Some(allowed) => {
+            lua_newtable(lua);
+            for field in allowed {
+                let field = CString::new(*field).unwrap();
+                lua_pushboolean(lua, 1);
+                lua_setfield(lua, -2, field.as_ptr());
+            }
+        },
+        None => lua_pushnil(lua)
+    }
+    lua_pushcclosure(lua, Some(read_only_index), 2);
+    lua_setfield(lua, -2, c_str!("__index"));
+    let name = CString::new(name).unwrap_or_default();
+    lua_pushstring(lua, name.as_ptr());
+    lua_pushcclosure(lua, Some(read_only_newindex), 1);
+    lua_setfield(lua, -2, c_str!("__newindex"));
+    lua_pushvalue(lua, table);
+    lua_pushcclosure(lua, Some(read_only_call), 1);
+    lua_setfield(lua, -2, c_str!("__call"));
+    lua_pushboolean(lua, 0);
+    lua_setfield(lua, -2, c_str!("__metatable"));
+    lua_setmetatable(lua, -2);
+    ::compat::lua_replace(lua, table);
+}
+
+/// `__index` of a proxy, with the table as the first upvalue and the set
+/// of allowed fields, or nil if all of them are, as the second.
+unsafe extern "C" fn read_only_index(lua: *mut lua_State) -> libc::c_int {
+    if lua_type(lua, ::compat::upvalueindex(2)) == LUA_TTABLE as i32 {
+        lua_pushvalue(lua, 2);
+        lua_rawget(lua, ::compat::upvalueindex(2));
+        if lua_toboolean(lua, -1) == 0 {
+            lua_pushnil(lua);
+            return 1
+        }
+        lua_pop(lua, 1);
+    }
+    lua_pushvalue(lua, 2);
+    lua_gettable(lua, ::compat::upvalueindex(1));
+    1
+}
+
+/// `__newindex` of a proxy, with its name as the first upvalue.
+unsafe extern "C" fn read_only_newindex(lua: *mut lua_State) -> libc::c_int {
+    let name = lua_tolstring(lua, ::compat::upvalueindex(1), ::std::ptr::null_mut());
+    luaL_error(lua, c_str!("%s is read-only in a sandbox"), name)
+}
+
+/// `__call` of a proxy, which calls the table, the first upvalue, with the
+/// same arguments.
+unsafe extern "C" fn read_only_call(lua: *mut lua_State) -> libc::c_int {
+    lua_pushvalue(lua, ::compat::upvalueindex(1));
+    ::compat::lua_replace(lua, 1);
+    if ::compat::pcall(lua, lua_gettop(lua) - 1, LUA_MULTRET, 0) != 0 {
+        /* Raised again, as if the table had been called directly */
+        return lua_error(lua)
+    }
+    lua_gettop(lua)
+}
+
+/// `getmetatable` for sandboxes, which refuses anything but tables so the
+/// metatables shared with the host (e.g of strings) can't be changed.
+unsafe extern "C" fn sandbox_getmetatable(lua: *mut lua_State) -> libc::c_int {
+    luaL_checktype(lua, 1, LUA_TTABLE as libc::c_int);
+    if lua_getmetatable(lua, 1) == 0 {
+        lua_pushnil(lua);
+        return 1
+    }
+    /* Like the real one, __metatable is returned instead if it's set */
+    lua_pushstring(lua, c_str!("__metatable"));
+    lua_rawget(lua, -2);
+    if lua_type(lua, -1) == LUA_TNIL as libc::c_int {
+        lua_pop(lua, 1);
+    }
+    1
+}
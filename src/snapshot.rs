@@ -0,0 +1,453 @@
+//! Snapshots of the state managed in the [GlobalConf](../globalconf/struct.GlobalConf.html),
+//! to attach to bug reports.
+//!
+//! A snapshot holds the screens, clients (with their geometry and flags),
+//! the stacking order, the focus, the tags, the drawins and the root keys
+//! and buttons. It is written as JSON, with the fields and lists always in
+//! the same order, so two snapshots of the same state are identical.
+//!
+//! Objects refer to each other by their index in their list of the
+//! snapshot, e.g `stack` lists indices in `clients`.
+//!
+//! [restore](struct.Snapshot.html#method.restore) rebuilds an equivalent
+//! state, e.g in a headless instance, to replay a layout problem in a test.
+
+use std::fmt;
+use std::ptr;
+use std::sync::{Mutex, RwLock};
+use lua_sys::*;
+use serde_json;
+use ::callbacks::button::ButtonState;
+use ::callbacks::client::{ClientState, SizeHints};
+use ::callbacks::drawin::DrawinState;
+use ::callbacks::screen::ScreenState;
+use ::callbacks::tag::TagState;
+use ::globalconf::GlobalConf;
+use ::lua::{Lua, luaA};
+use ::object::{KeyState, Signals};
+use ::object::class::Class;
+use ::state;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotErr {
+    /// The JSON could not be parsed as a snapshot
+    Json(String),
+    /// The class has not been set up in the Lua state, e.g with `register_all!`
+    ClassNotSetUp(&'static str),
+    /// The index does not refer to an object of the list in the snapshot
+    InvalidIndex { list: &'static str, index: usize },
+    /// The client is transient for itself, directly or through the clients
+    /// it is transient for
+    TransientCycle { client: usize }
+}
+
+impl fmt::Display for SnapshotErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SnapshotErr::Json(ref err) => write!(f, "invalid snapshot: {}", err),
+            SnapshotErr::ClassNotSetUp(class) =>
+                write!(f, "the {} class has not been set up", class),
+            SnapshotErr::InvalidIndex { list, index } =>
+                write!(f, "there is no {} at index {}", list, index),
+            SnapshotErr::TransientCycle { client } =>
+                write!(f, "client {} is transient for itself", client)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Geometry {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16
+}
+
+impl From<luaA::area_t> for Geometry {
+    fn from(area: luaA::area_t) -> Self {
+        Geometry { x: area.x, y: area.y, width: area.width, height: area.height }
+    }
+}
+
+impl From<Geometry> for luaA::area_t {
+    fn from(geometry: Geometry) -> Self {
+        luaA::area_t {
+            x: geometry.x,
+            y: geometry.y,
+            width: geometry.width,
+            height: geometry.height
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScreenSnapshot {
+    pub valid: bool
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClientSnapshot {
+    /// The X window number
+    pub window: i32,
+    pub name: String,
+    pub icon_name: String,
+    pub class: String,
+    pub instance: String,
+    pub role: String,
+    pub machine: String,
+    pub pid: u32,
+    pub startup_id: String,
+    pub geometry: Geometry,
+    pub border_width: u16,
+    pub size_hints: SizeHints,
+    pub opacity: f64,
+    /// Index in `screens`
+    pub screen: Option<usize>,
+    /// Index in `clients`
+    pub transient_for: Option<usize>,
+    pub sticky: bool,
+    pub urgent: bool,
+    pub hidden: bool,
+    pub minimized: bool,
+    pub fullscreen: bool,
+    pub maximized: bool,
+    pub maximized_horizontal: bool,
+    pub maximized_vertical: bool,
+    pub above: bool,
+    pub below: bool,
+    pub modal: bool,
+    pub ontop: bool,
+    pub isbanned: bool,
+    pub skip_taskbar: bool,
+    pub nofocus: bool,
+    pub focusable: bool,
+    pub focusable_set: bool,
+    pub size_hints_honor: bool
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TagSnapshot {}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DrawinSnapshot {
+    /// The X window number
+    pub window: i32,
+    pub geometry: Geometry,
+    pub opacity: f64,
+    pub ontop: bool,
+    pub visible: bool,
+    pub cursor: String
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeySnapshot {
+    pub modifiers: u16,
+    pub keysym: u32,
+    pub keycode: u8
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ButtonSnapshot {
+    pub modifiers: u16,
+    pub button: u8
+}
+
+/// The state managed in the `GlobalConf`, see the [module](index.html).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub screens: Vec<ScreenSnapshot>,
+    /// Index in `screens`
+    pub primary_screen: Option<usize>,
+    pub clients: Vec<ClientSnapshot>,
+    /// Indices in `clients`, from the bottom of the stack to the top
+    pub stack: Vec<usize>,
+    /// Index in `clients` of the focused client
+    pub focus: Option<usize>,
+    /// Indices in `clients` of the clients that had the focus, the most
+    /// recent first
+    #[serde(default)]
+    pub focus_history: Vec<usize>,
+    pub tags: Vec<TagSnapshot>,
+    pub drawins: Vec<DrawinSnapshot>,
+    /// Root window key bindings
+    pub keys: Vec<KeySnapshot>,
+    /// Root window mouse bindings
+    pub buttons: Vec<ButtonSnapshot>
+}
+
+/// Index of the object in the list, `None` if it is null or not listed.
+fn index_of<T>(list: &[*mut T], object: *mut T) -> Option<usize> {
+    list.iter().position(|&listed| listed == object)
+}
+
+impl Snapshot {
+    /// Takes a snapshot of the configuration.
+    pub fn new(conf: &GlobalConf) -> Self {
+        unsafe {
+            let screens = &conf.screens;
+            let clients = &conf.clients;
+            Snapshot {
+                screens: screens.iter()
+                    .map(|&screen| ScreenSnapshot { valid: (*screen).valid })
+                    .collect(),
+                primary_screen: index_of(screens, conf.primary_screen),
+                clients: clients.iter()
+                    .map(|&client| snapshot_client(&*client, screens, clients))
+                    .collect(),
+                stack: conf.stack.iter()
+                    .filter_map(|&client| index_of(clients, client))
+                    .collect(),
+                focus: index_of(clients, conf.focus.client),
+                focus_history: conf.focus.history.iter()
+                    .filter_map(|&client| index_of(clients, client))
+                    .collect(),
+                tags: conf.tags.iter().map(|_| TagSnapshot {}).collect(),
+                drawins: conf.drawins.iter()
+                    .map(|&drawin| {
+                        let drawin = &*drawin;
+                        DrawinSnapshot {
+                            window: drawin.window.window,
+                            geometry: drawin.geometry.into(),
+                            opacity: drawin.window.opacity,
+                            ontop: drawin.ontop,
+                            visible: drawin.visible,
+                            cursor: drawin.cursor.clone()
+                        }
+                    })
+                    .collect(),
+                keys: conf.keys.iter()
+                    .map(|key| KeySnapshot {
+                        modifiers: key.modifiers,
+                        keysym: key.keysym,
+                        keycode: key.keycode
+                    })
+                    .collect(),
+                buttons: conf.buttons.iter()
+                    .map(|button| ButtonSnapshot {
+                        modifiers: button.modifiers,
+                        button: button.button
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Writes the snapshot as indented JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self)
+            .expect("A snapshot can always be written as JSON")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SnapshotErr> {
+        serde_json::from_str(json).map_err(|err| SnapshotErr::Json(err.to_string()))
+    }
+
+    /// Checks that every index refers to an object of the snapshot, and
+    /// that no client is transient for itself.
+    pub fn validate(&self) -> Result<(), SnapshotErr> {
+        let check = |list: &'static str, len: usize, index: Option<usize>| {
+            match index {
+                Some(index) if index >= len =>
+                    Err(SnapshotErr::InvalidIndex { list, index }),
+                _ => Ok(())
+            }
+        };
+        let screens = self.screens.len();
+        let clients = self.clients.len();
+        check("screen", screens, self.primary_screen)?;
+        check("client", clients, self.focus)?;
+        for &index in self.stack.iter().chain(&self.focus_history) {
+            check("client", clients, Some(index))?;
+        }
+        for client in &self.clients {
+            check("screen", screens, client.screen)?;
+            check("client", clients, client.transient_for)?;
+        }
+        /* A chain longer than the number of clients goes around a cycle */
+        for index in 0..clients {
+            let mut parent = self.clients[index].transient_for;
+            for _ in 0..clients {
+                match parent {
+                    Some(parent) if parent == index =>
+                        return Err(SnapshotErr::TransientCycle { client: index }),
+                    Some(next) => parent = self.clients[next].transient_for,
+                    None => break
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates the objects of the snapshot in the Lua state and adds them
+    /// to the configuration, replacing its stack, focus and primary screen.
+    ///
+    /// This is meant for a new instance: the objects already in the
+    /// configuration are kept, but are no longer stacked or focused.
+    /// No signals are emitted for the new objects, other than `new` on
+    /// their class.
+    pub fn restore(&self, lua: &Lua, conf: &Mutex<GlobalConf>)
+                   -> Result<(), SnapshotErr> {
+        self.validate()?;
+        unsafe {
+            let state = state::get(lua.0);
+            /* The lock can't be held while the objects are created, as
+             * the `new` signal of their class runs Lua code */
+            let screens: Vec<*mut ScreenState> =
+                new_objects(lua, &state.screen_class, "screen", &self.screens)?;
+            let tags: Vec<*mut TagState> =
+                new_objects(lua, &state.tag_class, "tag", &self.tags)?;
+            let drawins: Vec<*mut DrawinState> =
+                new_objects(lua, &state.drawin_class, "drawin", &self.drawins)?;
+            let clients: Vec<*mut ClientState> =
+                new_objects(lua, &state.client_class, "client", &self.clients)?;
+
+            for (&screen, snapshot) in screens.iter().zip(&self.screens) {
+                (*screen).valid = snapshot.valid;
+            }
+            for (&drawin, snapshot) in drawins.iter().zip(&self.drawins) {
+                let drawin = &mut *drawin;
+                drawin.window.window = snapshot.window;
+                drawin.window.opacity = snapshot.opacity;
+                drawin.geometry = snapshot.geometry.into();
+                drawin.ontop = snapshot.ontop;
+                drawin.visible = snapshot.visible;
+                drawin.cursor = snapshot.cursor.clone();
+            }
+            for (&client, snapshot) in clients.iter().zip(&self.clients) {
+                restore_client(&mut *client, snapshot, &screens, &clients);
+            }
+
+            let mut conf = conf.lock().unwrap();
+            conf.screens.extend(&screens);
+            conf.primary_screen = self.primary_screen
+                .map(|index| screens[index])
+                .unwrap_or(ptr::null_mut());
+            conf.tags.extend(&tags);
+            conf.drawins.extend(&drawins);
+            conf.clients.extend(&clients);
+            conf.stack = self.stack.iter().map(|&index| clients[index]).collect();
+            conf.focus.client = self.focus
+                .map(|index| clients[index])
+                .unwrap_or(ptr::null_mut());
+            conf.focus.history = self.focus_history.iter()
+                .map(|&index| clients[index])
+                .collect();
+            conf.keys.extend(self.keys.iter().map(|key| KeyState {
+                signals: Signals::new(),
+                modifiers: key.modifiers,
+                keysym: key.keysym,
+                keycode: key.keycode
+            }));
+            conf.buttons.extend(self.buttons.iter().map(|button| ButtonState {
+                signals: Signals::new(),
+                modifiers: button.modifiers,
+                button: button.button
+            }));
+        }
+        Ok(())
+    }
+}
+
+unsafe fn snapshot_client(client: &ClientState, screens: &[*mut ScreenState],
+                          clients: &[*mut ClientState]) -> ClientSnapshot {
+    ClientSnapshot {
+        window: client.window.window,
+        name: client.name.clone(),
+        icon_name: client.icon_name.clone(),
+        class: client.class.clone(),
+        instance: client.instance.clone(),
+        role: client.role.clone(),
+        machine: client.machine.clone(),
+        pid: client.pid,
+        startup_id: client.startup_id.clone(),
+        geometry: client.geometry.into(),
+        border_width: client.window.border_width,
+        size_hints: client.size_hints,
+        opacity: client.window.opacity,
+        screen: index_of(screens, client.screen as *mut ScreenState),
+        transient_for: index_of(clients, client.transient_for),
+        sticky: client.sticky,
+        urgent: client.urgent,
+        hidden: client.hidden,
+        minimized: client.minimized,
+        fullscreen: client.fullscreen,
+        maximized: client.maximized,
+        maximized_horizontal: client.maximized_horizontal,
+        maximized_vertical: client.maximized_vertical,
+        above: client.above,
+        below: client.below,
+        modal: client.modal,
+        ontop: client.ontop,
+        isbanned: client.isbanned,
+        skip_taskbar: client.skip_taskbar,
+        nofocus: client.nofocus,
+        focusable: client.focusable,
+        focusable_set: client.focusable_set,
+        size_hints_honor: client.size_hints_honor
+    }
+}
+
+unsafe fn restore_client(client: &mut ClientState, snapshot: &ClientSnapshot,
+                         screens: &[*mut ScreenState],
+                         clients: &[*mut ClientState]) {
+    client.window.window = snapshot.window;
+    client.window.border_width = snapshot.border_width;
+    client.size_hints = snapshot.size_hints;
+    client.window.opacity = snapshot.opacity;
+    client.name = snapshot.name.clone();
+    client.icon_name = snapshot.icon_name.clone();
+    client.class = snapshot.class.clone();
+    client.instance = snapshot.instance.clone();
+    client.role = snapshot.role.clone();
+    client.machine = snapshot.machine.clone();
+    client.pid = snapshot.pid;
+    client.startup_id = snapshot.startup_id.clone();
+    client.geometry = snapshot.geometry.into();
+    client.screen = snapshot.screen
+        .map(|index| screens[index] as _)
+        .unwrap_or(ptr::null_mut());
+    client.transient_for = snapshot.transient_for
+        .map(|index| clients[index])
+        .unwrap_or(ptr::null_mut());
+    client.sticky = snapshot.sticky;
+    client.urgent = snapshot.urgent;
+    client.hidden = snapshot.hidden;
+    client.minimized = snapshot.minimized;
+    client.fullscreen = snapshot.fullscreen;
+    client.maximized = snapshot.maximized;
+    client.maximized_horizontal = snapshot.maximized_horizontal;
+    client.maximized_vertical = snapshot.maximized_vertical;
+    client.above = snapshot.above;
+    client.below = snapshot.below;
+    client.modal = snapshot.modal;
+    client.ontop = snapshot.ontop;
+    client.isbanned = snapshot.isbanned;
+    client.skip_taskbar = snapshot.skip_taskbar;
+    client.nofocus = snapshot.nofocus;
+    client.focusable = snapshot.focusable;
+    client.focusable_set = snapshot.focusable_set;
+    client.size_hints_honor = snapshot.size_hints_honor;
+}
+
+/// Creates an object of the class for each of the snapshots, referenced in
+/// the object registry like the objects the compositor manages.
+unsafe fn new_objects<O, S>(lua: &Lua, class: &RwLock<Class>,
+                            name: &'static str, snapshots: &[S])
+                            -> Result<Vec<*mut O>, SnapshotErr> {
+    let allocator = class.try_read().unwrap().allocator
+        .ok_or(SnapshotErr::ClassNotSetUp(name))?;
+    Ok(snapshots.iter()
+       .map(|_| {
+           let object = allocator(lua.0) as *mut O;
+           luaA::object_ref(lua.0, -1);
+           object
+       })
+       .collect())
+}
+
+/// `awesome.snapshot()`, the [snapshot](struct.Snapshot.html) of the
+/// configuration as JSON.
+pub unsafe fn awesome_snapshot(lua: *mut lua_State) -> ::libc::c_int {
+    let json = Snapshot::new(&state::global_conf(lua).lock().unwrap()).to_json();
+    ::convert::ToLua::push_to_lua(json, lua)
+}
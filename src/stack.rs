@@ -0,0 +1,164 @@
+//! The stacking order of the clients and drawins.
+//! Based off of stack.c in the C source.
+//!
+//! `GlobalConf.stack` holds the clients from the bottom to the top, as
+//! raised and lowered from Lua. The order the windows are actually shown
+//! in is computed from it by sorting the clients in to layers, keeping
+//! transient windows above the clients they are transient for, and
+//! putting the drawins between the layers.
+
+use ::callbacks::client::ClientState;
+use ::callbacks::drawin::DrawinState;
+use ::globalconf::GlobalConf;
+use ::object::WindowType;
+
+/// The layers of the windows, from the bottom to the top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    /// Transient windows, stacked with the client they are transient for
+    Ignore,
+    Desktop,
+    Below,
+    Normal,
+    Above,
+    /// Fullscreen clients, while they have the focus
+    Fullscreen,
+    Ontop
+}
+
+/// A window in the stacking order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackedWindow {
+    Client(*mut ClientState),
+    Drawin(*mut DrawinState)
+}
+
+/// The layer the client is stacked in.
+pub unsafe fn client_layer(conf: &GlobalConf, client: *mut ClientState) -> Layer {
+    let c = &*client;
+    /* first deal with user set attributes */
+    if c.ontop {
+        Layer::Ontop
+    } else if c.fullscreen && conf.focus.client == client {
+        /* Fullscreen windows only get their own layer when they have the focus */
+        Layer::Fullscreen
+    } else if c.above {
+        Layer::Above
+    } else if c.below {
+        Layer::Below
+    } else if !c.transient_for.is_null() && conf.stack.contains(&c.transient_for) {
+        Layer::Ignore
+    } else if c.window.window_type == WindowType::Desktop {
+        Layer::Desktop
+    } else {
+        Layer::Normal
+    }
+}
+
+/// Removes the client from the stack.
+pub fn stack_client_remove(conf: &mut GlobalConf, client: *mut ClientState) {
+    conf.stack.retain(|&c| c != client);
+}
+
+/// Puts the client at the bottom of the stack.
+pub fn stack_client_push(conf: &mut GlobalConf, client: *mut ClientState) {
+    stack_client_remove(conf, client);
+    conf.stack.insert(0, client);
+}
+
+/// Puts the client at the top of the stack.
+pub fn stack_client_append(conf: &mut GlobalConf, client: *mut ClientState) {
+    stack_client_remove(conf, client);
+    conf.stack.push(client);
+}
+
+/// Raises the client, with the clients it is transient for below it.
+pub unsafe fn client_raise(conf: &mut GlobalConf, client: *mut ClientState) {
+    let parents = transient_parents(client);
+    for &parent in parents.iter().rev() {
+        stack_client_append(conf, parent);
+    }
+    stack_client_append(conf, client);
+}
+
+/// Lowers the client, with the clients it is transient for below it.
+pub unsafe fn client_lower(conf: &mut GlobalConf, client: *mut ClientState) {
+    stack_client_push(conf, client);
+    for parent in transient_parents(client) {
+        stack_client_push(conf, parent);
+    }
+}
+
+/// The clients the client is transient for, closest first.
+unsafe fn transient_parents(client: *mut ClientState) -> Vec<*mut ClientState> {
+    let mut parents = Vec::new();
+    let mut parent = (*client).transient_for;
+    while !parent.is_null() && parent != client && !parents.contains(&parent) {
+        parents.push(parent);
+        parent = (*parent).transient_for;
+    }
+    parents
+}
+
+/// The windows from the bottom to the top, as they should be shown.
+///
+/// The desktop, below, normal and above layers come first, then the
+/// drawins that aren't on top, the fullscreen and ontop layers and at last
+/// the drawins that are on top.
+pub unsafe fn stacking_order(conf: &GlobalConf) -> Vec<StackedWindow> {
+    let mut order = Vec::with_capacity(conf.stack.len() + conf.drawins.len());
+    for &layer in &[Layer::Desktop, Layer::Below, Layer::Normal, Layer::Above] {
+        stack_layer(conf, layer, &mut order);
+    }
+    for &drawin in conf.drawins.iter().filter(|&&d| !(*d).ontop) {
+        order.push(StackedWindow::Drawin(drawin));
+    }
+    for &layer in &[Layer::Fullscreen, Layer::Ontop] {
+        stack_layer(conf, layer, &mut order);
+    }
+    for &drawin in conf.drawins.iter().filter(|&&d| (*d).ontop) {
+        order.push(StackedWindow::Drawin(drawin));
+    }
+    order
+}
+
+/// The clients from the bottom to the top, as they should be shown.
+pub unsafe fn stacked_clients(conf: &GlobalConf) -> Vec<*mut ClientState> {
+    stacking_order(conf).into_iter().filter_map(|window| match window {
+        StackedWindow::Client(client) => Some(client),
+        StackedWindow::Drawin(_) => None
+    }).collect()
+}
+
+unsafe fn stack_layer(conf: &GlobalConf, layer: Layer,
+                      order: &mut Vec<StackedWindow>) {
+    for &client in &conf.stack {
+        if client_layer(conf, client) == layer {
+            stack_client_above(conf, client, order, &mut Vec::new());
+        }
+    }
+}
+
+/// Stacks the client on top of the windows so far, then the clients that
+/// are transient for it on top of it, the modal ones last.
+///
+/// A client that was already stacked is moved up, like restacking an
+/// existing window would.
+unsafe fn stack_client_above(conf: &GlobalConf, client: *mut ClientState,
+                             order: &mut Vec<StackedWindow>,
+                             parents: &mut Vec<*mut ClientState>) {
+    let window = StackedWindow::Client(client);
+    order.retain(|&w| w != window);
+    order.push(window);
+    parents.push(client);
+    for &modal in &[false, true] {
+        for &transient in &conf.stack {
+            if (*transient).transient_for == client
+                && (*transient).modal == modal
+                && !parents.contains(&transient) {
+                stack_client_above(conf, transient, order, parents);
+            }
+        }
+    }
+    parents.pop();
+}
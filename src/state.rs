@@ -0,0 +1,158 @@
+//! State of the library that is tied to a Lua state.
+//!
+//! Nothing here is global: it is stored in the registry of the Lua state,
+//! so that several [Awesome](../awesome/struct.Awesome.html) instances can
+//! run side by side in one process.
+
+use lua_sys::*;
+use libc;
+use std::any::TypeId;
+use std::cell::Cell;
+use std::collections::{HashMap, LinkedList};
+use std::ptr;
+use std::sync::{Mutex, RwLock};
+use ::globalconf::GlobalConf;
+use ::lua::luaA::{self, ClassWrapper};
+use ::object::class::Class;
+use ::object::lua_class::CustomClass;
+use ::object::signal::Signals;
+use ::profiler::Profiler;
+use ::diagnostics::RegistryError;
+
+/// Key of the `State` in the registry.
+const STATE_KEY: &str = "awesome.state\0";
+
+/// Everything that belongs to one Lua state.
+pub struct State {
+    /// Signals connected with `awesome.connect_signal`
+    pub signals: Mutex<Signals>,
+    pub button_class: RwLock<Class>,
+    pub client_class: RwLock<Class>,
+    pub drawin_class: RwLock<Class>,
+    pub drawable_class: RwLock<Class>,
+    pub screen_class: RwLock<Class>,
+    pub tag_class: RwLock<Class>,
+    /// Classes defined with `LuaClass`, by the `TypeId` of their struct
+    pub custom_classes: RwLock<HashMap<TypeId, CustomClass>>,
+    /// All of the classes that have been set up
+    pub classes: Mutex<LinkedList<ClassWrapper>>,
+    /// Lua function to call on dofunction() error
+    pub error_func: RwLock<lua_CFunction>,
+    /// Timings of the callbacks and signals, when enabled
+    pub profiler: Mutex<Profiler>,
+    /// Set while `debug::error` is emitted
+    pub handling_error: Cell<bool>,
+    /// Misuses of the object registry, see the `diagnostics` module
+    pub registry_errors: Mutex<Vec<RegistryError>>,
+    /// Last key handed out by `luaA::object_weak_ref`
+    pub weak_keys: Cell<libc::c_int>,
+    /// The configuration of the `Awesome` using this state
+    conf: Cell<*const Mutex<GlobalConf>>,
+    /// The callbacks of the `Awesome` using this state
+    callbacks: Cell<Option<(TypeId, *const libc::c_void)>>
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            signals: Mutex::new(Signals::new()),
+            button_class: RwLock::new(Class::default()),
+            client_class: RwLock::new(Class::default()),
+            drawin_class: RwLock::new(Class::default()),
+            drawable_class: RwLock::new(Class::default()),
+            screen_class: RwLock::new(Class::default()),
+            tag_class: RwLock::new(Class::default()),
+            custom_classes: RwLock::new(HashMap::new()),
+            classes: Mutex::new(LinkedList::new()),
+            error_func: RwLock::new(Some(luaA::dofunction_on_error)),
+            profiler: Mutex::new(Profiler::default()),
+            handling_error: Cell::new(false),
+            registry_errors: Mutex::new(Vec::new()),
+            weak_keys: Cell::new(0),
+            conf: Cell::new(ptr::null()),
+            callbacks: Cell::new(None)
+        }
+    }
+}
+
+impl State {
+    /// Makes the configuration and the callbacks reachable from Lua.
+    ///
+    /// # Safety
+    /// Both must outlive the Lua state.
+    pub unsafe fn attach<T: 'static>(&self, conf: *const Mutex<GlobalConf>,
+                                     callbacks: *const T) {
+        self.conf.set(conf);
+        self.callbacks.set(Some((TypeId::of::<T>(), callbacks as _)));
+    }
+}
+
+/// Creates the `State` of a new Lua state.
+///
+/// The state is a userdata, so that it is dropped when the Lua state is
+/// closed. As it is the first object with a finalizer it is also the last
+/// one to be finalized, so the classes outlive their objects.
+pub unsafe fn setup(lua: *mut lua_State) {
+    let state = lua_newuserdata(lua, ::std::mem::size_of::<State>()) as *mut State;
+    ptr::write(state, State::default());
+    lua_newtable(lua);
+    lua_pushcfunction(lua, Some(state_gc));
+    lua_setfield(lua, -2, c_str!("__gc"));
+    lua_setmetatable(lua, -2);
+    lua_setfield(lua, LUA_REGISTRYINDEX, STATE_KEY.as_ptr() as _);
+}
+
+unsafe extern fn state_gc(lua: *mut lua_State) -> libc::c_int {
+    ptr::drop_in_place(lua_touserdata(lua, 1) as *mut State);
+    0
+}
+
+/// Gets the `State` of the Lua state.
+///
+/// # Panics
+/// If the Lua state was not created with [Lua::new](../struct.Lua.html#method.new).
+pub unsafe fn get<'a>(lua: *mut lua_State) -> &'a State {
+    lua_getfield(lua, LUA_REGISTRYINDEX, STATE_KEY.as_ptr() as _);
+    let state = lua_touserdata(lua, -1) as *const State;
+    lua_pop(lua, 1);
+    if state.is_null() {
+        panic!("Lua state has no awesome state, use Lua::new to create it");
+    }
+    &*state
+}
+
+/// Gets the configuration of the `Awesome` using the Lua state.
+///
+/// # Panics
+/// If the Lua state is not used by an `Awesome`.
+pub unsafe fn global_conf<'a>(lua: *mut lua_State) -> &'a Mutex<GlobalConf> {
+    match try_global_conf(lua) {
+        Some(conf) => conf,
+        None => panic!("Lua state is not used by an Awesome instance")
+    }
+}
+
+/// Gets the configuration of the `Awesome` using the Lua state, or `None`
+/// if the Lua state is used on its own.
+pub unsafe fn try_global_conf<'a>(lua: *mut lua_State)
+                                  -> Option<&'a Mutex<GlobalConf>> {
+    let conf = get(lua).conf.get();
+    if conf.is_null() {
+        None
+    } else {
+        Some(&*conf)
+    }
+}
+
+/// Gets the callbacks of the `Awesome` using the Lua state.
+///
+/// # Panics
+/// If the Lua state is not used by an `Awesome<T>`.
+pub unsafe fn callbacks<'a, T: 'static>(lua: *mut lua_State) -> &'a T {
+    match get(lua).callbacks.get() {
+        Some((type_id, callbacks)) if type_id == TypeId::of::<T>() =>
+            &*(callbacks as *const T),
+        _ => panic!("Lua state is not used by an Awesome instance with \
+                     these callbacks")
+    }
+}
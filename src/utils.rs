@@ -12,29 +12,32 @@ macro_rules! c_str {
     }
 }
 
-/// Registers a lua function in a LuaL_reg to call the given global identifier.
+/// Registers a lua function in a LuaL_reg to call the method of the same
+/// name on the callbacks of the `Awesome` that uses the Lua state.
 ///
-/// Do NOT use this macro directly, use the register_for_lua! instead.
+/// Do NOT use this macro directly, use the register_*! macros instead.
 #[macro_export]
 macro_rules! register_lua {
-    ($global_name:ident, $([ $( $inner:ident; $inner_lua_name:ident ),+ ])+) => {{
+    ($callback_impl:ident, $([ $( $inner:ident; $inner_lua_name:ident ),+ ])+) => {{
         use ::awesome_wayland::callbacks::Awesome;
         use ::libc::c_int;
-        $($(unsafe extern "C" fn $inner(lua: *mut lua_State) -> c_int {
-            let callback = $global_name.read()
-                .expect("Could not lock user defined callback object");
-            callback.callbacks.$inner(&**LUA)
+        $($(unsafe extern "C" fn $inner(lua: *mut ::awesome_wayland::lua_sys::lua_State) -> c_int {
+            let callbacks = ::awesome_wayland::state::callbacks::<$callback_impl>(lua);
+            ::awesome_wayland::profiler::profile(
+                lua, ::awesome_wayland::profiler::CallKind::Method,
+                stringify!($inner),
+                || callbacks.$inner(&::awesome_wayland::Lua(lua)))
         })*),*
             [
                 $($(register_lua!($inner, $inner_lua_name)),*),*,
-                ::lua_sys::luaL_Reg {
+                ::awesome_wayland::lua_sys::luaL_Reg {
                     name: ::std::ptr::null(),
                     func: None
                 },
             ]
     }};
     ($name:ident, $lua_name:ident) => {
-        ::lua_sys::luaL_Reg {
+        ::awesome_wayland::lua_sys::luaL_Reg {
             name: c_str!(stringify!($lua_name)),
             func: Some($name)
         }
@@ -45,16 +48,18 @@ macro_rules! register_lua {
 ///
 /// Note that errors for registering the method is up to the caller
 ///
-/// Use this in your main method, after using [register_for_lua](register_for_lua)
+/// Use this in your main method, with the `lua` of the [Awesome](awesome/struct.Awesome.html)
 #[macro_export]
 macro_rules! register_awesome {
-    ($callback_impl:ident, $global_name:ident) => {{
-        let awesome_lib = register_lua!($global_name, [
+    ($callback_impl:ident, $lua:expr) => {{
+        let lua: &::awesome_wayland::Lua = $lua;
+        let awesome_lib = register_lua!($callback_impl, [
             awesome_quit; quit,
             awesome_exec; exec,
             awesome_spawn; spawn,
             awesome_restart; restart,
             awesome_connect_signal; connect_signal,
+            awesome_weak_connect_signal; weak_connect_signal,
             awesome_disconnect_signal; disconnect_signal,
             awesome_emit_signal; emit_signal,
             awesome_systray; systray,
@@ -70,11 +75,15 @@ macro_rules! register_awesome {
             awesome_xkb_get_group_names; xkb_get_group_names,
             awesome_xrdb_get_value; xrdb_get_value,
             awesome_kill; kill,
-            awesome_sync; sync
+            awesome_sync; sync,
+            awesome_classes; classes,
+            awesome_class_info; class_info,
+            awesome_signals; signals,
+            awesome_diagnostics; diagnostics,
+            awesome_snapshot; snapshot
         ]);
-        let lua = LUA.0;
         unsafe {
-            luaA::openlib(lua, c_str!("awesome"), &awesome_lib, &awesome_lib);
+            ::awesome_wayland::luaA::openlib(lua.0, c_str!("awesome"), &awesome_lib, &awesome_lib);
         }
     }}
 }
@@ -83,16 +92,16 @@ macro_rules! register_awesome {
 ///
 /// Note that errors for registering the method is up to the caller
 ///
-/// Use this in your main method, after using [register_for_lua](register_for_lua)
+/// Use this in your main method, with the `lua` of the [Awesome](awesome/struct.Awesome.html)
 #[macro_export]
 macro_rules! register_button {
-    ($callback_impl:ident, $global_name:ident) => {{
+    ($callback_impl:ident, $lua:expr) => {{
+        let lua: &::awesome_wayland::Lua = $lua;
         use ::awesome_wayland::callbacks::Button;
         use ::awesome_wayland::callbacks::button::{button_new,
-                                                   button_get_button,
-                                                   button_get_modifiers};
+                                                   button_properties};
         use std::ptr::null_mut;
-        let button_methods = register_lua!($global_name, [
+        let button_methods = register_lua!($callback_impl, [
             button_add_signal; add_signal,
             button_connect_signal; connect_signal,
             button_disconnect_signal; disconnect_signal,
@@ -102,7 +111,7 @@ macro_rules! register_button {
             button_set_newindex_miss_handler; set_newindex_miss_handler,
             button___call; __call
         ]);
-        let button_meta = register_lua!($global_name, [
+        let button_meta = register_lua!($callback_impl, [
             button___tostring_meta; __tostring,
             button_connect_signal_meta; connect_signal,
             button_disconnect_signal_meta; button_disconnect_signal,
@@ -110,23 +119,18 @@ macro_rules! register_button {
             button___index_meta; __index,
             button___newindex_meta; __newindex
         ]);
-        let lua = LUA.0;
-
         unsafe {
-            let mut button_class = luaA::BUTTON_CLASS.try_write().unwrap();
-            luaA::class_setup(lua, &mut *button_class, c_str!("button"), null_mut() as _,
+            let mut button_class = ::awesome_wayland::state::get(lua.0).button_class.try_write().unwrap();
+            ::awesome_wayland::luaA::class_setup(lua.0, &mut *button_class,
+                              &::awesome_wayland::state::get(lua.0).button_class,
+                              c_str!("button"), null_mut() as _,
                             button_new, None, None,
-                            Some(luaA::class_index_miss_property),
-                            Some(luaA::class_newindex_miss_property),
+                            Some(::awesome_wayland::luaA::class_index_miss_property),
+                            Some(::awesome_wayland::luaA::class_newindex_miss_property),
                             &button_methods, &button_meta);
-            luaA::class_add_property(&mut *button_class, "button",
-                                    Some(luaA::button_set_button),
-                                    Some(button_get_button),
-                                    Some(luaA::button_set_button));
-            luaA::class_add_property(&mut *button_class, "modifiers",
-                                    Some(luaA::button_set_modifiers),
-                                    Some(button_get_modifiers),
-                                    Some(luaA::button_set_modifiers));
+            for property in button_properties() {
+                ::awesome_wayland::luaA::class_add_typed_property(&mut *button_class, property);
+            }
         }
     }}
 }
@@ -135,17 +139,32 @@ macro_rules! register_button {
 ///
 /// Note that errors for registering the method is up to the caller
 ///
-/// Use this in your main method, after using [register_for_lua](register_for_lua)
+/// Use this in your main method, with the `lua` of the [Awesome](awesome/struct.Awesome.html)
 #[macro_export]
 macro_rules! register_client {
-    ($callback_impl:ident, $global_name:ident) => {{
+    ($callback_impl:ident, $lua:expr) => {{
+        let lua: &::awesome_wayland::Lua = $lua;
         use ::awesome_wayland::callbacks::Client;
-        let lua_reg = register_lua!($global_name,  [
-            // Methods
-            client_get; get,
-            client___index; __index,
-            client___newindex; __newindex,
-            // Class methods
+        use ::awesome_wayland::callbacks::client::*;
+        /* Lets the compositor apply the properties set from Lua */
+        fn client_property_changed(lua: *mut ::awesome_wayland::lua_sys::lua_State,
+                                   client: *mut ::awesome_wayland::object::class::Object,
+                                   property: &str) {
+            unsafe {
+                let callbacks = ::awesome_wayland::state::callbacks::<$callback_impl>(lua);
+                let lua = ::awesome_wayland::Lua(lua);
+                callbacks.client_property_changed(&lua,
+                                                  &mut *(client as *mut ClientState),
+                                                  property);
+                /* These move the client to another layer */
+                match property {
+                    "ontop" | "above" | "below" | "fullscreen" =>
+                        stack_refresh(&lua, callbacks),
+                    _ => {}
+                }
+            }
+        }
+        let client_methods = register_lua!($callback_impl,  [
             client_add_signal; add_signal,
             client_connect_signal; connect_signal,
             client_disconnect_signal; disconnect_signal,
@@ -153,15 +172,19 @@ macro_rules! register_client {
             client_instances; instances,
             client_set_index_miss_handler; set_index_miss_handler,
             client_set_newindex_miss_handler; set_newindex_miss_handler,
-            // Object methods meta
-            client___tostring_meta; __tostring_meta,
-            client_connect_signal_meta; connect_signal_meta,
-            client_disconnect_signal_meta; button_disconnect_signal_meta,
-            // Class methods meta
-            client___index_meta; __index_meta,
-            client___newindex_meta; __newindex_meta,
             client___call; __call,
-            // Meta
+            client_get; get,
+            client_focus_history; focus_history,
+            client___index; __index,
+            client___newindex; __newindex
+        ]);
+        let client_meta = register_lua!($callback_impl, [
+            client___tostring_meta; __tostring,
+            client_connect_signal_meta; connect_signal,
+            client_disconnect_signal_meta; disconnect_signal,
+            client_emit_signal_meta; emit_signal,
+            client___index_meta; __index,
+            client___newindex_meta; __newindex,
             client_keys; keys,
             client_isvisible; isvisible,
             client_geometry; geometry,
@@ -171,54 +194,32 @@ macro_rules! register_client {
             client_swap; swap,
             client_raise; raise,
             client_lower; lower,
-            client_unmanange; unmanange,
+            client_unmanange; unmanage,
             client_titlebar_top; titlebar_top,
             client_titlebar_right; titlebar_right,
             client_titlebar_bottom; titlebar_bottom,
             client_titlebar_left; titlebar_left,
-            client_get_icon; get_icon,
-            // Properties
-            client_name; name,
-            client_transient_for; transient_for,
-            client_skip_taskbar; skip_taskbar,
-            client_content; content,
-            client_type_; type_,
-            client_class; class,
-            client_instance; instance,
-            client_role; role,
-            client_pid; pid,
-            client_leader_window; leader_window,
-            client_machine; machine,
-            client_icon_name; icon_name,
-            client_screen; screen,
-            client_hidden; hidden,
-            client_minimized; minimized,
-            client_fullscreen; fullscreen,
-            client_modal; modal,
-            client_group_window; group_window,
-            client_maximized; maximized,
-            client_maximized_horizontal; maximized_horizontal,
-            client_maximized_vertical; maximized_vertical,
-            client_icon; icon,
-            client_icon_sizes; icon_sizes,
-            client_ontop; ontop,
-            client_above; above,
-            client_below; below,
-            client_sticky; sticky,
-            client_size_hints_honor; size_hints_honor,
-            client_urgent; urgent,
-            client_size_hints; size_hints,
-            client_focusable; focusable,
-            client_shape_bounding; shape_bounding,
-            client_shape_clip; shape_clip,
-            client_shape_input; shape_input,
-            client_startup_id; startup_id,
-            client_client_shape_bounding; client_shape_bounding,
-            client_client_shape_clip; client_shape_clip,
-            client_first_tag; first_tag
+            client_get_icon; get_icon
         ]);
 
-        LUA.register_methods("client\0", &lua_reg)
+        unsafe {
+            let mut client_class = ::awesome_wayland::state::get(lua.0).client_class.try_write().unwrap();
+            ::awesome_wayland::luaA::class_setup(lua.0, &mut *client_class,
+                              &::awesome_wayland::state::get(lua.0).client_class,
+                              c_str!("client"),
+                              ::std::ptr::null_mut(),
+                              client_new, None, Some(client_checker),
+                              Some(::awesome_wayland::luaA::class_index_miss_property),
+                              Some(::awesome_wayland::luaA::class_newindex_miss_property),
+                              &client_methods, &client_meta);
+            client_class.removed_signal = "unmanage".into();
+            let properties = client_properties().into_iter()
+                .chain(client_callback_properties::<$callback_impl>());
+            for property in properties {
+                let property = property.on_change(client_property_changed);
+                ::awesome_wayland::luaA::class_add_typed_property(&mut *client_class, property);
+            }
+        }
     }}
 }
 
@@ -226,12 +227,14 @@ macro_rules! register_client {
 ///
 /// Note that errors for registering the method is up to the caller
 ///
-/// Use this in your main method, after using [register_for_lua](register_for_lua)
+/// Use this in your main method, with the `lua` of the [Awesome](awesome/struct.Awesome.html)
 #[macro_export]
 macro_rules! register_drawin {
-    ($callback_impl:ident, $global_name:ident) => {{
+    ($callback_impl:ident, $lua:expr) => {{
+        let lua: &::awesome_wayland::Lua = $lua;
         use ::awesome_wayland::callbacks::Drawin;
-        let lua_reg = register_lua!($global_name,  [
+        use ::awesome_wayland::callbacks::drawin::{drawin_new, drawin_checker};
+        let drawin_methods = register_lua!($callback_impl,  [
             drawin_add_signal; add_signal,
             drawin_connect_signal; connect_signal,
             drawin_disconnect_signal; disconnect_signal,
@@ -239,28 +242,40 @@ macro_rules! register_drawin {
             drawin_instances; instances,
             drawin_set_index_miss_handler; set_index_miss_handler,
             drawin_set_newindex_miss_handler; set_newindex_miss_handler,
-            drawin___call; __call,
-            drawin___tostring_meta; __tostring_meta,
-            drawin_connect_signal_meta; connect_signal_meta,
-            drawin_disconnect_signal_meta; disconnect_signal_meta,
-            drawin___index_meta; __index_meta,
-            drawin___newindex_meta; __newindex_meta
+            drawin___call; __call
+        ]);
+        let drawin_meta = register_lua!($callback_impl, [
+            drawin___tostring_meta; __tostring,
+            drawin_connect_signal_meta; connect_signal,
+            drawin_disconnect_signal_meta; disconnect_signal,
+            drawin___index_meta; __index,
+            drawin___newindex_meta; __newindex
         ]);
 
-        LUA.register_methods("drawin\0", &lua_reg)
+        unsafe {
+            let mut drawin_class = ::awesome_wayland::state::get(lua.0).drawin_class.try_write().unwrap();
+            ::awesome_wayland::luaA::class_setup(lua.0, &mut *drawin_class,
+                              &::awesome_wayland::state::get(lua.0).drawin_class,
+                              c_str!("drawin"),
+                              ::std::ptr::null_mut(),
+                              drawin_new, None, Some(drawin_checker),
+                              Some(::awesome_wayland::luaA::class_index_miss_property),
+                              Some(::awesome_wayland::luaA::class_newindex_miss_property),
+                              &drawin_methods, &drawin_meta);
+        }
     }}
 }
 /// Registers a struct that implements [Drawable](callbacks/trait.Drawable.html)
 ///
 /// Note that errors for registering the method is up to the caller
 ///
-/// Use this in your main method, after using [register_for_lua](register_for_lua)
+/// Use this in your main method, with the `lua` of the [Awesome](awesome/struct.Awesome.html)
 #[macro_export]
 macro_rules! register_drawable {
-    ($callback_impl:ident, $global_name:ident) => {{
+    ($callback_impl:ident, $lua:expr) => {{
+        let lua: &::awesome_wayland::Lua = $lua;
         use ::awesome_wayland::callbacks::{drawable, Drawable};
-        use ::awesome_wayland::luaA::DRAWABLE_CLASS;
-        let drawable_methods = register_lua!($global_name, [
+        let drawable_methods = register_lua!($callback_impl, [
             drawable_add_signal; add_signal,
             drawable_connect_signal; connect_signal,
             drawable_disconnect_signal; disconnect_signal,
@@ -269,7 +284,7 @@ macro_rules! register_drawable {
             drawable_set_index_miss_handler; set_index_miss_handler,
             drawable_set_newindex_miss_handler; set_newindex_miss_handler
         ]);
-        let drawable_meta = register_lua!($global_name, [
+        let drawable_meta = register_lua!($callback_impl, [
             drawable___tostring_meta; __to_string,
             drawable_connect_signal_meta; connect_signal,
             drawable_disconnect_signal_meta; disconnect_signal,
@@ -279,24 +294,24 @@ macro_rules! register_drawable {
             drawable_geometry; geometry
         ]);
 
-        let lua = LUA.0;
         unsafe {
-            let mut drawable_class = DRAWABLE_CLASS.try_write().unwrap();
-            luaA::class_setup(lua,
+            let mut drawable_class = ::awesome_wayland::state::get(lua.0).drawable_class.try_write().unwrap();
+            ::awesome_wayland::luaA::class_setup(lua.0,
                               &mut *drawable_class,
+                              &::awesome_wayland::state::get(lua.0).drawable_class,
                               c_str!("drawable"),
                               ::std::ptr::null_mut(),
                               drawable::new,
                               Some(drawable::wipe),
                               None,
-                              Some(luaA::class_index_miss_property),
-                              Some(luaA::class_newindex_miss_property),
+                              Some(::awesome_wayland::luaA::class_index_miss_property),
+                              Some(::awesome_wayland::luaA::class_newindex_miss_property),
                               &drawable_methods,
                               &drawable_meta);
-            luaA::class_add_property(&mut *drawable_class,
+            ::awesome_wayland::luaA::class_add_property(&mut *drawable_class,
                                      "surface",
                                      None,
-                                     Some(luaA::drawable_get_surface),
+                                     Some(::awesome_wayland::luaA::drawable_get_surface),
                                      None);
         }
     }}
@@ -306,12 +321,13 @@ macro_rules! register_drawable {
 ///
 /// Note that errors for registering the method is up to the caller
 ///
-/// Use this in your main method, after using [register_for_lua](register_for_lua)
+/// Use this in your main method, with the `lua` of the [Awesome](awesome/struct.Awesome.html)
 #[macro_export]
 macro_rules! register_keygrabber {
-    ($callback_impl:ident, $global_name:ident) => {{
+    ($callback_impl:ident, $lua:expr) => {{
+        let lua: &::awesome_wayland::Lua = $lua;
         use ::awesome_wayland::callbacks::Keygrabber;
-        let lua_reg = register_lua!($global_name,  [
+        let lua_reg = register_lua!($callback_impl,  [
             keygrabber_run; run,
             keygrabber_stop; stop,
             keygrabber_isrunning; isrunning,
@@ -319,7 +335,7 @@ macro_rules! register_keygrabber {
             keygrabber___newindex; __newindex
         ]);
 
-        LUA.register_methods("keygrabber\0", &lua_reg)
+        lua.register_methods("keygrabber", &lua_reg)
     }}
 }
 
@@ -327,12 +343,13 @@ macro_rules! register_keygrabber {
 ///
 /// Note that errors for registering the method is up to the caller
 ///
-/// Use this in your main method, after using [register_for_lua](register_for_lua)
+/// Use this in your main method, with the `lua` of the [Awesome](awesome/struct.Awesome.html)
 #[macro_export]
 macro_rules! register_mousegrabber {
-    ($callback_impl:ident, $global_name:ident) => {{
+    ($callback_impl:ident, $lua:expr) => {{
+        let lua: &::awesome_wayland::Lua = $lua;
         use ::awesome_wayland::callbacks::Mousegrabber;
-        let lua_reg = register_lua!($global_name,  [
+        let lua_reg = register_lua!($callback_impl,  [
             mousegrabber_run; run,
             mousegrabber_stop; stop,
             mousegrabber_isrunning; isrunning,
@@ -340,7 +357,7 @@ macro_rules! register_mousegrabber {
             mousegrabber___newindex; __newindex
         ]);
 
-        LUA.register_methods("mousegrabber\0", &lua_reg)
+        lua.register_methods("mousegrabber", &lua_reg)
     }}
 }
 
@@ -348,12 +365,13 @@ macro_rules! register_mousegrabber {
 ///
 /// Note that errors for registering the method is up to the caller
 ///
-/// Use this in your main method, after using [register_for_lua](register_for_lua)
+/// Use this in your main method, with the `lua` of the [Awesome](awesome/struct.Awesome.html)
 #[macro_export]
 macro_rules! register_mouse {
-    ($callback_impl:ident, $global_name:ident) => {{
+    ($callback_impl:ident, $lua:expr) => {{
+        let lua: &::awesome_wayland::Lua = $lua;
         use ::awesome_wayland::callbacks::Mouse;
-        let lua_reg = register_lua!($global_name,  [
+        let lua_reg = register_lua!($callback_impl,  [
             mouse___index; __index,
             mouse___newindex; __newindex,
             mouse_coords; coords,
@@ -362,7 +380,7 @@ macro_rules! register_mouse {
             mouse_set_newindex_miss_handler; set_newindex_miss_handler
         ]);
 
-        LUA.register_methods("mouse\0", &lua_reg)
+        lua.register_methods("mouse", &lua_reg)
     }}
 }
 
@@ -370,12 +388,13 @@ macro_rules! register_mouse {
 ///
 /// Note that errors for registering the method is up to the caller
 ///
-/// Use this in your main method, after using [register_for_lua](register_for_lua)
+/// Use this in your main method, with the `lua` of the [Awesome](awesome/struct.Awesome.html)
 #[macro_export]
 macro_rules! register_root {
-    ($callback_impl:ident, $global_name:ident) => {{
+    ($callback_impl:ident, $lua:expr) => {{
+        let lua: &::awesome_wayland::Lua = $lua;
         use ::awesome_wayland::callbacks::Root;
-        let lua_reg = register_lua!($global_name,  [
+        let lua_reg = register_lua!($callback_impl,  [
             root_buttons; buttons,
             root_keys; keys,
             root_cursor; cursor,
@@ -389,7 +408,7 @@ macro_rules! register_root {
             root___newindex; __newindex
         ]);
 
-        LUA.register_methods("root\0", &lua_reg)
+        lua.register_methods("root", &lua_reg)
     }}
 }
 
@@ -397,12 +416,14 @@ macro_rules! register_root {
 ///
 /// Note that errors for registering the method is up to the caller
 ///
-/// Use this in your main method, after using [register_for_lua](register_for_lua)
+/// Use this in your main method, with the `lua` of the [Awesome](awesome/struct.Awesome.html)
 #[macro_export]
 macro_rules! register_screen {
-    ($callback_impl:ident, $global_name:ident) => {{
+    ($callback_impl:ident, $lua:expr) => {{
+        let lua: &::awesome_wayland::Lua = $lua;
         use ::awesome_wayland::callbacks::Screen;
-        let lua_reg = register_lua!($global_name,  [
+        use ::awesome_wayland::callbacks::screen::{screen_new, screen_checker};
+        let screen_methods = register_lua!($callback_impl,  [
             screen_add_signal; add_signal,
             screen_connect_signal; connect_signal,
             screen_disconnect_signal; disconnect_signal,
@@ -415,22 +436,34 @@ macro_rules! register_screen {
             screen___newindex; __newindex,
             screen___call; __call,
             screen_fake_add; fake_add,
-            screen___tostring_meta; __tostring_meta,
-            screen_connect_signal_meta; connect_signal_meta,
-            screen_disconnect_signal_meta; disconnect_signal_meta,
-            screen___index_meta; __index_meta,
-            screen___newindex_meta; __newindex_meta,
-            screen_fake_remove; fake_remove,
-            screen_fake_resize; fake_resize,
-            screen_swap; swap,
             // properties
             screen_geometry; geometry,
             screen_index; index,
             screen_outputs; outputs,
             screen_workarea; workarea
         ]);
+        let screen_meta = register_lua!($callback_impl, [
+            screen___tostring_meta; __tostring,
+            screen_connect_signal_meta; connect_signal,
+            screen_disconnect_signal_meta; disconnect_signal,
+            screen___index_meta; __index,
+            screen___newindex_meta; __newindex,
+            screen_fake_remove; fake_remove,
+            screen_fake_resize; fake_resize,
+            screen_swap; swap
+        ]);
 
-        LUA.register_methods("screen\0", &lua_reg)
+        unsafe {
+            let mut screen_class = ::awesome_wayland::state::get(lua.0).screen_class.try_write().unwrap();
+            ::awesome_wayland::luaA::class_setup(lua.0, &mut *screen_class,
+                              &::awesome_wayland::state::get(lua.0).screen_class,
+                              c_str!("screen"),
+                              ::std::ptr::null_mut(),
+                              screen_new, None, Some(screen_checker),
+                              Some(::awesome_wayland::luaA::class_index_miss_property),
+                              Some(::awesome_wayland::luaA::class_newindex_miss_property),
+                              &screen_methods, &screen_meta);
+        }
     }}
 }
 
@@ -438,12 +471,14 @@ macro_rules! register_screen {
 ///
 /// Note that errors for registering the method is up to the caller
 ///
-/// Use this in your main method, after using [register_for_lua](register_for_lua)
+/// Use this in your main method, with the `lua` of the [Awesome](awesome/struct.Awesome.html)
 #[macro_export]
 macro_rules! register_tag {
-    ($callback_impl:ident, $global_name:ident) => {{
+    ($callback_impl:ident, $lua:expr) => {{
+        let lua: &::awesome_wayland::Lua = $lua;
         use ::awesome_wayland::callbacks::Tag;
-        let lua_reg = register_lua!($global_name,  [
+        use ::awesome_wayland::callbacks::tag::tag_new;
+        let tag_methods = register_lua!($callback_impl,  [
             tag_add_signal; add_signal,
             tag_connect_signal; connect_signal,
             tag_disconnect_signal; disconnect_signal,
@@ -452,18 +487,30 @@ macro_rules! register_tag {
             tag_set_index_miss_handler; set_index_miss_handler,
             tag_set_newindex_miss_handler; set_newindex_miss_handler,
             tag___call; __call,
-            tag___tostring_meta; __tostring_meta,
-            tag_connect_signal_meta; connect_signal_meta,
-            tag_disconnect_signal_meta; disconnect_signal_meta,
-            tag___index_meta; __index_meta,
-            tag___newindex_meta; __newindex_meta,
-            tag_clients_meta; clients_meta,
             tag_name; name,
             tag_selected; selected,
             tag_activated; activated
         ]);
+        let tag_meta = register_lua!($callback_impl, [
+            tag___tostring_meta; __tostring,
+            tag_connect_signal_meta; connect_signal,
+            tag_disconnect_signal_meta; disconnect_signal,
+            tag___index_meta; __index,
+            tag___newindex_meta; __newindex,
+            tag_clients_meta; clients
+        ]);
 
-        LUA.register_methods("tag\0", &lua_reg)
+        unsafe {
+            let mut tag_class = ::awesome_wayland::state::get(lua.0).tag_class.try_write().unwrap();
+            ::awesome_wayland::luaA::class_setup(lua.0, &mut *tag_class,
+                              &::awesome_wayland::state::get(lua.0).tag_class,
+                              c_str!("tag"),
+                              ::std::ptr::null_mut(),
+                              tag_new, None, None,
+                              Some(::awesome_wayland::luaA::class_index_miss_property),
+                              Some(::awesome_wayland::luaA::class_newindex_miss_property),
+                              &tag_methods, &tag_meta);
+        }
     }}
 }
 
@@ -477,40 +524,22 @@ macro_rules! register_tag {
 /// (which is unlikely, they should work) then use the individual register_*!
 #[macro_export]
 macro_rules! register_all {
-    ($callback_impl:ident, $global_name:ident) => {{
-        register_awesome!($callback_impl, $global_name);
-        register_button!($callback_impl, $global_name);
-        register_client!($callback_impl, $global_name).unwrap();
-        register_drawin!($callback_impl, $global_name).unwrap();
-        register_drawable!($callback_impl, $global_name);
-        register_keygrabber!($callback_impl, $global_name).unwrap();
-        register_mousegrabber!($callback_impl, $global_name).unwrap();
-        register_mouse!($callback_impl, $global_name).unwrap();
-        register_root!($callback_impl, $global_name).unwrap();
-        register_screen!($callback_impl, $global_name).unwrap();
-        register_tag!($callback_impl, $global_name).unwrap();
+    ($callback_impl:ident, $lua:expr) => {{
+        let lua: &::awesome_wayland::Lua = $lua;
+        register_awesome!($callback_impl, lua);
+        register_button!($callback_impl, lua);
+        register_client!($callback_impl, lua);
+        register_drawin!($callback_impl, lua);
+        register_drawable!($callback_impl, lua);
+        register_keygrabber!($callback_impl, lua).unwrap();
+        register_mousegrabber!($callback_impl, lua).unwrap();
+        register_mouse!($callback_impl, lua).unwrap();
+        register_root!($callback_impl, lua).unwrap();
+        register_screen!($callback_impl, lua);
+        register_tag!($callback_impl, lua);
     }}
 }
 
-/// Registers a new instance of the passed-in user object as a global
-/// singleton that will be used for all of the Lua callbacks.
-///
-/// This also registers a global named `LUA` that is an instance of
-/// [Lua](../awesome_wayland/struct.Lua.html). This is used by both the user
-/// and internally by the library, which is why it needs a pre-defined name.
-#[macro_export]
-macro_rules! register_for_lua {
-    ($callback_impl:ident, $global_name:ident) => {
-        use ::std::sync::{RwLock, Arc};
-        lazy_static! {
-            #[allow(non_upper_case_globals)]
-            pub static ref $global_name: RwLock<Awesome<$callback_impl>> =
-                RwLock::new(Awesome::new());
-            pub static ref LUA: Arc<Lua> = Arc::new(Lua::new());
-        }
-    }
-}
-
 /// Defines properties for the method
 /// Eventually, this will automatically set the correct values in lua
 /// so that they can be used as accessors, e.g []
@@ -540,18 +569,31 @@ pub unsafe fn luaL_opt(lua: *mut lua_State, f: fn(*mut lua_State, libc::c_int) -
 
 /// Defines the lua object functions. This is functionally equiv to
 /// the C macro LUA_OBJECT_FUNCS from the awesome lib.
+///
+/// `$lua_class` is the name of the class in the [State](state/struct.State.html).
+/// `$init`, if given, is called on the new object once it's zeroed, e.g to
+/// mark it valid.
 #[macro_export]
 macro_rules! LUA_OBJECT_FUNCS {
-    ($lua_class:path, $type:ty, $new_name:ident) => {
+    ($lua_class:ident, $type:ty, $new_name:ident) => {
+        LUA_OBJECT_FUNCS!($lua_class, $type, $new_name, |_: &mut $type| {});
+    };
+    ($lua_class:ident, $type:ty, $new_name:ident, $init:expr) => {
         use std::ptr;
         pub unsafe extern fn $new_name(lua: *mut lua_State) -> *mut Object {
             let type_size =::std::mem::size_of::<$type>();
             let p = lua_newuserdata(lua, type_size) as *mut $type;
             ptr::write_bytes::<$type>(p, 0, 1);
-            let class = $lua_class.try_read().unwrap();
-            let old_instances = class.instances.get();
-            class.instances.set(old_instances + 1);
-            luaA::settype(lua, &*class);
+            /* Every object starts with its signals, which can't be zeroed */
+            ptr::write(p as *mut Object,
+                       Object { signals: ::object::signal::Signals::new() });
+            ($init)(&mut *p);
+            /* Not locked, as the handlers of the new signal may use the class */
+            let class = &*::state::get(lua).$lua_class.try_read().unwrap()
+                as *const ::object::class::Class;
+            let old_instances = (*class).instances.get();
+            (*class).instances.set(old_instances + 1);
+            luaA::settype(lua, class);
             lua_newtable(lua);
             lua_newtable(lua);
             lua_setmetatable(lua, -2);
@@ -559,7 +601,7 @@ macro_rules! LUA_OBJECT_FUNCS {
             lua_setfield(lua, -2, c_str!("data"));
             luaA::setuservalue(lua, -2);
             lua_pushvalue(lua, -1);
-            luaA::class_emit_signal(lua, &*class,
+            luaA::class_emit_signal(lua, class,
                                     c_str!("new"), 1);
             return p as _;
         }
@@ -568,7 +610,7 @@ macro_rules! LUA_OBJECT_FUNCS {
 
 #[macro_export]
 macro_rules! LUA_CLASS_FUNCS {
-    ($lua_class:path, $add_sig:ident, $con_sig: ident, $discon_sig:ident,
+    ($lua_class:ident, $add_sig:ident, $con_sig: ident, $discon_sig:ident,
      $emit_sig:ident, $class_inst: ident, $index_miss:ident,
      $newindex_miss:ident) => {
         #[allow(unused_imports)]
@@ -576,25 +618,33 @@ macro_rules! LUA_CLASS_FUNCS {
         use ::std::ptr::null_mut;
         use ::libc;
 
-        unsafe extern fn $add_sig(_lua: *mut lua_State) -> libc::c_int {
+        unsafe extern fn $add_sig(lua: *mut lua_State) -> libc::c_int {
             eprintln!("signal usage with add_signal()");
+            let name = luaL_checklstring(lua, 1, null_mut());
+            let name = ::std::ffi::CStr::from_ptr(name).to_string_lossy().into_owned();
+            let mut class = ::state::get(lua).$lua_class.try_write().unwrap();
+            class.declare_signal(name);
             0
         }
 
         unsafe extern fn $con_sig(lua: *mut lua_State) -> libc::c_int {
+            let weak = ::luaA::signal_connect_args(lua, 1);
             let check_string = luaL_checklstring(lua, 1, null_mut());
-            let mut class = $lua_class.try_write().unwrap();
-            ::luaA::class_connect_signal_from_stack(lua,
-                                                  &mut *class,
-                                                  check_string,
-                                                  2);
+            /* Checked before locking, as a Lua error would keep it locked */
+            let func = ::luaA::signal_func_from_stack(lua, 2, weak);
+            let name = ::std::ffi::CStr::from_ptr(check_string)
+                .to_string_lossy().into_owned();
+            let mut class = ::state::get(lua).$lua_class.try_write().unwrap();
+            class.signals.prune(lua);
+            class.signals.connect(name, func);
             0
 
         }
 
         unsafe extern fn $discon_sig(lua: *mut lua_State) -> libc::c_int {
             let check_string = luaL_checklstring(lua, 1, null_mut());
-            let mut class = $lua_class.try_write().unwrap();
+            ::luaA::checkfunction(lua, 2);
+            let mut class = ::state::get(lua).$lua_class.try_write().unwrap();
             ::luaA::class_disconnect_signal_from_stack(lua,
                                                      &mut *class,
                                                      check_string,
@@ -604,26 +654,30 @@ macro_rules! LUA_CLASS_FUNCS {
 
         unsafe extern fn $emit_sig(lua: *mut lua_State) -> libc::c_int {
             let check_string = luaL_checklstring(lua, 1, null_mut());
-            let mut class = $lua_class.try_write().unwrap();
-            ::luaA::class_emit_signal(lua, &mut *class,
+            /* Not locked, as the handlers may use the class */
+            let class = &*::state::get(lua).$lua_class.read().unwrap()
+                as *const ::object::class::Class;
+            ::luaA::class_emit_signal(lua, class,
                                     check_string, lua_gettop(lua) -1);
             0
         }
 
         unsafe extern fn $class_inst(lua: *mut lua_State) -> libc::c_int {
-            let class = $lua_class.try_write().unwrap();
+            let class = ::state::get(lua).$lua_class.try_write().unwrap();
             lua_pushinteger(lua, class.instances.get() as lua_Integer);
             1
         }
 
         unsafe extern fn $index_miss(lua: *mut lua_State) -> libc::c_int {
-            let mut class = $lua_class.try_write().unwrap();
-            ::luaA::registerfct(lua, 1, &mut class.newindex_miss_handler)
+            ::luaA::checkfunction(lua, 1);
+            let mut class = ::state::get(lua).$lua_class.try_write().unwrap();
+            ::luaA::registerfct(lua, 1, &mut class.index_miss_handler)
         }
 
         unsafe extern fn $newindex_miss(lua: *mut lua_State) -> libc::c_int {
-            let mut class = $lua_class.try_write().unwrap();
-            luaA::registerfct(lua, 1, &mut class.newindex_miss_handler)
+            ::luaA::checkfunction(lua, 1);
+            let mut class = ::state::get(lua).$lua_class.try_write().unwrap();
+            ::luaA::registerfct(lua, 1, &mut class.newindex_miss_handler)
         }
     }
 }